@@ -1,9 +1,13 @@
-use super::{Mutation, Recombination};
+use super::{EvolutionError, Genotype, Mutation, Phenotype, Recombination};
+use super::memetic::{HillClimber, Improved, Improvement, LocalSearch};
+use super::diversity::{mean_pairwise_distance, Diversity, GenotypeDistance, LocusStats};
+use super::hall_of_fame::TextEncoding;
+use super::random;
 use bit_vec::BitVec;
-use rand::{self, Rng};
-use std::{clone, cmp};
+use rand::Rng;
+use std::clone;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct BinaryChromosome {
     pub bits: BitVec,
 }
@@ -11,9 +15,10 @@ pub struct BinaryChromosome {
 impl BinaryChromosome {
     pub fn new(size: usize) -> BinaryChromosome {
         let mut bits = BitVec::with_capacity(size);
+        let mut rng = random::rng();
 
         for _ in 0..size {
-            bits.push(rand::random());
+            bits.push(rng.gen());
         }
 
         BinaryChromosome {
@@ -32,6 +37,76 @@ impl BinaryChromosome {
             bits: BitVec::from_elem(size, true)
         }
     }
+
+    /// Returns the number of bits that are set. See [count_ones].
+    pub fn count_ones(&self) -> usize {
+        count_ones(&self.bits)
+    }
+
+    /// Gives mutable access to the storage blocks of the bit vector. Bit `i` is stored in block
+    /// `i / BLOCK_BITS` at position `i % BLOCK_BITS`, counting from the least significant bit.
+    /// Afterwards, the unused bits in the last block are cleared, whatever `update` wrote there.
+    fn update_blocks(&mut self, update: impl FnOnce(&mut [u32])) {
+        let len = self.bits.len();
+        // SAFETY: The bit vector relies on its storage having the right number of blocks, and on
+        // the unused bits in the last block being zero. Only a slice of the storage is handed
+        // out, so the number of blocks cannot change, and the unused bits are cleared below.
+        let blocks = unsafe { &mut self.bits.storage_mut()[..] };
+        update(blocks);
+
+        let used = len % BLOCK_BITS;
+        if let Some(last) = blocks.last_mut().filter(|_| used != 0) {
+            *last &= (1u32 << used) - 1;
+        }
+    }
+}
+
+/// The number of bits in a storage block of a bit vector.
+const BLOCK_BITS: usize = 32;
+
+/// Returns the number of bits that are set. It counts them a storage block at a time, which makes
+/// it a fast building block for fitness functions.
+pub fn count_ones(bits: &BitVec) -> usize {
+    bits.storage().iter().map(|block| block.count_ones() as usize).sum()
+}
+
+/// Copies the bits in range [from, to> from one block storage to another. Blocks that are fully
+/// in range are copied as a whole, only the blocks at the edges need masking.
+fn copy_bit_range(target: &mut [u32], source: &[u32], from: usize, to: usize) {
+    if from >= to {
+        return;
+    }
+
+    let first = from / BLOCK_BITS;
+    let last = (to - 1) / BLOCK_BITS;
+    for i in first..=last {
+        let lo = if i == first { from % BLOCK_BITS } else { 0 };
+        let hi = if i == last { (to - 1) % BLOCK_BITS + 1 } else { BLOCK_BITS };
+        let mask = if hi - lo == BLOCK_BITS { !0 } else { ((1u32 << (hi - lo)) - 1) << lo };
+
+        target[i] = (target[i] & !mask) | (source[i] & mask);
+    }
+}
+
+/// Returns a block of random bits, where each bit is set with probability `k / 2^24`. It
+/// requires that `k` is in range [1, 2^24>.
+///
+/// It processes the binary digits of the probability, starting with the least significant one
+/// that is set. Each step halves the probability, and adds one half when the digit is set. This
+/// is done by combining the mask so far with a block of uniformly random bits, using AND for a
+/// zero digit and OR for a one digit.
+fn random_mask<R: Rng>(rng: &mut R, k: u32) -> u32 {
+    let mut mask = rng.gen::<u32>();
+
+    for digit in k.trailing_zeros() + 1..24 {
+        mask = if k & (1 << digit) != 0 {
+            mask | rng.gen::<u32>()
+        } else {
+            mask & rng.gen::<u32>()
+        };
+    }
+
+    mask
 }
 
 impl clone::Clone for BinaryChromosome {
@@ -40,6 +115,92 @@ impl clone::Clone for BinaryChromosome {
             bits: self.bits.clone()
         }
     }
+
+    fn clone_from(&mut self, source: &Self) {
+        // Re-uses the storage of the bit vector
+        self.bits.clone_from(&source.bits);
+    }
+}
+
+/// Encodes the bits as a string of `0`s and `1`s.
+impl TextEncoding for BinaryChromosome {
+    fn encode(&self) -> String {
+        self.bits.iter().map(|bit| if bit { '1' } else { '0' }).collect()
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let bits = text.chars().map(|char| match char {
+            '0' => Some(false),
+            '1' => Some(true),
+            _ => None,
+        }).collect::<Option<BitVec>>()?;
+
+        Some(BinaryChromosome {
+            bits
+        })
+    }
+}
+
+impl GenotypeDistance for BinaryChromosome {
+    /// Returns the Hamming distance. When the lengths differ, each bit beyond the end of the
+    /// shorter chromosome counts as a difference.
+    fn distance(&self, other: &Self) -> f32 {
+        let len = self.bits.len().min(other.bits.len());
+        let (blocks, other_blocks) = (self.bits.storage(), other.bits.storage());
+        let full_blocks = len / BLOCK_BITS;
+
+        let mut differences: usize = blocks[..full_blocks].iter().zip(&other_blocks[..full_blocks])
+            .map(|(a, b)| (a ^ b).count_ones() as usize)
+            .sum();
+        let rest = len % BLOCK_BITS;
+        if rest > 0 {
+            let mask = (1u32 << rest) - 1;
+            differences += ((blocks[full_blocks] ^ other_blocks[full_blocks]) & mask)
+                .count_ones() as usize;
+        }
+
+        (differences + self.bits.len().abs_diff(other.bits.len())) as f32
+    }
+
+    /// Measures the diversity, including per-locus statistics when all chromosomes have the same
+    /// length. In that case, the mean Hamming distance is derived from the number of ones at each
+    /// locus, which avoids comparing all pairs.
+    fn diversity(genotypes: &[&Self]) -> Diversity {
+        let len = genotypes.first().map_or(0, |genotype| genotype.bits.len());
+        if genotypes.iter().any(|genotype| genotype.bits.len() != len) {
+            return Diversity {
+                mean_distance: mean_pairwise_distance(genotypes),
+                loci: None,
+            };
+        }
+
+        let mut ones = vec![0; len];
+        for genotype in genotypes.iter() {
+            for (i, block) in genotype.bits.storage().iter().enumerate() {
+                let mut block = *block;
+                while block != 0 {
+                    ones[i * BLOCK_BITS + block.trailing_zeros() as usize] += 1;
+                    block &= block - 1;
+                }
+            }
+        }
+
+        // A locus with c ones contributes a difference to each of the c * (n - c) pairs of a one
+        // and a zero
+        let num = genotypes.len();
+        let num_pairs = num * num.saturating_sub(1) / 2;
+        let mean_distance = if num_pairs == 0 {
+            0.0
+        } else {
+            let differences: usize = ones.iter().map(|count| count * (num - count)).sum();
+            (differences as f64 / num_pairs as f64) as f32
+        };
+
+        Diversity {
+            mean_distance,
+            loci: Some(LocusStats::from_counts(&ones, num)),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -48,10 +209,18 @@ pub struct BinaryBitMutation {
 }
 
 impl BinaryBitMutation {
-    pub fn new(mutate_prob: f32) -> Self {
-        BinaryBitMutation {
-            mutate_prob
+    /// Creates a new Binary Bit Mutation operator.
+    ///
+    /// The mutation probability is the probability that each bit is flipped. It should be in
+    /// range [0, 1].
+    pub fn new(mutate_prob: f32) -> Result<Self, EvolutionError> {
+        if !(0.0..=1.0).contains(&mutate_prob) {
+            return Err(EvolutionError::InvalidParameter("mutate_prob", "in range [0, 1]"));
         }
+
+        Ok(BinaryBitMutation {
+            mutate_prob
+        })
     }
 }
 
@@ -73,10 +242,16 @@ impl Mutation for BinaryBitMutation {
         //   P(n <= N) = 1 - (1 - p)^N
         //
         // Where P(n <= N) is the probability that at least one of the "N" next bits changes.
+        //
+        // The formula does not hold for p = 0, as the offset would then be NaN or -inf, which the
+        // cast maps to zero.
+        if self.mutate_prob == 0.0 {
+            return;
+        }
         let denom = (1.0 - self.mutate_prob).ln();
         let mut i = 0;
         loop {
-            let num = (1.0 - rand::thread_rng().gen::<f32>()).ln();
+            let num = (1.0 - random::rng().gen::<f32>()).ln();
 
             // Note: the cast rounds towards zero and maps the infinity float value and other
             // values that are "too big" to the maximum integer value, which is what we want.
@@ -109,29 +284,43 @@ impl Recombination for BinaryNPointBitCrossover {
 
     fn recombine(
         &self, parent1: &Self::Genotype, parent2: &Self::Genotype
-    ) -> Self::Genotype {
+    ) -> Result<Self::Genotype, EvolutionError> {
+        let mut child = BinaryChromosome::zeroes(0);
+        self.recombine_into(parent1, parent2, &mut child)?;
+
+        Ok(child)
+    }
+
+    fn recombine_into(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype, child: &mut Self::Genotype
+    ) -> Result<(), EvolutionError> {
+        check_equal_length(parent1, parent2)?;
+
+        let range = parent1.bits.len();
+        if self.n > 0 && range < 2 {
+            // There must be at least one position to cross over at
+            return Err(EvolutionError::ChromosomeTooShort(range, 2));
+        }
 
-        let range = cmp::min(parent1.bits.len(), parent2.bits.len());
         let mut points: Vec<usize> = (0..self.n).map(
-            |_| rand::thread_rng().gen_range(1..range)
+            |_| random::rng().gen_range(1..range)
         ).collect();
-        &points[..].sort_unstable();
+        points.sort_unstable();
 
         if self.n % 2 == 1 {
             // Ensure that number of points is even
-            points.push(parent1.bits.len());
+            points.push(range);
         }
 
-        let mut child = parent1.clone();
-        for i in 0..points.len() / 2 {
-            let from = points[i * 2];
-            let to = points[i * 2 + 1];
-            for j in from..to {
-                child.bits.set(j, parent2.bits.get(j).unwrap());
+        child.clone_from(parent1);
+        let source = parent2.bits.storage();
+        child.update_blocks(|target| {
+            for i in 0..points.len() / 2 {
+                copy_bit_range(target, source, points[i * 2], points[i * 2 + 1]);
             }
-        }
+        });
 
-        child
+        Ok(())
     }
 }
 
@@ -148,14 +337,14 @@ impl BinaryUniformRecombination {
     /// equal probability from both parents. As bias increases, the one of the bits from one
     /// parent are increasingly favoured. As bias approaches 1, all bits are selected from one
     /// parent which means there is no recombination.
-    pub fn new(bias: f32) -> Self {
-        if bias < 0.0 || bias >= 1.0 {
-            panic!("Bias out of range");
+    pub fn new(bias: f32) -> Result<Self, EvolutionError> {
+        if !(0.0..1.0).contains(&bias) {
+            return Err(EvolutionError::InvalidParameter("bias", "in range [0, 1>"));
         }
 
-        BinaryUniformRecombination {
+        Ok(BinaryUniformRecombination {
             bias
-        }
+        })
     }
 }
 
@@ -164,16 +353,338 @@ impl Recombination for BinaryUniformRecombination {
 
     fn recombine(
         &self, parent1: &Self::Genotype, parent2: &Self::Genotype
-    ) -> Self::Genotype {
-        let mut child = parent1.clone();
+    ) -> Result<Self::Genotype, EvolutionError> {
+        let mut child = BinaryChromosome::zeroes(0);
+        self.recombine_into(parent1, parent2, &mut child)?;
+
+        Ok(child)
+    }
+
+    fn recombine_into(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype, child: &mut Self::Genotype
+    ) -> Result<(), EvolutionError> {
+        check_equal_length(parent1, parent2)?;
+
+        child.clone_from(parent1);
+
+        // Each bit is taken from the second parent when a random float is at least the limit.
+        // Random floats are multiples of 2^-24 in range [0, 1>, so this happens with probability
+        // k / 2^24, where k is the number of multiples that are at least the limit.
         let limit = 0.5 * (1.0 + self.bias);
+        let k = (1u32 << 24) - (limit * (1u32 << 24) as f32).ceil() as u32;
+        if k == 0 {
+            return Ok(());
+        }
 
-        for i in 0..child.bits.len() {
-            if rand::thread_rng().gen::<f32>() >= limit {
-                child.bits.set(i, parent2.bits.get(i).unwrap());
+        let mut rng = random::rng();
+        let source = parent2.bits.storage();
+        child.update_blocks(|target| {
+            for (block, source) in target.iter_mut().zip(source) {
+                let mask = random_mask(&mut rng, k);
+                *block = (*block & !mask) | (source & mask);
             }
+        });
+
+        Ok(())
+    }
+}
+
+/// The minimum and maximum length of variable-length chromosomes. Operators that change the
+/// length of a chromosome never produce chromosomes outside these limits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthLimits {
+    min: usize,
+    max: usize,
+}
+
+impl LengthLimits {
+    pub fn new(min: usize, max: usize) -> Result<Self, EvolutionError> {
+        if min > max {
+            return Err(EvolutionError::InvalidParameter("min", "at most max"));
+        }
+
+        Ok(LengthLimits {
+            min,
+            max
+        })
+    }
+
+    pub fn min(&self) -> usize {
+        self.min
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Chooses the length of the head of the first parent and of the tail of the second parent
+    /// that make up a cut-and-splice child, such that the child is within limits.
+    pub(crate) fn random_splice(
+        &self, len1: usize, len2: usize
+    ) -> Result<(usize, usize), EvolutionError> {
+        // The length of the head should be such that a tail can be found that completes it to a
+        // child whose length is within limits.
+        let min_head = self.min.saturating_sub(len2);
+        let max_head = len1.min(self.max);
+        if min_head > max_head {
+            return Err(EvolutionError::ChromosomeTooShort(len1 + len2, self.min));
+        }
+        let head = random::rng().gen_range(min_head..=max_head);
+
+        let min_tail = self.min.saturating_sub(head);
+        let max_tail = len2.min(self.max - head);
+        let tail = random::rng().gen_range(min_tail..=max_tail);
+
+        Ok((head, tail))
+    }
+}
+
+/// Penalises chromosomes that are longer than a given target length. This can be used by
+/// phenotypes to counteract bloat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LengthPenalty {
+    target: usize,
+    weight: f32,
+}
+
+impl LengthPenalty {
+    /// Creates a length penalty, which subtracts `weight` from the fitness for every bit that
+    /// the chromosome is longer than `target`. It is meant for fitness that is maximised.
+    pub fn new(target: usize, weight: f32) -> Self {
+        LengthPenalty {
+            target,
+            weight
+        }
+    }
+
+    pub fn apply(&self, fitness: f32, length: usize) -> f32 {
+        fitness - self.weight * length.saturating_sub(self.target) as f32
+    }
+}
+
+/// Returns the length of a random segment of at most `max_len` bits. It returns zero when
+/// `max_len` is zero.
+fn random_segment_len(max_len: usize) -> usize {
+    if max_len == 0 {
+        0
+    } else {
+        random::rng().gen_range(1..=max_len)
+    }
+}
+
+/// Inserts the bits of `segment` into the chromosome, before the bit at `pos`.
+fn insert_bits(target: &mut BinaryChromosome, pos: usize, segment: &BitVec) {
+    let mut bits = BitVec::with_capacity(target.bits.len() + segment.len());
+
+    bits.extend(target.bits.iter().take(pos));
+    bits.extend(segment.iter());
+    bits.extend(target.bits.iter().skip(pos));
+
+    target.bits = bits;
+}
+
+/// Inserts a segment of random bits at a random position. The segment contains at least one and
+/// at most `max_segment` bits, but never makes the chromosome exceed its maximum length.
+#[derive(Debug)]
+pub struct BinaryInsertionMutation {
+    limits: LengthLimits,
+    max_segment: usize,
+}
+
+impl BinaryInsertionMutation {
+    pub fn new(limits: LengthLimits, max_segment: usize) -> Self {
+        BinaryInsertionMutation {
+            limits,
+            max_segment
+        }
+    }
+}
+
+impl Mutation for BinaryInsertionMutation {
+    type Genotype = BinaryChromosome;
+
+    fn mutate(&self, target: &mut Self::Genotype) {
+        let len = target.bits.len();
+        let segment_len = random_segment_len(
+            self.max_segment.min(self.limits.max.saturating_sub(len))
+        );
+        if segment_len == 0 {
+            return;
+        }
+
+        let pos = random::rng().gen_range(0..=len);
+        insert_bits(target, pos, &BinaryChromosome::new(segment_len).bits);
+    }
+}
+
+/// Deletes a segment of bits at a random position. The segment contains at least one and at
+/// most `max_segment` bits, but never makes the chromosome shorter than its minimum length.
+#[derive(Debug)]
+pub struct BinaryDeletionMutation {
+    limits: LengthLimits,
+    max_segment: usize,
+}
+
+impl BinaryDeletionMutation {
+    pub fn new(limits: LengthLimits, max_segment: usize) -> Self {
+        BinaryDeletionMutation {
+            limits,
+            max_segment
+        }
+    }
+}
+
+impl Mutation for BinaryDeletionMutation {
+    type Genotype = BinaryChromosome;
+
+    fn mutate(&self, target: &mut Self::Genotype) {
+        let len = target.bits.len();
+        let segment_len = random_segment_len(
+            self.max_segment.min(len.saturating_sub(self.limits.min))
+        );
+        if segment_len == 0 {
+            return;
+        }
+
+        let pos = random::rng().gen_range(0..=len - segment_len);
+        target.bits = target.bits.iter().enumerate().filter(
+            |(i, _)| *i < pos || *i >= pos + segment_len
+        ).map(|(_, bit)| bit).collect();
+    }
+}
+
+/// Duplicates a randomly chosen segment of bits. The copy is inserted directly after the
+/// original segment. The segment contains at least one and at most `max_segment` bits, but
+/// never makes the chromosome exceed its maximum length.
+#[derive(Debug)]
+pub struct BinaryDuplicationMutation {
+    limits: LengthLimits,
+    max_segment: usize,
+}
+
+impl BinaryDuplicationMutation {
+    pub fn new(limits: LengthLimits, max_segment: usize) -> Self {
+        BinaryDuplicationMutation {
+            limits,
+            max_segment
         }
+    }
+}
+
+impl Mutation for BinaryDuplicationMutation {
+    type Genotype = BinaryChromosome;
+
+    fn mutate(&self, target: &mut Self::Genotype) {
+        let len = target.bits.len();
+        let segment_len = random_segment_len(
+            self.max_segment.min(len).min(self.limits.max.saturating_sub(len))
+        );
+        if segment_len == 0 {
+            return;
+        }
+
+        let pos = random::rng().gen_range(0..=len - segment_len);
+        let segment: BitVec = target.bits.iter().skip(pos).take(segment_len).collect();
+        insert_bits(target, pos + segment_len, &segment);
+    }
+}
+
+/// Cut-and-splice crossover, as used by messy genetic algorithms. It cuts both parents at an
+/// independently chosen random position. The child consists of the head of the first parent,
+/// followed by the tail of the second parent. Parents therefore do not need to have the same
+/// length, and the child can be shorter or longer than both parents.
+///
+/// Cut points are chosen such that the child respects the length limits.
+#[derive(Debug)]
+pub struct BinaryCutAndSpliceCrossover {
+    limits: LengthLimits,
+}
+
+impl BinaryCutAndSpliceCrossover {
+    pub fn new(limits: LengthLimits) -> Self {
+        BinaryCutAndSpliceCrossover {
+            limits
+        }
+    }
+}
+
+impl Recombination for BinaryCutAndSpliceCrossover {
+    type Genotype = BinaryChromosome;
+
+    fn recombine(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype
+    ) -> Result<Self::Genotype, EvolutionError> {
+        let mut child = BinaryChromosome::zeroes(0);
+        self.recombine_into(parent1, parent2, &mut child)?;
 
-        child
+        Ok(child)
     }
-}
\ No newline at end of file
+
+    fn recombine_into(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype, child: &mut Self::Genotype
+    ) -> Result<(), EvolutionError> {
+        let len2 = parent2.bits.len();
+        let (head, tail) = self.limits.random_splice(parent1.bits.len(), len2)?;
+
+        // Truncating keeps the storage of the child, so it can be re-used
+        child.bits.truncate(0);
+        child.bits.extend(parent1.bits.iter().take(head));
+        child.bits.extend(parent2.bits.iter().skip(len2 - tail));
+
+        Ok(())
+    }
+}
+
+/// Hill climbing that flips one bit at a time. It stops when no single bit flip improves the
+/// fitness, or when its evaluation budget is used up.
+#[derive(Debug)]
+pub struct BinaryBitFlipHillClimbing {
+    improvement: Improvement,
+    max_evaluations: usize,
+}
+
+impl BinaryBitFlipHillClimbing {
+    /// Creates a new Binary Bit Flip Hill Climbing local search. The maximum number of
+    /// evaluations per improved genotype includes the evaluation of the original genotype, so it
+    /// should be at least 1.
+    pub fn new(improvement: Improvement, max_evaluations: usize) -> Result<Self, EvolutionError> {
+        if max_evaluations == 0 {
+            return Err(EvolutionError::InvalidParameter("max_evaluations", "at least 1"));
+        }
+
+        Ok(BinaryBitFlipHillClimbing {
+            improvement,
+            max_evaluations
+        })
+    }
+}
+
+impl<P: Phenotype> LocalSearch<P, BinaryChromosome> for BinaryBitFlipHillClimbing
+where
+    BinaryChromosome: Genotype<P>,
+{
+    fn improve(&self, genotype: &mut BinaryChromosome) -> Improved<P::Fitness> {
+        let flip = |genotype: &mut BinaryChromosome, i: usize| {
+            genotype.bits.set(i, !genotype.bits[i]);
+        };
+
+        let mut climber = HillClimber::new(self.improvement, self.max_evaluations);
+        let fitness = climber.evaluate(genotype);
+        let num_moves = genotype.bits.len();
+
+        let fitness = climber.climb(
+            genotype, fitness, num_moves, flip, |genotype, i, _| flip(genotype, i)
+        );
+        climber.improved(fitness)
+    }
+}
+
+fn check_equal_length(
+    parent1: &BinaryChromosome, parent2: &BinaryChromosome
+) -> Result<(), EvolutionError> {
+    if parent1.bits.len() != parent2.bits.len() {
+        return Err(EvolutionError::LengthMismatch(parent1.bits.len(), parent2.bits.len()));
+    }
+
+    Ok(())
+}
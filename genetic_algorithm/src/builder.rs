@@ -0,0 +1,388 @@
+use super::{
+    EvolutionaryAlgorithm, EvolutionError, Genotype, GenotypeConfig, GenotypeFactory,
+    GenotypeManipulation, Mutation, Phenotype, Recombination, SelectionFactory
+};
+use super::diversity::{Diversity, GenotypeDistance};
+use super::fitness::{InvalidFitnessPolicy, MeanFitness};
+use super::hall_of_fame::HallOfFame;
+use super::memetic::{LearningMode, LocalSearch, MemeticConfig};
+use super::noise::{NoiseConfig, NoiseHandling};
+use super::selection::RankBasedSelection;
+use super::random;
+use rand::Rng;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A genotype configuration that is composed of a factory function, a mutation operator and a
+/// recombination operator. It avoids the need to implement [GenotypeConfig] by hand.
+pub struct OperatorConfig<P, G, F, M, R> {
+    factory: F,
+    mutation: M,
+    recombination: R,
+    phantom: PhantomData<fn() -> (P, G)>,
+}
+
+impl<P, G, F, M, R> OperatorConfig<P, G, F, M, R>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    F: Fn() -> G,
+    M: Mutation<Genotype = G>,
+    R: Recombination<Genotype = G>,
+{
+    pub fn new(factory: F, mutation: M, recombination: R) -> Self {
+        OperatorConfig {
+            factory,
+            mutation,
+            recombination,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<P, G, F, M, R> fmt::Debug for OperatorConfig<P, G, F, M, R>
+where
+    M: fmt::Debug,
+    R: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OperatorConfig")
+            .field("mutation", &self.mutation)
+            .field("recombination", &self.recombination)
+            .finish()
+    }
+}
+
+impl<P, G, F, M, R> GenotypeFactory<P, G> for OperatorConfig<P, G, F, M, R>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    F: Fn() -> G,
+{
+    fn create(&self) -> G {
+        (self.factory)()
+    }
+}
+
+impl<P, G, F, M, R> GenotypeManipulation<P, G> for OperatorConfig<P, G, F, M, R>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G>,
+    R: Recombination<Genotype = G>,
+{
+    fn mutate(&self, target: &mut G) {
+        self.mutation.mutate(target);
+    }
+
+    fn recombine(&self, parent1: &G, parent2: &G) -> Result<G, EvolutionError> {
+        self.recombination.recombine(parent1, parent2)
+    }
+
+    fn recombine_into(
+        &self, parent1: &G, parent2: &G, child: &mut G
+    ) -> Result<(), EvolutionError> {
+        self.recombination.recombine_into(parent1, parent2, child)
+    }
+}
+
+impl<P, G, F, M, R> GenotypeConfig<P, G> for OperatorConfig<P, G, F, M, R>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    F: Fn() -> G,
+    M: Mutation<Genotype = G> + fmt::Debug,
+    R: Recombination<Genotype = G> + fmt::Debug,
+{}
+
+/// Picks one of the given items at random, with a probability proportional to its weight.
+fn pick_weighted<T>(items: &[(f32, T)], total_weight: f32) -> Option<&T> {
+    let mut remaining = random::rng().gen::<f32>() * total_weight;
+
+    for (weight, item) in items.iter() {
+        if remaining < *weight {
+            return Some(item);
+        }
+        remaining -= weight;
+    }
+
+    // Guard against rounding errors
+    items.last().map(|(_, item)| item)
+}
+
+fn check_weight(weight: f32) -> Result<(), EvolutionError> {
+    if weight > 0.0 && weight.is_finite() {
+        Ok(())
+    } else {
+        Err(EvolutionError::InvalidParameter("weight", "positive"))
+    }
+}
+
+/// A mutation that applies one of several mutation operators. Each time, the operator is chosen
+/// at random with a probability proportional to its weight.
+pub struct WeightedMutation<G> {
+    mutations: Vec<(f32, Box<dyn Mutation<Genotype = G>>)>,
+    total_weight: f32,
+}
+
+impl<G> WeightedMutation<G> {
+    pub fn new() -> Self {
+        WeightedMutation {
+            mutations: Vec::new(),
+            total_weight: 0.0,
+        }
+    }
+
+    /// Adds a mutation operator. Its weight should be positive.
+    pub fn with(
+        mut self, weight: f32, mutation: impl Mutation<Genotype = G> + 'static
+    ) -> Result<Self, EvolutionError> {
+        check_weight(weight)?;
+
+        self.mutations.push((weight, Box::new(mutation)));
+        self.total_weight += weight;
+
+        Ok(self)
+    }
+}
+
+impl<G> Default for WeightedMutation<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> fmt::Debug for WeightedMutation<G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let weights: Vec<f32> = self.mutations.iter().map(|(weight, _)| *weight).collect();
+        f.debug_struct("WeightedMutation").field("weights", &weights).finish()
+    }
+}
+
+impl<G> Mutation for WeightedMutation<G> {
+    type Genotype = G;
+
+    /// Applies one of the mutation operators. Does nothing when no operator was added.
+    fn mutate(&self, target: &mut G) {
+        if let Some(mutation) = pick_weighted(&self.mutations, self.total_weight) {
+            mutation.mutate(target);
+        }
+    }
+}
+
+/// A recombination that applies one of several recombination operators. Each time, the operator
+/// is chosen at random with a probability proportional to its weight.
+pub struct WeightedRecombination<G> {
+    recombinations: Vec<(f32, Box<dyn Recombination<Genotype = G>>)>,
+    total_weight: f32,
+}
+
+impl<G> WeightedRecombination<G> {
+    pub fn new() -> Self {
+        WeightedRecombination {
+            recombinations: Vec::new(),
+            total_weight: 0.0,
+        }
+    }
+
+    /// Adds a recombination operator. Its weight should be positive.
+    pub fn with(
+        mut self, weight: f32, recombination: impl Recombination<Genotype = G> + 'static
+    ) -> Result<Self, EvolutionError> {
+        check_weight(weight)?;
+
+        self.recombinations.push((weight, Box::new(recombination)));
+        self.total_weight += weight;
+
+        Ok(self)
+    }
+}
+
+impl<G> Default for WeightedRecombination<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> fmt::Debug for WeightedRecombination<G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let weights: Vec<f32> = self.recombinations.iter().map(|(weight, _)| *weight).collect();
+        f.debug_struct("WeightedRecombination").field("weights", &weights).finish()
+    }
+}
+
+impl<G> Recombination for WeightedRecombination<G> {
+    type Genotype = G;
+
+    fn recombine(&self, parent1: &G, parent2: &G) -> Result<G, EvolutionError> {
+        match pick_weighted(&self.recombinations, self.total_weight) {
+            Some(recombination) => recombination.recombine(parent1, parent2),
+            None => Err(EvolutionError::InvalidParameter("recombinations", "not empty")),
+        }
+    }
+
+    fn recombine_into(
+        &self, parent1: &G, parent2: &G, child: &mut G
+    ) -> Result<(), EvolutionError> {
+        match pick_weighted(&self.recombinations, self.total_weight) {
+            Some(recombination) => recombination.recombine_into(parent1, parent2, child),
+            None => Err(EvolutionError::InvalidParameter("recombinations", "not empty")),
+        }
+    }
+}
+
+/// Configures and creates an [EvolutionaryAlgorithm].
+///
+/// Only the genotype configuration is required. Either set it using [config], or compose it
+/// from operators using [operators]. By default, the population size is 100, recombination and
+/// mutation probabilities are 0.8, selection is rank based with a group size of two, and there
+/// is no elitism.
+pub struct EvolutionaryAlgorithmBuilder<P: Phenotype, G: Genotype<P>> {
+    pop_size: usize,
+    recombination_prob: f32,
+    mutation_prob: f32,
+    elitism: usize,
+    selection: Option<Box<dyn SelectionFactory<P, G>>>,
+    config: Option<Box<dyn GenotypeConfig<P, G>>>,
+    hall_of_fame: Option<HallOfFame<P, G>>,
+    diversity: Option<fn(&[&G]) -> Diversity>,
+    memetic: Option<MemeticConfig<P, G>>,
+    invalid_fitness_policy: InvalidFitnessPolicy,
+    noise: Option<NoiseConfig<P::Fitness>>,
+}
+
+impl<P: Phenotype, G: Genotype<P>> EvolutionaryAlgorithmBuilder<P, G> {
+    pub fn new() -> Self {
+        EvolutionaryAlgorithmBuilder {
+            pop_size: 100,
+            recombination_prob: 0.8,
+            mutation_prob: 0.8,
+            elitism: 0,
+            selection: None,
+            config: None,
+            hall_of_fame: None,
+            diversity: None,
+            memetic: None,
+            invalid_fitness_policy: InvalidFitnessPolicy::Worst,
+            noise: None,
+        }
+    }
+
+    pub fn pop_size(mut self, pop_size: usize) -> Self {
+        self.pop_size = pop_size;
+        self
+    }
+
+    /// Sets the probability that a child is created by recombining two parents, instead of by
+    /// copying one parent.
+    pub fn recombination_prob(mut self, recombination_prob: f32) -> Self {
+        self.recombination_prob = recombination_prob;
+        self
+    }
+
+    /// Sets the probability that a child is mutated.
+    pub fn mutation_prob(mut self, mutation_prob: f32) -> Self {
+        self.mutation_prob = mutation_prob;
+        self
+    }
+
+    /// Sets the number of fittest individuals that survive unchanged into the next generation.
+    pub fn elitism(mut self, elitism: usize) -> Self {
+        self.elitism = elitism;
+        self
+    }
+
+    pub fn selection(mut self, selection: impl SelectionFactory<P, G> + 'static) -> Self {
+        self.selection = Some(Box::new(selection));
+        self
+    }
+
+    pub fn config(mut self, config: impl GenotypeConfig<P, G> + 'static) -> Self {
+        self.config = Some(Box::new(config));
+        self
+    }
+
+    /// Sets the genotype configuration to one composed of the given factory and operators. See
+    /// [OperatorConfig].
+    pub fn operators<F, M, R>(self, factory: F, mutation: M, recombination: R) -> Self
+    where
+        F: Fn() -> G + 'static,
+        M: Mutation<Genotype = G> + fmt::Debug + 'static,
+        R: Recombination<Genotype = G> + fmt::Debug + 'static,
+    {
+        self.config(OperatorConfig::new(factory, mutation, recombination))
+    }
+
+    /// Keeps the fittest individuals ever evaluated in the given hall of fame.
+    pub fn hall_of_fame(mut self, hall_of_fame: HallOfFame<P, G>) -> Self {
+        self.hall_of_fame = Some(hall_of_fame);
+        self
+    }
+
+    /// Includes the diversity of the population in the statistics.
+    pub fn diversity(mut self) -> Self where G: GenotypeDistance {
+        self.diversity = Some(G::diversity);
+        self
+    }
+
+    /// Improves children using a local search. See
+    /// [EvolutionaryAlgorithm::set_local_search].
+    pub fn local_search(
+        mut self, local_search: impl LocalSearch<P, G> + 'static, mode: LearningMode, prob: f32
+    ) -> Self {
+        self.memetic = Some(MemeticConfig { local_search: Box::new(local_search), mode, prob });
+        self
+    }
+
+    /// Sets what to do with evaluations that give an invalid fitness. See
+    /// [EvolutionaryAlgorithm::set_invalid_fitness_policy].
+    pub fn invalid_fitness_policy(mut self, policy: InvalidFitnessPolicy) -> Self {
+        self.invalid_fitness_policy = policy;
+        self
+    }
+
+    /// Handles noisy fitness by sampling it repeatedly. See
+    /// [EvolutionaryAlgorithm::set_noise_handling].
+    pub fn noise_handling(mut self, handling: NoiseHandling) -> Self where P::Fitness: MeanFitness {
+        self.noise = Some(NoiseConfig::new(handling));
+        self
+    }
+
+    pub fn build(self) -> Result<EvolutionaryAlgorithm<P, G>, EvolutionError> {
+        if !(0.0..=1.0).contains(&self.recombination_prob) {
+            return Err(EvolutionError::InvalidParameter("recombination_prob", "in range [0, 1]"));
+        }
+        if !(0.0..=1.0).contains(&self.mutation_prob) {
+            return Err(EvolutionError::InvalidParameter("mutation_prob", "in range [0, 1]"));
+        }
+        if self.elitism >= self.pop_size {
+            return Err(EvolutionError::InvalidParameter("elitism", "less than pop_size"));
+        }
+
+        let config = self.config.ok_or(EvolutionError::InvalidParameter("config", "set"))?;
+        let selection = match self.selection {
+            Some(selection) => selection,
+            None => Box::new(RankBasedSelection::new(2)?),
+        };
+
+        let mut ea = EvolutionaryAlgorithm::new(self.pop_size, config, selection)?;
+        ea.recombination_prob = self.recombination_prob;
+        ea.mutation_prob = self.mutation_prob;
+        ea.elitism = self.elitism;
+        ea.hall_of_fame = self.hall_of_fame;
+        ea.diversity = self.diversity;
+        ea.invalid_fitness_policy = self.invalid_fitness_policy;
+        ea.noise = self.noise;
+        if let Some(memetic) = self.memetic {
+            ea.set_local_search(memetic.local_search, memetic.mode, memetic.prob)?;
+        }
+
+        Ok(ea)
+    }
+}
+
+impl<P: Phenotype, G: Genotype<P>> Default for EvolutionaryAlgorithmBuilder<P, G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,350 @@
+//! Cartesian genetic programming (CGP), which evolves programs that are directed acyclic graphs,
+//! such as digital circuits.
+//!
+//! The genotype is a grid of nodes with a fixed size. Each node has a function gene and a
+//! connection gene for each of its arguments. A connection refers to a program input, or to a
+//! node in one of the preceding columns, up to the levels-back parameter. Output genes refer to
+//! the inputs or nodes that give the program outputs. Only the nodes that the outputs depend on
+//! are active; the others are ignored when the program is decoded and run.
+//!
+//! As the genotype has a fixed length, programs cannot bloat. The inactive nodes make many
+//! mutations neutral, which lets the search drift between genotypes with equal fitness. This is
+//! why CGP is usually run with the (1 + λ) strategy, see [one_plus_lambda].
+//!
+//! Which functions the function genes refer to is up to the phenotype. It gives the function for
+//! each node when it runs the program, see [CgpProgram::run].
+
+use super::{EvolutionError, Genotype, Mutation, Phenotype};
+use super::diversity::GenotypeDistance;
+use super::random;
+use super::trajectory::{HillClimbing, TrajectorySearch};
+use rand::Rng;
+
+/// The shape of the grid of nodes, and the number of inputs, outputs and functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CgpLayout {
+    num_inputs: usize,
+    num_outputs: usize,
+    rows: usize,
+    columns: usize,
+    levels_back: usize,
+    num_functions: usize,
+    arity: usize,
+}
+
+impl CgpLayout {
+    /// Creates a layout with a single row of nodes, which can connect to any preceding node.
+    /// Each node applies one of `num_functions` functions to `arity` arguments. Functions that
+    /// need fewer arguments can ignore the others.
+    pub fn new(
+        num_inputs: usize, num_outputs: usize, columns: usize, num_functions: usize, arity: usize
+    ) -> Result<Self, EvolutionError> {
+        if num_inputs == 0 {
+            return Err(EvolutionError::InvalidParameter("num_inputs", "at least 1"));
+        }
+        if num_outputs == 0 {
+            return Err(EvolutionError::InvalidParameter("num_outputs", "at least 1"));
+        }
+        if columns == 0 {
+            return Err(EvolutionError::InvalidParameter("columns", "at least 1"));
+        }
+        if num_functions == 0 {
+            return Err(EvolutionError::InvalidParameter("num_functions", "at least 1"));
+        }
+
+        Ok(CgpLayout {
+            num_inputs,
+            num_outputs,
+            rows: 1,
+            columns,
+            levels_back: columns,
+            num_functions,
+            arity,
+        })
+    }
+
+    /// Sets the number of rows of nodes. Defaults to 1.
+    pub fn with_rows(mut self, rows: usize) -> Result<Self, EvolutionError> {
+        if rows == 0 {
+            return Err(EvolutionError::InvalidParameter("rows", "at least 1"));
+        }
+        self.rows = rows;
+        Ok(self)
+    }
+
+    /// Sets how many columns back a node can connect to. Nodes in the first `levels_back`
+    /// columns can connect to the inputs as well. Defaults to the number of columns, which lets
+    /// nodes connect to any preceding node.
+    pub fn with_levels_back(mut self, levels_back: usize) -> Result<Self, EvolutionError> {
+        if levels_back == 0 {
+            return Err(EvolutionError::InvalidParameter("levels_back", "at least 1"));
+        }
+        self.levels_back = levels_back;
+        Ok(self)
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.num_outputs
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn levels_back(&self) -> usize {
+        self.levels_back
+    }
+
+    pub fn num_functions(&self) -> usize {
+        self.num_functions
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// The number of genes: a function gene and `arity` connection genes per node, followed by
+    /// an output gene per output.
+    pub fn num_genes(&self) -> usize {
+        self.num_nodes() * (self.arity + 1) + self.num_outputs
+    }
+
+    /// Returns a random value for the gene at the given index. Connections are addressed such
+    /// that the inputs come first, followed by the nodes column by column.
+    fn random_gene<R: Rng>(&self, index: usize, rng: &mut R) -> usize {
+        let node_genes = self.num_nodes() * (self.arity + 1);
+        if index >= node_genes {
+            return rng.gen_range(0..self.num_inputs + self.num_nodes());
+        }
+
+        let (node, offset) = (index / (self.arity + 1), index % (self.arity + 1));
+        if offset == 0 {
+            return rng.gen_range(0..self.num_functions);
+        }
+
+        let column = node / self.rows;
+        let min_column = column.saturating_sub(self.levels_back);
+        let num_inputs = self.num_connectable_inputs(column);
+        let source = rng.gen_range(0..num_inputs + (column - min_column) * self.rows);
+        if source < num_inputs {
+            source
+        } else {
+            self.num_inputs + min_column * self.rows + (source - num_inputs)
+        }
+    }
+
+    /// The number of inputs that nodes in the given column can connect to. Only nodes in the
+    /// first `levels_back` columns can connect to the inputs.
+    fn num_connectable_inputs(&self, column: usize) -> usize {
+        if column < self.levels_back { self.num_inputs } else { 0 }
+    }
+
+    /// The number of values that the gene at the given index can have.
+    fn num_alleles(&self, index: usize) -> usize {
+        let node_genes = self.num_nodes() * (self.arity + 1);
+        if index >= node_genes {
+            return self.num_inputs + self.num_nodes();
+        }
+
+        let (node, offset) = (index / (self.arity + 1), index % (self.arity + 1));
+        if offset == 0 {
+            self.num_functions
+        } else {
+            let column = node / self.rows;
+            self.num_connectable_inputs(column)
+                + (column - column.saturating_sub(self.levels_back)) * self.rows
+        }
+    }
+}
+
+/// A CGP genotype, which is a grid of nodes and the output genes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgpChromosome {
+    layout: CgpLayout,
+    genes: Vec<usize>,
+}
+
+impl CgpChromosome {
+    /// Creates a chromosome with random genes.
+    pub fn new(layout: CgpLayout) -> Self {
+        let mut rng = random::rng();
+
+        CgpChromosome {
+            layout,
+            genes: (0..layout.num_genes()).map(|i| layout.random_gene(i, &mut rng)).collect(),
+        }
+    }
+
+    pub fn layout(&self) -> &CgpLayout {
+        &self.layout
+    }
+
+    pub fn genes(&self) -> &[usize] {
+        &self.genes
+    }
+
+    fn node_genes(&self, node: usize) -> &[usize] {
+        let len = self.layout.arity + 1;
+        &self.genes[node * len..(node + 1) * len]
+    }
+
+    fn output_genes(&self) -> &[usize] {
+        &self.genes[self.layout.num_nodes() * (self.layout.arity + 1)..]
+    }
+
+    /// The nodes that the outputs depend on, in increasing order.
+    pub fn active_nodes(&self) -> Vec<usize> {
+        let num_inputs = self.layout.num_inputs;
+        let mut active = vec![false; self.layout.num_nodes()];
+        let mut stack: Vec<usize> = self.output_genes().to_vec();
+
+        while let Some(address) = stack.pop() {
+            if address < num_inputs || active[address - num_inputs] {
+                continue;
+            }
+            let node = address - num_inputs;
+            active[node] = true;
+            stack.extend_from_slice(&self.node_genes(node)[1..]);
+        }
+
+        (0..active.len()).filter(|&node| active[node]).collect()
+    }
+
+    /// Decodes the active nodes into a program.
+    pub fn decode(&self) -> CgpProgram {
+        let nodes = self.active_nodes().into_iter().map(|node| {
+            let genes = self.node_genes(node);
+            CgpNode {
+                address: self.layout.num_inputs + node,
+                function: genes[0],
+                arguments: genes[1..].to_vec(),
+            }
+        }).collect();
+
+        CgpProgram {
+            num_inputs: self.layout.num_inputs,
+            num_addresses: self.layout.num_inputs + self.layout.num_nodes(),
+            nodes,
+            outputs: self.output_genes().to_vec(),
+        }
+    }
+}
+
+impl GenotypeDistance for CgpChromosome {
+    /// Returns the number of genes that differ.
+    fn distance(&self, other: &Self) -> f32 {
+        self.genes.iter().zip(other.genes.iter()).filter(|(a, b)| a != b).count() as f32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CgpNode {
+    address: usize,
+    function: usize,
+    arguments: Vec<usize>,
+}
+
+/// The program that a CGP genotype decodes to. It only contains the active nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgpProgram {
+    num_inputs: usize,
+    num_addresses: usize,
+    // Ordered such that the arguments of a node are computed before it
+    nodes: Vec<CgpNode>,
+    outputs: Vec<usize>,
+}
+
+impl CgpProgram {
+    /// The number of active nodes.
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Runs the program on the given inputs, and returns its outputs. Each node calls `apply`
+    /// with its function gene and the values of its arguments.
+    ///
+    /// Panics when the number of inputs does not match the layout.
+    pub fn run<T: Copy + Default>(&self, inputs: &[T], apply: impl Fn(usize, &[T]) -> T) -> Vec<T> {
+        assert_eq!(inputs.len(), self.num_inputs, "Wrong number of inputs");
+
+        let mut values = vec![T::default(); self.num_addresses];
+        values[..self.num_inputs].copy_from_slice(inputs);
+        let mut arguments = Vec::new();
+
+        for node in self.nodes.iter() {
+            arguments.clear();
+            arguments.extend(node.arguments.iter().map(|&argument| values[argument]));
+            values[node.address] = apply(node.function, &arguments);
+        }
+
+        self.outputs.iter().map(|&output| values[output]).collect()
+    }
+}
+
+/// Point mutation, which replaces genes by other valid values.
+#[derive(Debug)]
+pub struct CgpPointMutation {
+    mutate_prob: f32,
+}
+
+impl CgpPointMutation {
+    /// Creates a new CGP Point Mutation operator. The mutation probability is the probability
+    /// that each gene is changed. It should be in range [0, 1].
+    pub fn new(mutate_prob: f32) -> Result<Self, EvolutionError> {
+        if !(0.0..=1.0).contains(&mutate_prob) {
+            return Err(EvolutionError::InvalidParameter("mutate_prob", "in range [0, 1]"));
+        }
+
+        Ok(CgpPointMutation {
+            mutate_prob
+        })
+    }
+}
+
+impl Mutation for CgpPointMutation {
+    type Genotype = CgpChromosome;
+
+    fn mutate(&self, target: &mut Self::Genotype) {
+        let mut rng = random::rng();
+        let layout = target.layout;
+
+        for (i, gene) in target.genes.iter_mut().enumerate() {
+            if rng.gen::<f32>() >= self.mutate_prob || layout.num_alleles(i) < 2 {
+                continue;
+            }
+            // A gene is always changed to a different value
+            let original = *gene;
+            while *gene == original {
+                *gene = layout.random_gene(i, &mut rng);
+            }
+        }
+    }
+}
+
+/// Creates the (1 + λ) strategy that is usually used with CGP. Each generation, it creates
+/// `lambda` mutants of the parent. The fittest one replaces the parent, unless it is less fit.
+/// Replacing the parent by an equally fit mutant is what lets the search drift.
+///
+/// It is [hill climbing](HillClimbing) with `lambda` neighbours, which can be run as any other
+/// [Optimiser](crate::optimiser::Optimiser).
+pub fn one_plus_lambda<P: Phenotype>(
+    layout: CgpLayout, mutation: CgpPointMutation, lambda: usize
+) -> Result<TrajectorySearch<P, CgpChromosome, CgpPointMutation, HillClimbing>, EvolutionError>
+where
+    CgpChromosome: Genotype<P>,
+{
+    let strategy = HillClimbing::new(lambda)?;
+    Ok(TrajectorySearch::new(move || CgpChromosome::new(layout), mutation, strategy))
+}
@@ -0,0 +1,103 @@
+//! Operators for composite genotypes, i.e. genotypes that consist of multiple heterogeneous
+//! parts. A composite genotype is a tuple, where each element is a part. For example, a pair of a
+//! chromosome that encodes an ordering and a `BinaryChromosome` that encodes a set of flags.
+//!
+//! The composite operators delegate to an operator for each part. Composites of two up to four
+//! parts are supported. For more parts, composites can be nested.
+
+use super::{EvolutionError, Mutation, Recombination};
+use super::random;
+use rand::Rng;
+
+/// Implemented by the tuples of operators that composite operators delegate to.
+pub trait Parts {
+    /// The number of parts.
+    const COUNT: usize;
+}
+
+/// Mutates a composite genotype by mutating its parts. Each part is mutated independently with
+/// its own probability, so a mutation may change multiple parts, or none.
+#[derive(Debug)]
+pub struct CompositeMutation<T> {
+    operators: T,
+    probs: Vec<f32>,
+}
+
+/// Recombines a composite genotype by recombining each of its parts.
+#[derive(Debug)]
+pub struct CompositeRecombination<T> {
+    operators: T,
+}
+
+impl<T: Parts> CompositeMutation<T> {
+    /// Creates a new Composite Mutation operator. It is given a mutation operator for each part
+    /// and, for each part, the probability that it is mutated. Probabilities should be in range
+    /// [0, 1].
+    pub fn new(operators: T, probs: &[f32]) -> Result<Self, EvolutionError> {
+        if probs.len() != T::COUNT {
+            return Err(EvolutionError::InvalidParameter("probs", "one value per part"));
+        }
+        if probs.iter().any(|prob| !(0.0..=1.0).contains(prob)) {
+            return Err(EvolutionError::InvalidParameter("probs", "in range [0, 1]"));
+        }
+
+        Ok(CompositeMutation {
+            operators,
+            probs: probs.to_vec(),
+        })
+    }
+}
+
+impl<T: Parts> CompositeRecombination<T> {
+    pub fn new(operators: T) -> Self {
+        CompositeRecombination {
+            operators
+        }
+    }
+}
+
+macro_rules! impl_composite {
+    ($n:expr; $($part:ident $idx:tt),+) => {
+        impl<$($part),+> Parts for ($($part,)+) {
+            const COUNT: usize = $n;
+        }
+
+        impl<$($part: Mutation),+> Mutation for CompositeMutation<($($part,)+)> {
+            type Genotype = ($($part::Genotype,)+);
+
+            fn mutate(&self, target: &mut Self::Genotype) {
+                $(
+                    if random::rng().gen::<f32>() < self.probs[$idx] {
+                        self.operators.$idx.mutate(&mut target.$idx);
+                    }
+                )+
+            }
+        }
+
+        impl<$($part: Recombination),+> Recombination for CompositeRecombination<($($part,)+)> {
+            type Genotype = ($($part::Genotype,)+);
+
+            fn recombine(
+                &self, parent1: &Self::Genotype, parent2: &Self::Genotype
+            ) -> Result<Self::Genotype, EvolutionError> {
+                Ok(($(self.operators.$idx.recombine(&parent1.$idx, &parent2.$idx)?,)+))
+            }
+
+            fn recombine_into(
+                &self, parent1: &Self::Genotype, parent2: &Self::Genotype,
+                child: &mut Self::Genotype
+            ) -> Result<(), EvolutionError> {
+                $(
+                    self.operators.$idx.recombine_into(
+                        &parent1.$idx, &parent2.$idx, &mut child.$idx
+                    )?;
+                )+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_composite!(2; A 0, B 1);
+impl_composite!(3; A 0, B 1, C 2);
+impl_composite!(4; A 0, B 1, C 2, D 3);
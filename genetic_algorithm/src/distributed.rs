@@ -0,0 +1,653 @@
+//! Evaluation of phenotypes by workers that connect over TCP, possibly from other machines.
+//!
+//! A [Coordinator] listens for workers and implements [Evaluator], so it can be used as the
+//! evaluation backend of the evolutionary algorithm. The phenotypes to evaluate are encoded with
+//! [LineEncoding], split into batches and handed out to the connected workers. A [Worker]
+//! connects to the coordinator and evaluates the batches it receives, until the coordinator
+//! closes the connection.
+//!
+//! Messages are sent as frames: a 4-byte length followed by that many bytes, of which the first
+//! one gives the type of the message. All integers are big-endian.
+//!
+//! * Batch (type 1), coordinator to worker: the task id (u64), the number of phenotypes (u32),
+//!   and for each phenotype its length (u32) followed by its encoding as UTF-8.
+//! * Results (type 2), worker to coordinator: the task id (u64), the number of results (u32),
+//!   and for each phenotype in the batch its fitness (f32), in the same order.
+//! * Heartbeat (type 3), both ways: no content.
+//!
+//! Both sides send a heartbeat at a regular interval, also while a worker is busy evaluating, and
+//! consider the connection lost when nothing has been received for a while. When a worker is
+//! lost, the batch it was evaluating is handed out again to another worker. When no worker makes
+//! progress in time, the remaining phenotypes are assigned the failure fitness.
+
+use super::{EvolutionError, Evaluator, Phenotype};
+use super::external::LineEncoding;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::fmt;
+
+const BATCH: u8 = 1;
+const RESULTS: u8 = 2;
+const HEARTBEAT: u8 = 3;
+
+/// Frames larger than this are rejected, so that a corrupt length does not exhaust memory.
+const MAX_FRAME_LEN: usize = 1 << 26;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Message {
+    Batch { task: u64, phenotypes: Vec<String> },
+    Results { task: u64, fitnesses: Vec<f32> },
+    Heartbeat,
+}
+
+fn protocol_violation(msg: &str) -> EvolutionError {
+    EvolutionError::ProtocolViolation(String::from(msg))
+}
+
+fn frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(5 + payload.len());
+    frame.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+    frame.push(kind);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn encode_batch(task: u64, phenotypes: &[String]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    payload.extend_from_slice(&task.to_be_bytes());
+    payload.extend_from_slice(&(phenotypes.len() as u32).to_be_bytes());
+    for phenotype in phenotypes {
+        payload.extend_from_slice(&(phenotype.len() as u32).to_be_bytes());
+        payload.extend_from_slice(phenotype.as_bytes());
+    }
+    frame(BATCH, &payload)
+}
+
+fn encode_results(task: u64, fitnesses: &[f32]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(12 + 4 * fitnesses.len());
+    payload.extend_from_slice(&task.to_be_bytes());
+    payload.extend_from_slice(&(fitnesses.len() as u32).to_be_bytes());
+    for fitness in fitnesses {
+        payload.extend_from_slice(&fitness.to_bits().to_be_bytes());
+    }
+    frame(RESULTS, &payload)
+}
+
+fn encode_heartbeat() -> Vec<u8> {
+    frame(HEARTBEAT, &[])
+}
+
+/// Reads the fields of a frame, failing when the frame ends too soon.
+struct FrameReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> FrameReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], EvolutionError> {
+        if len > self.bytes.len() {
+            return Err(protocol_violation("Frame ends unexpectedly"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, EvolutionError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, EvolutionError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<String, EvolutionError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| protocol_violation("Phenotype is not valid UTF-8"))
+    }
+}
+
+/// Reads the next message. Returns `None` when the connection was closed by the other side.
+fn read_message(input: &mut impl Read) -> Result<Option<Message>, EvolutionError> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => {},
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+    if len == 0 || len > MAX_FRAME_LEN {
+        return Err(EvolutionError::ProtocolViolation(format!("Invalid frame length {}", len)));
+    }
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+
+    let mut reader = FrameReader { bytes: &bytes[1..] };
+    let message = match bytes[0] {
+        BATCH => {
+            let task = reader.u64()?;
+            let len = reader.u32()?;
+            let phenotypes = (0..len).map(|_| reader.string()).collect::<Result<_, _>>()?;
+            Message::Batch { task, phenotypes }
+        },
+        RESULTS => {
+            let task = reader.u64()?;
+            let len = reader.u32()?;
+            let fitnesses = (0..len).map(|_| reader.u32().map(f32::from_bits))
+                .collect::<Result<_, _>>()?;
+            Message::Results { task, fitnesses }
+        },
+        HEARTBEAT => Message::Heartbeat,
+        kind => {
+            return Err(EvolutionError::ProtocolViolation(format!("Unknown message type {}", kind)));
+        },
+    };
+
+    if !reader.bytes.is_empty() {
+        return Err(protocol_violation("Frame is longer than its message"));
+    }
+    Ok(Some(message))
+}
+
+/// How often heartbeats are sent, and how long to wait for a message before the connection is
+/// considered lost. The coordinator and its workers should use the same settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl Heartbeat {
+    /// The timeout must be longer than the interval, and should allow for a few missed
+    /// heartbeats.
+    pub fn new(interval: Duration, timeout: Duration) -> Result<Self, EvolutionError> {
+        if interval.is_zero() {
+            return Err(EvolutionError::InvalidParameter("interval", "greater than 0"));
+        }
+        if timeout <= interval {
+            return Err(EvolutionError::InvalidParameter("timeout", "greater than interval"));
+        }
+
+        Ok(Heartbeat {
+            interval,
+            timeout,
+        })
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+/// Sends a heartbeat every second, and waits five seconds for a message.
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A batch of phenotypes of a job, which starts at index `start` of the job.
+struct Task {
+    id: u64,
+    job: u64,
+    start: usize,
+    phenotypes: Vec<String>,
+}
+
+struct Connection {
+    // Used to shut the connection down, which does not wait for a write in progress
+    stream: TcpStream,
+    // Frames are written outside the state lock, so that a slow worker does not block the others
+    writer: Arc<Mutex<TcpStream>>,
+    task: Option<Task>,
+}
+
+/// Writes a frame to a worker. When that fails, the connection is shut down. The worker is then
+/// removed, and its batch handed out again, once its reader notices the closed connection.
+fn send(writer: &Mutex<TcpStream>, frame: &[u8]) {
+    let mut stream = writer.lock().unwrap();
+    if stream.write_all(frame).is_err() {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+}
+
+struct State {
+    next_worker: u64,
+    next_task: u64,
+    // Ordered by id, so that batches are handed out to the longest connected workers first
+    workers: BTreeMap<u64, Connection>,
+    queue: VecDeque<Task>,
+    job: u64,
+    results: Vec<Option<f32>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    // Notified when a worker connects, delivers results or is lost
+    changed: Condvar,
+    closed: AtomicBool,
+    heartbeat: Heartbeat,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn accept(self: Arc<Self>, listener: TcpListener) {
+        while !self.closed.load(Ordering::Relaxed) {
+            let stream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(_) => {
+                    // The listener does not block, so that it notices when it is closed
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                },
+            };
+
+            let configured = stream.set_nonblocking(false)
+                .and_then(|_| stream.set_nodelay(true))
+                .and_then(|_| stream.set_read_timeout(Some(self.heartbeat.timeout)))
+                .and_then(|_| stream.set_write_timeout(Some(self.heartbeat.timeout)))
+                .and_then(|_| Ok((stream.try_clone()?, stream.try_clone()?)));
+            let (shutdown, writer) = match configured {
+                Ok(clones) => clones,
+                Err(_) => continue,
+            };
+
+            let mut state = self.lock();
+            let id = state.next_worker;
+            state.next_worker += 1;
+            state.workers.insert(id, Connection {
+                stream: shutdown,
+                writer: Arc::new(Mutex::new(writer)),
+                task: None,
+            });
+            drop(state);
+            self.changed.notify_all();
+
+            let shared = Arc::clone(&self);
+            thread::spawn(move || shared.serve(id, stream));
+        }
+    }
+
+    /// Receives the messages of a worker, until the connection is lost.
+    fn serve(&self, id: u64, mut stream: TcpStream) {
+        while let Ok(Some(message)) = read_message(&mut stream) {
+            match message {
+                Message::Heartbeat => {},
+                Message::Results { task, fitnesses } => if !self.complete(id, task, fitnesses) {
+                    break;
+                },
+                Message::Batch { .. } => break,
+            }
+        }
+
+        // Hand out the batch of the lost worker again
+        let mut state = self.lock();
+        if let Some(connection) = state.workers.remove(&id) {
+            let _ = connection.stream.shutdown(Shutdown::Both);
+            if let Some(task) = connection.task.filter(|task| task.job == state.job) {
+                state.queue.push_front(task);
+            }
+        }
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    /// Stores the results of a task. Returns false when they do not match the task that the
+    /// worker was given.
+    fn complete(&self, id: u64, task: u64, fitnesses: Vec<f32>) -> bool {
+        let mut state = self.lock();
+        let connection = match state.workers.get_mut(&id) {
+            Some(connection) => connection,
+            None => return false,
+        };
+        match &connection.task {
+            Some(expected) if expected.id == task && expected.phenotypes.len() == fitnesses.len()
+                => {},
+            _ => return false,
+        }
+
+        let task = connection.task.take().unwrap();
+        // Results of a job that was given up on are discarded
+        if task.job == state.job {
+            for (i, fitness) in fitnesses.into_iter().enumerate() {
+                state.results[task.start + i] = Some(fitness);
+            }
+        }
+        drop(state);
+        self.changed.notify_all();
+        true
+    }
+
+    fn send_heartbeats(&self) {
+        let heartbeat = encode_heartbeat();
+        let mut last_sent = Instant::now();
+        let mut state = self.lock();
+
+        while !self.closed.load(Ordering::Relaxed) {
+            let elapsed = last_sent.elapsed();
+            if elapsed < self.heartbeat.interval {
+                state = self.changed.wait_timeout(state, self.heartbeat.interval - elapsed)
+                    .unwrap().0;
+                continue;
+            }
+
+            let writers: Vec<Arc<Mutex<TcpStream>>> = state.workers.values().map(
+                |connection| Arc::clone(&connection.writer)
+            ).collect();
+            drop(state);
+            for writer in writers {
+                send(&writer, &heartbeat);
+            }
+            last_sent = Instant::now();
+            state = self.lock();
+        }
+    }
+}
+
+/// Assigns queued tasks to idle workers. Returns the frames to send to them, which should be
+/// written after the state is unlocked, see [send].
+fn dispatch(state: &mut State) -> Vec<(Arc<Mutex<TcpStream>>, Vec<u8>)> {
+    let State { workers, queue, .. } = state;
+    let mut frames = Vec::new();
+
+    for connection in workers.values_mut().filter(|connection| connection.task.is_none()) {
+        let task = match queue.pop_front() {
+            Some(task) => task,
+            None => break,
+        };
+
+        frames.push((Arc::clone(&connection.writer), encode_batch(task.id, &task.phenotypes)));
+        connection.task = Some(task);
+    }
+
+    frames
+}
+
+/// Accepts workers over TCP, and distributes the evaluation of phenotypes over them.
+pub struct Coordinator {
+    shared: Arc<Shared>,
+    local_addr: SocketAddr,
+    batch_size: usize,
+    timeout: Duration,
+    failure_fitness: f32,
+    // Only one batch of phenotypes is evaluated at a time
+    job: Mutex<()>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Coordinator {
+    /// Listens for workers on the given address. Use port 0 to let the system pick a free port,
+    /// which is then available from [local_addr](Coordinator::local_addr).
+    pub fn bind(addr: impl ToSocketAddrs, heartbeat: Heartbeat) -> Result<Self, EvolutionError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                next_worker: 0,
+                next_task: 0,
+                workers: BTreeMap::new(),
+                queue: VecDeque::new(),
+                job: 0,
+                results: Vec::new(),
+            }),
+            changed: Condvar::new(),
+            closed: AtomicBool::new(false),
+            heartbeat,
+        });
+
+        let accepting = Arc::clone(&shared);
+        let beating = Arc::clone(&shared);
+        let threads = vec![
+            thread::spawn(move || accepting.accept(listener)),
+            thread::spawn(move || beating.send_heartbeats()),
+        ];
+
+        Ok(Coordinator {
+            shared,
+            local_addr,
+            batch_size: 16,
+            timeout: Duration::from_secs(60),
+            failure_fitness: f32::NAN,
+            job: Mutex::new(()),
+            threads,
+        })
+    }
+
+    /// Sets the number of phenotypes that are sent to a worker at once. Defaults to 16.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Result<Self, EvolutionError> {
+        if batch_size == 0 {
+            return Err(EvolutionError::InvalidParameter("batch_size", "at least 1"));
+        }
+        self.batch_size = batch_size;
+        Ok(self)
+    }
+
+    /// Sets how long to wait for the next batch of results, for example while no workers are
+    /// connected. When it takes longer, the evaluation fails. Defaults to a minute.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the fitness that is assigned when an evaluation fails. Defaults to NaN, so that the
+    /// algorithm handles failed evaluations according to its
+    /// [InvalidFitnessPolicy](crate::fitness::InvalidFitnessPolicy).
+    pub fn with_failure_fitness(mut self, failure_fitness: f32) -> Self {
+        self.failure_fitness = failure_fitness;
+        self
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn failure_fitness(&self) -> f32 {
+        self.failure_fitness
+    }
+
+    /// The number of workers that are currently connected.
+    pub fn num_workers(&self) -> usize {
+        self.shared.lock().workers.len()
+    }
+
+    /// Evaluates all encoded phenotypes, distributing them over the workers. Returns the results
+    /// in the same order.
+    pub fn evaluate_batch(&self, encoded: &[String]) -> Result<Vec<f32>, EvolutionError> {
+        self.run_job(encoded).into_iter().collect::<Option<_>>()
+            .ok_or(EvolutionError::EvaluationTimeout)
+    }
+
+    /// Evaluates all encoded phenotypes. The results are `None` for phenotypes that were not
+    /// evaluated in time.
+    fn run_job(&self, encoded: &[String]) -> Vec<Option<f32>> {
+        let _job = self.job.lock().unwrap();
+        let mut state = self.shared.lock();
+
+        state.job += 1;
+        let job = state.job;
+        state.results = vec![None; encoded.len()];
+        for (i, phenotypes) in encoded.chunks(self.batch_size).enumerate() {
+            let id = state.next_task;
+            state.next_task += 1;
+            state.queue.push_back(Task {
+                id,
+                job,
+                start: i * self.batch_size,
+                phenotypes: phenotypes.to_vec(),
+            });
+        }
+
+        let mut num_evaluated = 0;
+        let mut deadline = Instant::now() + self.timeout;
+        loop {
+            let evaluated = state.results.iter().filter(|result| result.is_some()).count();
+            if evaluated == encoded.len() {
+                break;
+            }
+            if evaluated > num_evaluated {
+                num_evaluated = evaluated;
+                deadline = Instant::now() + self.timeout;
+            }
+
+            let frames = dispatch(&mut state);
+            if !frames.is_empty() {
+                drop(state);
+                for (writer, frame) in frames {
+                    send(&writer, &frame);
+                }
+                state = self.shared.lock();
+                continue;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.shared.changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+
+        // Batches that are still being evaluated are discarded when their results arrive
+        state.queue.clear();
+        std::mem::take(&mut state.results)
+    }
+}
+
+impl Drop for Coordinator {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Relaxed);
+        for connection in self.shared.lock().workers.values() {
+            // This tells the worker to stop, and ends the thread that receives its messages
+            let _ = connection.stream.shutdown(Shutdown::Both);
+        }
+        self.shared.changed.notify_all();
+
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for Coordinator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "Coordinator {{ local_addr: {}, num_workers: {} }}",
+            self.local_addr, self.num_workers()
+        )
+    }
+}
+
+impl<P: Phenotype<Fitness = f32> + LineEncoding> Evaluator<P> for Coordinator {
+    fn evaluate(&self, phenotypes: &[&P]) -> Vec<f32> {
+        let encoded: Vec<String> = phenotypes.iter().map(|phenotype| phenotype.encode()).collect();
+
+        self.run_job(&encoded).into_iter().map(
+            |result| result.unwrap_or(self.failure_fitness)
+        ).collect()
+    }
+}
+
+/// Stops the heartbeats of a worker and closes its connection, also when evaluation panics.
+struct HeartbeatGuard {
+    stream: TcpStream,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for HeartbeatGuard {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        let _ = self.stream.shutdown(Shutdown::Both);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Evaluates batches of phenotypes for a coordinator.
+#[derive(Debug)]
+pub struct Worker {
+    stream: TcpStream,
+    heartbeat: Heartbeat,
+}
+
+impl Worker {
+    pub fn connect(addr: impl ToSocketAddrs, heartbeat: Heartbeat) -> Result<Self, EvolutionError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(heartbeat.timeout))?;
+        stream.set_write_timeout(Some(heartbeat.timeout))?;
+
+        Ok(Worker {
+            stream,
+            heartbeat,
+        })
+    }
+
+    /// Evaluates the batches that the coordinator sends, until it closes the connection. The
+    /// function is given the encoding of a phenotype, and returns its fitness.
+    ///
+    /// Fails when the coordinator stops sending heartbeats, or violates the protocol.
+    pub fn run(mut self, evaluate: impl Fn(&str) -> f32) -> Result<(), EvolutionError> {
+        let writer = Arc::new(Mutex::new(self.stream.try_clone()?));
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let (writer, stopped) = (Arc::clone(&writer), Arc::clone(&stopped));
+            let interval = self.heartbeat.interval;
+            thread::spawn(move || {
+                let heartbeat = encode_heartbeat();
+                loop {
+                    thread::park_timeout(interval);
+                    if stopped.load(Ordering::Relaxed)
+                        || writer.lock().unwrap().write_all(&heartbeat).is_err() {
+                        return;
+                    }
+                }
+            })
+        };
+        let _guard = HeartbeatGuard {
+            stream: self.stream.try_clone()?,
+            stopped,
+            thread: Some(thread),
+        };
+
+        loop {
+            match read_message(&mut self.stream)? {
+                None => return Ok(()),
+                Some(Message::Heartbeat) => {},
+                Some(Message::Batch { task, phenotypes }) => {
+                    let fitnesses: Vec<f32> = phenotypes.iter()
+                        .map(|phenotype| evaluate(phenotype))
+                        .collect();
+                    writer.lock().unwrap().write_all(&encode_results(task, &fitnesses))?;
+                },
+                Some(Message::Results { .. }) => {
+                    return Err(protocol_violation("Worker received results"));
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Diversity and convergence metrics of a population.
+//!
+//! When the fitness of a run stalls, the diversity tells why. When the population is still
+//! diverse, the landscape is hard. When it has converged, the search has lost its ability to
+//! explore and more mutation or a larger population may help.
+
+/// The frequency that the most common allele at a locus should have for the locus to count as
+/// converged.
+pub const CONVERGENCE_THRESHOLD: f32 = 0.95;
+
+/// A distance between genotypes, which is used to measure the diversity of a population.
+pub trait GenotypeDistance {
+    /// Returns the distance to the other genotype. It should be zero for equal genotypes.
+    fn distance(&self, other: &Self) -> f32;
+
+    /// Measures the diversity of the given genotypes. By default, it computes the mean distance
+    /// between all pairs of genotypes. Implementations can override it to compute it more
+    /// efficiently, or to add per-locus statistics.
+    fn diversity(genotypes: &[&Self]) -> Diversity where Self: Sized {
+        Diversity {
+            mean_distance: mean_pairwise_distance(genotypes),
+            loci: None,
+        }
+    }
+}
+
+/// The diversity of a population.
+#[derive(Debug, Clone)]
+pub struct Diversity {
+    /// The mean distance between all pairs of genotypes. See [GenotypeDistance::distance].
+    pub mean_distance: f32,
+    /// Per-locus statistics. Only available for genotypes whose loci can be compared, i.e. when
+    /// all genotypes have the same length.
+    pub loci: Option<LocusStats>,
+}
+
+/// Statistics of the alleles at each locus of genotypes with two alleles per locus.
+#[derive(Debug, Clone)]
+pub struct LocusStats {
+    /// For each locus, the fraction of genotypes that have a one (or `true`) there.
+    pub allele_frequencies: Vec<f32>,
+    /// For each locus, the Shannon entropy of its alleles, in bits.
+    pub entropy: Vec<f32>,
+    /// The mean entropy over all loci.
+    pub mean_entropy: f32,
+    /// The number of loci where one allele has a frequency of at least [CONVERGENCE_THRESHOLD].
+    pub converged_loci: usize,
+}
+
+impl LocusStats {
+    /// Computes the statistics from the number of ones at each locus, out of `num` genotypes.
+    pub fn from_counts(ones: &[usize], num: usize) -> Self {
+        let allele_frequencies: Vec<f32> = ones.iter().map(
+            |count| if num == 0 { 0.0 } else { *count as f32 / num as f32 }
+        ).collect();
+        let entropy: Vec<f32> = allele_frequencies.iter().map(
+            |p| binary_entropy(*p)
+        ).collect();
+        let mean_entropy = if entropy.is_empty() {
+            0.0
+        } else {
+            entropy.iter().sum::<f32>() / entropy.len() as f32
+        };
+        let converged_loci = allele_frequencies.iter().filter(
+            |p| p.max(1.0 - **p) >= CONVERGENCE_THRESHOLD
+        ).count();
+
+        LocusStats {
+            allele_frequencies,
+            entropy,
+            mean_entropy,
+            converged_loci,
+        }
+    }
+}
+
+/// Returns the mean distance between all pairs of the given genotypes. It is zero when there are
+/// fewer than two genotypes.
+pub fn mean_pairwise_distance<G: GenotypeDistance>(genotypes: &[&G]) -> f32 {
+    let num = genotypes.len();
+    if num < 2 {
+        return 0.0;
+    }
+
+    let mut sum = 0.0;
+    for (i, genotype) in genotypes.iter().enumerate() {
+        for other in genotypes[i + 1..].iter() {
+            sum += genotype.distance(other) as f64;
+        }
+    }
+
+    (sum / (num * (num - 1) / 2) as f64) as f32
+}
+
+/// The entropy, in bits, of a locus where one of two alleles has frequency `p`.
+fn binary_entropy(p: f32) -> f32 {
+    [p, 1.0 - p].iter().filter(|p| **p > 0.0).map(|p| -p * p.log2()).sum()
+}
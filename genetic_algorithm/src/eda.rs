@@ -0,0 +1,316 @@
+//! Estimation-of-distribution algorithms for binary problems.
+//!
+//! Instead of a population, these algorithms keep a probabilistic model of good solutions. The
+//! model is a vector that holds, for each bit, the probability that it is set. Each generation,
+//! solutions are sampled from the model and evaluated, after which the model is moved towards
+//! the fittest solutions. How it is moved depends on the algorithm, see [ProbabilityUpdate].
+
+use super::{EvolutionError, Evaluator, Genotype, Phenotype, Stats};
+use super::binary::BinaryChromosome;
+use super::diversity::{Diversity, GenotypeDistance};
+use super::fitness::InvalidFitnessPolicy;
+use super::logging::LogSink;
+use super::optimiser::{Evaluation, Optimiser};
+use super::random;
+use bit_vec::BitVec;
+use rand::Rng;
+use std::{cmp, fmt};
+
+/// Updates the probability vector of an estimation-of-distribution algorithm.
+pub trait ProbabilityUpdate: fmt::Debug {
+    /// The number of solutions to sample each generation.
+    fn num_samples(&self) -> usize;
+
+    /// Moves the probabilities towards the fittest solutions. The solutions are ranked by
+    /// fitness, fittest first.
+    fn update(&self, probabilities: &mut [f32], ranked: &[BinaryChromosome]);
+}
+
+/// Population-Based Incremental Learning (PBIL). Each generation, the probabilities are moved
+/// towards the fittest solution by the learning rate. Optionally, the probabilities are also
+/// mutated, which keeps them from converging too early.
+#[derive(Debug)]
+pub struct Pbil {
+    num_samples: usize,
+    learning_rate: f32,
+    mutate_prob: f32,
+    mutation_shift: f32,
+}
+
+impl Pbil {
+    /// Creates a PBIL update that samples `num_samples` solutions per generation. Both should be
+    /// positive, and the learning rate should be at most 1.
+    pub fn new(num_samples: usize, learning_rate: f32) -> Result<Self, EvolutionError> {
+        if num_samples == 0 {
+            return Err(EvolutionError::InvalidParameter("num_samples", "at least 1"));
+        }
+        if !(learning_rate > 0.0 && learning_rate <= 1.0) {
+            return Err(EvolutionError::InvalidParameter("learning_rate", "in range <0, 1]"));
+        }
+
+        Ok(Pbil {
+            num_samples,
+            learning_rate,
+            mutate_prob: 0.0,
+            mutation_shift: 0.0,
+        })
+    }
+
+    /// Mutates each probability with probability `mutate_prob`, by moving it towards a random
+    /// bit by `mutation_shift`. Both should be in range [0, 1].
+    pub fn with_mutation(
+        mut self, mutate_prob: f32, mutation_shift: f32
+    ) -> Result<Self, EvolutionError> {
+        if !(0.0..=1.0).contains(&mutate_prob) {
+            return Err(EvolutionError::InvalidParameter("mutate_prob", "in range [0, 1]"));
+        }
+        if !(0.0..=1.0).contains(&mutation_shift) {
+            return Err(EvolutionError::InvalidParameter("mutation_shift", "in range [0, 1]"));
+        }
+
+        self.mutate_prob = mutate_prob;
+        self.mutation_shift = mutation_shift;
+        Ok(self)
+    }
+}
+
+impl ProbabilityUpdate for Pbil {
+    fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+
+    fn update(&self, probabilities: &mut [f32], ranked: &[BinaryChromosome]) {
+        let mut rng = random::rng();
+        let best = &ranked[0];
+
+        for (i, prob) in probabilities.iter_mut().enumerate() {
+            let bit = if best.bits[i] { 1.0 } else { 0.0 };
+            *prob = (1.0 - self.learning_rate) * *prob + self.learning_rate * bit;
+
+            if rng.gen::<f32>() < self.mutate_prob {
+                let bit = if rng.gen::<bool>() { 1.0 } else { 0.0 };
+                *prob = (1.0 - self.mutation_shift) * *prob + self.mutation_shift * bit;
+            }
+        }
+    }
+}
+
+/// The Univariate Marginal Distribution Algorithm (UMDA). Each generation, the probabilities are
+/// set to the frequency of ones among the fittest solutions.
+#[derive(Debug)]
+pub struct Umda {
+    num_samples: usize,
+    num_selected: usize,
+}
+
+impl Umda {
+    /// Creates a UMDA update that samples `num_samples` solutions per generation, of which the
+    /// `num_selected` fittest determine the probabilities. It should select at least one, and
+    /// at most all samples.
+    pub fn new(num_samples: usize, num_selected: usize) -> Result<Self, EvolutionError> {
+        if num_selected == 0 {
+            return Err(EvolutionError::InvalidParameter("num_selected", "at least 1"));
+        }
+        if num_selected > num_samples {
+            return Err(EvolutionError::InvalidParameter("num_selected", "at most num_samples"));
+        }
+
+        Ok(Umda {
+            num_samples,
+            num_selected,
+        })
+    }
+}
+
+impl ProbabilityUpdate for Umda {
+    fn num_samples(&self) -> usize {
+        self.num_samples
+    }
+
+    fn update(&self, probabilities: &mut [f32], ranked: &[BinaryChromosome]) {
+        let selected = &ranked[..self.num_selected];
+
+        for (i, prob) in probabilities.iter_mut().enumerate() {
+            let ones = selected.iter().filter(|solution| solution.bits[i]).count();
+            *prob = ones as f32 / selected.len() as f32;
+        }
+    }
+}
+
+/// The compact genetic algorithm (cGA). It simulates a genetic algorithm with a population of
+/// the given size, using only its probability vector. Each generation, two solutions compete.
+/// Where they differ, the probabilities are moved towards the winner by one over the population
+/// size.
+#[derive(Debug)]
+pub struct CompactGa {
+    pop_size: usize,
+}
+
+impl CompactGa {
+    /// Creates a cGA update that simulates a population of `pop_size` solutions. It should be at
+    /// least 1.
+    pub fn new(pop_size: usize) -> Result<Self, EvolutionError> {
+        if pop_size == 0 {
+            return Err(EvolutionError::InvalidParameter("pop_size", "at least 1"));
+        }
+
+        Ok(CompactGa {
+            pop_size
+        })
+    }
+}
+
+impl ProbabilityUpdate for CompactGa {
+    fn num_samples(&self) -> usize {
+        2
+    }
+
+    fn update(&self, probabilities: &mut [f32], ranked: &[BinaryChromosome]) {
+        let (winner, loser) = match ranked {
+            [winner, loser, ..] => (winner, loser),
+            _ => return,
+        };
+        let shift = 1.0 / self.pop_size as f32;
+
+        for (i, prob) in probabilities.iter_mut().enumerate() {
+            match (winner.bits[i], loser.bits[i]) {
+                (true, false) => *prob = (*prob + shift).min(1.0),
+                (false, true) => *prob = (*prob - shift).max(0.0),
+                _ => {},
+            }
+        }
+    }
+}
+
+/// An estimation-of-distribution algorithm, which optimises binary chromosomes of a fixed
+/// length. The algorithm is determined by its [ProbabilityUpdate].
+pub struct Eda<P: Phenotype, U: ProbabilityUpdate> {
+    update: U,
+    probabilities: Vec<f32>,
+    generation: usize,
+    best: Option<(BinaryChromosome, P::Fitness)>,
+    diversity: Option<fn(&[&BinaryChromosome]) -> Diversity>,
+    evaluation: Evaluation<P>,
+}
+
+impl<P: Phenotype, U: ProbabilityUpdate> Eda<P, U>
+where
+    BinaryChromosome: Genotype<P>,
+{
+    /// Creates an algorithm that optimises chromosomes of length `len`. Initially, each bit is
+    /// set with probability 0.5.
+    pub fn new(len: usize, update: U) -> Result<Self, EvolutionError> {
+        if update.num_samples() == 0 {
+            return Err(EvolutionError::InvalidParameter("num_samples", "at least 1"));
+        }
+
+        Ok(Eda {
+            update,
+            probabilities: vec![0.5; len],
+            generation: 0,
+            best: None,
+            diversity: None,
+            evaluation: Evaluation::new(),
+        })
+    }
+
+    /// Sets the evaluator that is used to determine the fitness of solutions. See [Evaluator].
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator<P>>) {
+        self.evaluation.set_evaluator(evaluator);
+    }
+
+    /// Adds a sink that the run log is written to. Besides the statistics, it receives the
+    /// probability vector that each generation was sampled from.
+    pub fn add_log_sink(&mut self, sink: Box<dyn LogSink>) {
+        self.evaluation.add_log_sink(sink);
+    }
+
+    /// Sets what to do with evaluations that give an invalid fitness, such as NaN. By default,
+    /// such solutions are considered the least fit. When the policy rejects them, the generation
+    /// fails and the model is not updated. See [InvalidFitnessPolicy].
+    pub fn set_invalid_fitness_policy(&mut self, policy: InvalidFitnessPolicy) {
+        self.evaluation.set_invalid_fitness_policy(policy);
+    }
+
+    /// Includes the diversity of the sampled solutions in the statistics.
+    pub fn enable_diversity(&mut self) {
+        self.diversity = Some(BinaryChromosome::diversity);
+    }
+
+    /// The probability that each bit is set. After a generation, it is the updated model that
+    /// the next generation will be sampled from.
+    pub fn probabilities(&self) -> &[f32] {
+        &self.probabilities
+    }
+
+    /// The number of generations run.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The fittest solution sampled so far, and its fitness.
+    pub fn best(&self) -> Option<(&BinaryChromosome, P::Fitness)> {
+        self.best.as_ref().map(|(solution, fitness)| (solution, *fitness))
+    }
+
+    fn sample(&self) -> BinaryChromosome {
+        let mut rng = random::rng();
+
+        BinaryChromosome {
+            bits: BitVec::from_fn(self.probabilities.len(), |i| {
+                rng.gen::<f32>() < self.probabilities[i]
+            })
+        }
+    }
+}
+
+impl<P: Phenotype, U: ProbabilityUpdate> fmt::Debug for Eda<P, U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Eda")
+            .field("update", &self.update)
+            .field("generation", &self.generation)
+            .field("probabilities", &self.probabilities)
+            .finish()
+    }
+}
+
+impl<P: Phenotype, U: ProbabilityUpdate> Optimiser for Eda<P, U>
+where
+    BinaryChromosome: Genotype<P>,
+{
+    /// Samples solutions from the model, evaluates them, logs them and updates the model.
+    fn step(&mut self) -> Result<Stats, EvolutionError> {
+        let solutions: Vec<BinaryChromosome> = (0..self.update.num_samples()).map(
+            |_| self.sample()
+        ).collect();
+        let phenotypes: Vec<P> = solutions.iter().map(|solution| solution.express()).collect();
+        let fitnesses = self.evaluation.evaluate(&phenotypes)?;
+
+        let mut ranked: Vec<(BinaryChromosome, P::Fitness)> = solutions.into_iter()
+            .zip(fitnesses).collect();
+        ranked.sort_by(|a, b| P::DIRECTION.compare_optional(&Some(b.1), &Some(a.1)));
+
+        let genotypes: Vec<&BinaryChromosome> = ranked.iter().map(|(solution, _)| solution)
+            .collect();
+        let fitnesses: Vec<P::Fitness> = ranked.iter().map(|(_, fitness)| *fitness).collect();
+        let stats = self.evaluation.stats(&fitnesses, &genotypes, self.diversity)?;
+        self.evaluation.log_generation(self.generation, &stats, Some(&self.probabilities))?;
+
+        if P::DIRECTION.compare_optional(
+            &Some(ranked[0].1), &self.best.as_ref().map(|(_, best)| *best)
+        ) == cmp::Ordering::Greater {
+            self.best = Some(ranked[0].clone());
+        }
+
+        let ranked: Vec<BinaryChromosome> = ranked.into_iter().map(|(solution, _)| solution)
+            .collect();
+        self.update.update(&mut self.probabilities, &ranked);
+        self.generation += 1;
+
+        Ok(stats)
+    }
+
+    fn evaluations(&self) -> usize {
+        self.evaluation.evaluations()
+    }
+}
@@ -0,0 +1,490 @@
+//! Experiments that compare optimiser configurations over many runs.
+//!
+//! The outcome of a single run depends heavily on chance, so comparing two configurations based
+//! on one run each says little. An [Experiment] runs each configuration once for every seed, in
+//! parallel, and summarises the results with confidence intervals. Each pair of configurations
+//! is compared with a rank-sum test.
+
+use super::EvolutionError;
+use super::fitness::Direction;
+use super::optimiser::{Optimiser, Progress, Termination, TerminationReason};
+use super::random;
+use std::{cmp, fmt, thread};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Two-sided 95% critical values of Student's t-distribution, for 1 up to 30 degrees of freedom.
+const T_CRITICAL: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228,
+    2.201, 2.179, 2.160, 2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086,
+    2.080, 2.074, 2.069, 2.064, 2.060, 2.056, 2.052, 2.048, 2.045, 2.042,
+];
+
+/// The two-sided 95% critical value of the standard normal distribution.
+const Z_CRITICAL: f64 = 1.96;
+
+type OptimiserFactory = Box<dyn Fn() -> Result<Box<dyn Optimiser>, EvolutionError> + Sync>;
+
+/// Runs a set of optimiser configurations once for each seed, and compares the results.
+pub struct Experiment {
+    configurations: Vec<(String, OptimiserFactory)>,
+    seeds: Vec<u64>,
+    termination: Termination,
+    num_threads: usize,
+}
+
+impl Experiment {
+    /// Creates an experiment that runs each configuration once for each of the given seeds, until
+    /// the termination criteria are met. When the criteria include a target fitness, runs that
+    /// reach it count as successful.
+    ///
+    /// By default, it uses as many threads as there are processors.
+    pub fn new(seeds: Vec<u64>, termination: Termination) -> Result<Self, EvolutionError> {
+        if seeds.is_empty() {
+            return Err(EvolutionError::InvalidParameter("seeds", "at least one seed"));
+        }
+        termination.check()?;
+
+        Ok(Experiment {
+            configurations: Vec::new(),
+            seeds,
+            termination,
+            num_threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        })
+    }
+
+    /// Adds a configuration. The factory creates the optimiser for a run. It is called on the
+    /// thread that does the run, after the thread's random number generator is seeded. See
+    /// [random::seed].
+    pub fn add_configuration<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn() -> Result<Box<dyn Optimiser>, EvolutionError> + Sync + 'static,
+    {
+        self.configurations.push((name.to_string(), Box::new(factory)));
+    }
+
+    /// Sets the number of runs that are done in parallel. It should be at least 1.
+    pub fn set_num_threads(&mut self, num_threads: usize) -> Result<(), EvolutionError> {
+        if num_threads == 0 {
+            return Err(EvolutionError::InvalidParameter("num_threads", "at least 1"));
+        }
+
+        self.num_threads = num_threads;
+        Ok(())
+    }
+
+    /// Does all runs and summarises their results. Fails with the error of the first run that
+    /// failed, if any.
+    pub fn run(&self) -> Result<ExperimentSummary, EvolutionError> {
+        let num_runs = self.configurations.len() * self.seeds.len();
+        let next_run = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+
+        let mut results: Vec<Option<Result<RunResult, EvolutionError>>> = (0..num_runs).map(
+            |_| None
+        ).collect();
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.num_threads.min(num_runs)).map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+
+                loop {
+                    let run = next_run.fetch_add(1, Ordering::Relaxed);
+                    if run >= num_runs || failed.load(Ordering::Relaxed) {
+                        return done;
+                    }
+
+                    let (factory, seed) = (
+                        &self.configurations[run / self.seeds.len()].1,
+                        self.seeds[run % self.seeds.len()],
+                    );
+                    let result = self.run_once(factory, seed);
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    done.push((run, result));
+                }
+            })).collect();
+
+            for worker in workers {
+                match worker.join() {
+                    Ok(done) => for (run, result) in done {
+                        results[run] = Some(result);
+                    },
+                    Err(_) => {
+                        failed.store(true, Ordering::Relaxed);
+                        return Err(EvolutionError::WorkerCrashed(
+                            "experiment thread panicked".to_string()
+                        ));
+                    },
+                }
+            }
+
+            Ok(())
+        })?;
+
+        // Report the error of the first failed run, so that the outcome does not depend on the
+        // order in which the threads finished
+        let mut runs = Vec::with_capacity(num_runs);
+        for result in results.into_iter().flatten() {
+            runs.push(result?);
+        }
+
+        let target = self.termination.target();
+        let mut runs = runs.into_iter();
+        let configurations: Vec<ConfigurationSummary> = self.configurations.iter().map(
+            |(name, _)| ConfigurationSummary::new(
+                name, runs.by_ref().take(self.seeds.len()).collect(), target.is_some()
+            )
+        ).collect();
+
+        let mut comparisons = Vec::new();
+        for first in 0..configurations.len() {
+            for second in first + 1..configurations.len() {
+                comparisons.push(Comparison::new(&configurations, first, second));
+            }
+        }
+
+        Ok(ExperimentSummary {
+            configurations,
+            comparisons,
+        })
+    }
+
+    fn run_once(
+        &self, factory: &OptimiserFactory, seed: u64
+    ) -> Result<RunResult, EvolutionError> {
+        random::seed(seed);
+        let mut optimiser = factory()?;
+        let mut progress = Progress::default();
+        let mut curve = Vec::new();
+
+        loop {
+            let stats = optimiser.step()?;
+            let reason = progress.update(&self.termination, &stats, optimiser.evaluations());
+            if let Some(best_fitness) = progress.best_fitness() {
+                curve.push(best_fitness);
+            }
+
+            if let Some(reason) = reason {
+                return Ok(RunResult {
+                    seed,
+                    best_fitness: curve.last().copied().unwrap_or(stats.best_fitness),
+                    curve,
+                    direction: stats.direction,
+                    evaluations: optimiser.evaluations(),
+                    reason,
+                });
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Experiment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.configurations.iter().map(|(name, _)| name.as_str()).collect();
+
+        f.debug_struct("Experiment")
+            .field("configurations", &names)
+            .field("seeds", &self.seeds)
+            .field("termination", &self.termination)
+            .field("num_threads", &self.num_threads)
+            .finish()
+    }
+}
+
+/// The result of a single run.
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    pub seed: u64,
+    /// The best fitness found so far, after each generation.
+    pub curve: Vec<f64>,
+    pub best_fitness: f64,
+    /// Whether the fitness was maximised or minimised.
+    pub direction: Direction,
+    pub evaluations: usize,
+    pub reason: TerminationReason,
+}
+
+impl RunResult {
+    /// Whether the run reached the target fitness.
+    pub fn reached_target(&self) -> bool {
+        self.reason == TerminationReason::TargetReached
+    }
+
+    /// Orders runs from worst to best. A run that reached the target is better than one that did
+    /// not. Of two runs that did, the one that needed fewer evaluations is better. Otherwise, the
+    /// one with the better best fitness is. A run without a valid best fitness, which is NaN, is
+    /// worse than any run with one, so that this is a total order.
+    fn compare(&self, other: &RunResult) -> cmp::Ordering {
+        match (self.reached_target(), other.reached_target()) {
+            (true, true) => other.evaluations.cmp(&self.evaluations),
+            (true, false) => cmp::Ordering::Greater,
+            (false, true) => cmp::Ordering::Less,
+            (false, false) => self.direction.compare_optional(
+                &Some(self.best_fitness), &Some(other.best_fitness)
+            ),
+        }
+    }
+}
+
+/// A mean or proportion, with its 95% confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub mean: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Estimate {
+    /// Estimates the mean of the given values, using Student's t-distribution. For a single
+    /// value, the interval is just that value. Returns None if there are no values.
+    fn mean_of(values: &[f64]) -> Option<Self> {
+        let n = values.len();
+        if n == 0 {
+            return None;
+        }
+
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let half_width = if n > 1 {
+            let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>()
+                / (n - 1) as f64;
+            t_critical(n - 1) * (variance / n as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        Some(Estimate {
+            mean,
+            lower: mean - half_width,
+            upper: mean + half_width,
+        })
+    }
+
+    /// Estimates a proportion from the number of successes, using the Wilson score interval.
+    fn proportion(successes: usize, n: usize) -> Self {
+        let p = successes as f64 / n as f64;
+        let z2 = Z_CRITICAL * Z_CRITICAL;
+        let n = n as f64;
+
+        let denominator = 1.0 + z2 / n;
+        let centre = (p + z2 / (2.0 * n)) / denominator;
+        let half_width = Z_CRITICAL / denominator
+            * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+
+        Estimate {
+            mean: p,
+            lower: (centre - half_width).max(0.0),
+            upper: (centre + half_width).min(1.0),
+        }
+    }
+}
+
+impl fmt::Display for Estimate {
+    /// Writes the mean followed by the interval, using the precision of the formatter, which
+    /// defaults to 3 decimals.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let precision = f.precision().unwrap_or(3);
+
+        write!(
+            f, "{:.*} [{:.*}, {:.*}]",
+            precision, self.mean, precision, self.lower, precision, self.upper
+        )
+    }
+}
+
+/// The results of all runs of one configuration.
+#[derive(Debug, Clone)]
+pub struct ConfigurationSummary {
+    pub name: String,
+    /// The runs, in the order of the seeds.
+    pub runs: Vec<RunResult>,
+    /// The best fitness found so far, for each generation. Runs that stopped earlier keep their
+    /// final best fitness.
+    pub mean_curve: Vec<Estimate>,
+    pub best_fitness: Estimate,
+    /// The fraction of runs that reached the target fitness. None if there is no target.
+    pub success_rate: Option<Estimate>,
+    /// The number of evaluations of the runs that reached the target. None if none did.
+    pub evaluations_to_target: Option<Estimate>,
+}
+
+impl ConfigurationSummary {
+    fn new(name: &str, runs: Vec<RunResult>, has_target: bool) -> Self {
+        let num_generations = runs.iter().map(|run| run.curve.len()).max().unwrap_or(0);
+        let mean_curve = (0..num_generations).filter_map(|generation| {
+            let values: Vec<f64> = runs.iter().filter_map(
+                |run| run.curve.get(generation).or(run.curve.last()).copied()
+            ).collect();
+            Estimate::mean_of(&values)
+        }).collect();
+
+        let best_fitnesses: Vec<f64> = runs.iter().map(|run| run.best_fitness).collect();
+        let successful: Vec<f64> = runs.iter().filter(|run| run.reached_target()).map(
+            |run| run.evaluations as f64
+        ).collect();
+
+        ConfigurationSummary {
+            name: name.to_string(),
+            mean_curve,
+            best_fitness: Estimate::mean_of(&best_fitnesses).unwrap_or(Estimate {
+                mean: f64::NAN,
+                lower: f64::NAN,
+                upper: f64::NAN,
+            }),
+            success_rate: if has_target {
+                Some(Estimate::proportion(successful.len(), runs.len()))
+            } else {
+                None
+            },
+            evaluations_to_target: Estimate::mean_of(&successful),
+            runs,
+        }
+    }
+}
+
+/// A Mann-Whitney rank-sum test between the runs of two configurations. Runs are ranked as
+/// follows. A run that reached the target is better than one that did not. Of two runs that
+/// did, the one that needed fewer evaluations is better. Otherwise, the one with the better best
+/// fitness is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    /// The index of the first configuration.
+    pub first: usize,
+    /// The index of the second configuration.
+    pub second: usize,
+    /// The rank-sum statistic of the first configuration: the number of pairs of runs in which
+    /// its run is better, where ties count for one half.
+    pub u: f64,
+    /// The probability that a run of the first configuration is better than one of the second.
+    /// Ties count for one half.
+    pub effect_size: f64,
+    /// The two-sided p-value of the hypothesis that neither configuration tends to do better. It
+    /// uses the normal approximation, so it is only accurate for about ten or more runs each.
+    pub p_value: f64,
+}
+
+impl Comparison {
+    fn new(configurations: &[ConfigurationSummary], first: usize, second: usize) -> Self {
+        let mut runs: Vec<(&RunResult, bool)> = configurations[first].runs.iter().map(
+            |run| (run, true)
+        ).chain(configurations[second].runs.iter().map(|run| (run, false))).collect();
+        runs.sort_by(|a, b| a.0.compare(b.0));
+
+        // Tied runs all get the average of their ranks
+        let mut rank_sum = 0.0;
+        let mut tie_correction = 0.0;
+        let mut start = 0;
+        while start < runs.len() {
+            let mut end = start + 1;
+            while end < runs.len() && runs[start].0.compare(runs[end].0) == cmp::Ordering::Equal {
+                end += 1;
+            }
+
+            let rank = (start + end + 1) as f64 / 2.0;
+            let num_first = runs[start..end].iter().filter(|(_, is_first)| *is_first).count();
+            rank_sum += rank * num_first as f64;
+            let num_tied = (end - start) as f64;
+            tie_correction += num_tied.powi(3) - num_tied;
+
+            start = end;
+        }
+
+        let n1 = configurations[first].runs.len() as f64;
+        let n2 = configurations[second].runs.len() as f64;
+        let n = n1 + n2;
+        let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+
+        let mean = n1 * n2 / 2.0;
+        let variance = n1 * n2 / 12.0 * ((n + 1.0) - tie_correction / (n * (n - 1.0)));
+        let p_value = if variance > 0.0 {
+            // Includes a continuity correction
+            let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+            erfc(z / std::f64::consts::SQRT_2)
+        } else {
+            1.0
+        };
+
+        Comparison {
+            first,
+            second,
+            u,
+            effect_size: u / (n1 * n2),
+            p_value,
+        }
+    }
+}
+
+/// The summary of an experiment. Its [Display](fmt::Display) implementation writes it as a
+/// table.
+#[derive(Debug, Clone)]
+pub struct ExperimentSummary {
+    /// The configurations, in the order in which they were added.
+    pub configurations: Vec<ConfigurationSummary>,
+    /// The comparisons of each pair of configurations.
+    pub comparisons: Vec<Comparison>,
+}
+
+impl fmt::Display for ExperimentSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.configurations.iter().map(|config| config.name.len()).max()
+            .unwrap_or(0).max("configuration".len());
+        let optional = |estimate: Option<Estimate>, precision: usize| match estimate {
+            Some(estimate) => format!("{:.*}", precision, estimate),
+            None => "-".to_string(),
+        };
+
+        writeln!(
+            f, "{:<width$}  runs  {:<26}  {:<23}  evaluations to target",
+            "configuration", "best fitness", "success rate",
+            width = width
+        )?;
+        for config in self.configurations.iter() {
+            writeln!(
+                f, "{:<width$}  {:>4}  {:<26}  {:<23}  {}",
+                config.name, config.runs.len(), format!("{}", config.best_fitness),
+                optional(config.success_rate, 2), optional(config.evaluations_to_target, 0),
+                width = width
+            )?;
+        }
+
+        if !self.comparisons.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "rank-sum tests (A = probability that the first does better):")?;
+        }
+        for comparison in self.comparisons.iter() {
+            writeln!(
+                f, "{} vs {}: A = {:.2}, U = {:.1}, p = {:.4}",
+                self.configurations[comparison.first].name,
+                self.configurations[comparison.second].name,
+                comparison.effect_size, comparison.u, comparison.p_value
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn t_critical(degrees_of_freedom: usize) -> f64 {
+    // Beyond the table, use the value of the nearest tabulated smaller number of degrees of
+    // freedom, which is slightly conservative
+    match degrees_of_freedom {
+        0 => f64::NAN,
+        1..=30 => T_CRITICAL[degrees_of_freedom - 1],
+        31..=39 => T_CRITICAL[29],
+        40..=59 => 2.021,
+        60..=119 => 2.000,
+        _ => 1.980,
+    }
+}
+
+/// The complementary error function, with a relative error below 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -1.265_512_23 + t * (1.000_023_68 + t * (0.374_091_96 + t * (0.096_784_18
+        + t * (-0.186_288_06 + t * (0.278_868_07 + t * (-1.135_203_98 + t * (1.488_515_87
+        + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let result = t * (-z * z + poly).exp();
+
+    if x >= 0.0 { result } else { 2.0 - result }
+}
@@ -0,0 +1,293 @@
+//! Evaluation of phenotypes by external worker processes.
+//!
+//! This makes it possible to use fitness functions that are written in another language. The
+//! worker is a local program that reads phenotypes from its standard input and writes their
+//! fitness to its standard output, using the following line-based protocol:
+//!
+//! * For each evaluation, one line is written to the worker. It contains the phenotype as encoded
+//!   by [LineEncoding::encode], followed by a newline. The encoding itself must not contain any
+//!   newlines.
+//! * The worker replies with exactly one line, containing the fitness as a decimal number (e.g.
+//!   `0.75`, `-3` or `1e-4`). Surrounding whitespace is ignored.
+//! * Requests are sent one at a time; the next one is only sent after the reply to the previous
+//!   one has been received. Workers should therefore flush their output after each reply.
+//! * The worker should exit when its standard input is closed.
+//!
+//! Anything the worker writes to its standard error is passed through unchanged, so it can be
+//! used for diagnostics.
+//!
+//! By default a worker is kept alive and evaluates many phenotypes. Alternatively, a new process
+//! can be spawned for each evaluation. In that case the worker only needs to handle one line.
+//!
+//! When a worker does not reply in time it is killed. When it exits unexpectedly, or is killed,
+//! it is restarted for the next evaluation. The failed evaluation is reported as an error, or
+//! as the failure fitness when evaluated via the [Phenotype] or [Evaluator] interfaces. By
+//! default the failure fitness is NaN, so that the algorithm handles it according to its
+//! [InvalidFitnessPolicy](crate::fitness::InvalidFitnessPolicy).
+//!
+//! A minimal worker, in Python, that counts the number of ones in a bit string:
+//!
+//! ```text
+//! import sys
+//! for line in sys.stdin:
+//!     print(line.count("1"), flush=True)
+//! ```
+
+use super::{EvolutionError, Evaluator, Phenotype};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{fmt, thread};
+
+/// Encodes a phenotype as a single line of text, so that it can be sent to a worker process.
+pub trait LineEncoding {
+    fn encode(&self) -> String;
+}
+
+struct Worker {
+    process: Child,
+    stdin: ChildStdin,
+    replies: Receiver<String>,
+}
+
+impl Worker {
+    fn spawn(command: &str, args: &[String]) -> Result<Self, EvolutionError> {
+        let mut process = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| EvolutionError::WorkerCrashed(format!("{}: {}", command, err)))?;
+
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+        let (sender, replies) = mpsc::channel();
+
+        // Reading is done in a separate thread, so that the pool can stop waiting for a reply
+        // after a timeout. The thread ends when the worker closes its output.
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if sender.send(line).is_err() {
+                        return;
+                    },
+                    Err(_) => return,
+                }
+            }
+        });
+
+        Ok(Worker {
+            process,
+            stdin,
+            replies,
+        })
+    }
+
+    fn evaluate(&mut self, encoded: &str, timeout: Duration) -> Result<f32, EvolutionError> {
+        writeln!(self.stdin, "{}", encoded)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|err| EvolutionError::WorkerCrashed(err.to_string()))?;
+
+        match self.replies.recv_timeout(timeout) {
+            Ok(reply) => reply.trim().parse::<f32>().map_err(
+                |_| EvolutionError::ProtocolViolation(format!("Invalid fitness: {:?}", reply))
+            ),
+            Err(RecvTimeoutError::Timeout) => Err(EvolutionError::EvaluationTimeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                let status = self.process.wait().map(|status| status.to_string());
+                Err(EvolutionError::WorkerCrashed(
+                    status.unwrap_or_else(|err| err.to_string())
+                ))
+            },
+        }
+    }
+
+    fn stop(mut self) {
+        // Closing its input tells the worker to exit. Kill it anyway in case it does not listen.
+        drop(self.stdin);
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// A pool of worker processes that evaluate phenotypes. Multiple phenotypes are evaluated
+/// concurrently, one per worker.
+pub struct WorkerPool {
+    command: String,
+    args: Vec<String>,
+    timeout: Duration,
+    failure_fitness: f32,
+    spawn_per_evaluation: bool,
+    workers: Vec<Mutex<Option<Worker>>>,
+}
+
+impl WorkerPool {
+    /// Creates a pool of `size` workers that each run the given command. Workers are started
+    /// when they are first needed.
+    pub fn new(command: &str, args: &[&str], size: usize) -> Result<Self, EvolutionError> {
+        if size == 0 {
+            return Err(EvolutionError::InvalidParameter("size", "at least 1"));
+        }
+
+        Ok(WorkerPool {
+            command: String::from(command),
+            args: args.iter().map(|arg| String::from(*arg)).collect(),
+            timeout: Duration::from_secs(10),
+            failure_fitness: f32::NAN,
+            spawn_per_evaluation: false,
+            workers: (0..size).map(|_| Mutex::new(None)).collect(),
+        })
+    }
+
+    /// Sets how long to wait for a reply. When it takes longer, the worker is killed.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the fitness that is assigned when an evaluation fails. Defaults to NaN, which is an
+    /// invalid fitness regardless of whether it is maximised or minimised.
+    pub fn with_failure_fitness(mut self, failure_fitness: f32) -> Self {
+        self.failure_fitness = failure_fitness;
+        self
+    }
+
+    /// Spawns a new process for each evaluation, instead of keeping workers alive.
+    pub fn spawn_per_evaluation(mut self) -> Self {
+        self.spawn_per_evaluation = true;
+        self
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn failure_fitness(&self) -> f32 {
+        self.failure_fitness
+    }
+
+    /// Evaluates a single encoded phenotype, using the first available worker.
+    pub fn evaluate(&self, encoded: &str) -> Result<f32, EvolutionError> {
+        let slot = self.workers.iter().find_map(|slot| slot.try_lock().ok());
+
+        match slot {
+            Some(mut slot) => self.evaluate_with(&mut slot, encoded),
+            None => self.evaluate_with(&mut self.workers[0].lock().unwrap(), encoded),
+        }
+    }
+
+    /// Evaluates all encoded phenotypes, distributing them over the workers. Returns the results
+    /// in the same order.
+    pub fn evaluate_batch(&self, encoded: &[String]) -> Vec<Result<f32, EvolutionError>> {
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<Result<f32, EvolutionError>>>> = Mutex::new(
+            (0..encoded.len()).map(|_| None).collect()
+        );
+
+        thread::scope(|scope| {
+            for slot in self.workers.iter().take(encoded.len()) {
+                let (next, results) = (&next, &results);
+                scope.spawn(move || {
+                    let mut slot = slot.lock().unwrap();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= encoded.len() {
+                            return;
+                        }
+
+                        let result = self.evaluate_with(&mut slot, &encoded[i]);
+                        results.lock().unwrap()[i] = Some(result);
+                    }
+                });
+            }
+        });
+
+        results.into_inner().unwrap().into_iter().map(|result| result.unwrap()).collect()
+    }
+
+    fn evaluate_with(
+        &self, slot: &mut Option<Worker>, encoded: &str
+    ) -> Result<f32, EvolutionError> {
+        if encoded.contains('\n') {
+            return Err(EvolutionError::ProtocolViolation(
+                String::from("Encoded phenotype contains a newline")
+            ));
+        }
+
+        let mut worker = match slot.take() {
+            Some(worker) => worker,
+            None => Worker::spawn(&self.command, &self.args)?,
+        };
+
+        let result = worker.evaluate(encoded, self.timeout);
+
+        if result.is_ok() && !self.spawn_per_evaluation {
+            *slot = Some(worker);
+        } else {
+            // The worker is not in a known state after a failure, so replace it
+            worker.stop();
+        }
+
+        result
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        for slot in self.workers.iter_mut() {
+            if let Some(worker) = slot.get_mut().ok().and_then(|slot| slot.take()) {
+                worker.stop();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WorkerPool {{ command: {:?}, size: {} }}", self.command, self.workers.len())
+    }
+}
+
+impl<P: Phenotype<Fitness = f32> + LineEncoding> Evaluator<P> for WorkerPool {
+    fn evaluate(&self, phenotypes: &[&P]) -> Vec<f32> {
+        let encoded: Vec<String> = phenotypes.iter().map(|phenotype| phenotype.encode()).collect();
+
+        self.evaluate_batch(&encoded).into_iter().map(
+            |result| result.unwrap_or(self.failure_fitness)
+        ).collect()
+    }
+}
+
+/// A phenotype that is evaluated by a worker process. It wraps the actual solution, which only
+/// needs to know how to encode itself. The fitness that the worker replies with is maximised.
+#[derive(Debug)]
+pub struct ProcessPhenotype<T: LineEncoding + fmt::Debug + 'static> {
+    pub value: T,
+    pool: Arc<WorkerPool>,
+}
+
+impl<T: LineEncoding + fmt::Debug + 'static> ProcessPhenotype<T> {
+    pub fn new(value: T, pool: Arc<WorkerPool>) -> Self {
+        ProcessPhenotype {
+            value,
+            pool,
+        }
+    }
+}
+
+impl<T: LineEncoding + fmt::Debug + 'static> LineEncoding for ProcessPhenotype<T> {
+    fn encode(&self) -> String {
+        self.value.encode()
+    }
+}
+
+impl<T: LineEncoding + fmt::Debug + 'static> Phenotype for ProcessPhenotype<T> {
+    type Fitness = f32;
+
+    fn evaluate(&self) -> f32 {
+        self.pool.evaluate(&self.value.encode()).unwrap_or(self.pool.failure_fitness)
+    }
+}
@@ -0,0 +1,144 @@
+//! The fitness of solutions, and the direction in which it is optimised.
+//!
+//! Each phenotype declares its type of fitness and whether it should be maximised or minimised,
+//! see [Phenotype](super::Phenotype). All algorithms, selectors and statistics respect the
+//! direction, so minimisation problems do not need to negate their fitness.
+
+use std::{cmp, fmt};
+
+/// The fitness of a solution. Fitness values are compared using their partial order. Besides
+/// floating point numbers, this supports integer scores and tuples, which are compared
+/// lexicographically.
+///
+/// Values that cannot be compared to themselves, such as NaN, are never considered fitter than
+/// other values.
+pub trait Fitness: 'static + Copy + PartialOrd + fmt::Debug {
+    /// The fitness as a number. It is used for statistics and logging, and by algorithms that
+    /// need to know how much fitter one solution is than another, such as simulated annealing.
+    /// For tuples, it is the number of the first element.
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_numeric_fitness {
+    ($($t:ty),*) => {
+        $(
+            impl Fitness for $t {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric_fitness!(f32, f64, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl<A: Fitness, B: Fitness> Fitness for (A, B) {
+    fn to_f64(self) -> f64 {
+        self.0.to_f64()
+    }
+}
+
+impl<A: Fitness, B: Fitness, C: Fitness> Fitness for (A, B, C) {
+    fn to_f64(self) -> f64 {
+        self.0.to_f64()
+    }
+}
+
+/// A fitness that can be averaged over several evaluations. It is needed for handling noisy
+/// fitness, see [NoiseHandling](super::noise::NoiseHandling).
+pub trait MeanFitness: Fitness {
+    /// Converts an average back to the fitness.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl MeanFitness for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl MeanFitness for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// Whether the fitness is maximised or minimised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Maximise,
+    Minimise,
+}
+
+impl Direction {
+    /// Whether fitness `a` is better than fitness `b`.
+    pub fn is_better<F: PartialOrd>(self, a: F, b: F) -> bool {
+        match self {
+            Direction::Maximise => a > b,
+            Direction::Minimise => a < b,
+        }
+    }
+
+    /// Whether fitness `a` is at least as good as fitness `b`.
+    pub fn is_at_least_as_good<F: PartialOrd>(self, a: F, b: F) -> bool {
+        match self {
+            Direction::Maximise => a >= b,
+            Direction::Minimise => a <= b,
+        }
+    }
+
+    /// Compares two fitness values, such that the better one is greater. Values that cannot be
+    /// compared are considered equal.
+    pub fn compare<F: PartialOrd>(self, a: &F, b: &F) -> cmp::Ordering {
+        let ordering = a.partial_cmp(b).unwrap_or(cmp::Ordering::Equal);
+
+        match self {
+            Direction::Maximise => ordering,
+            Direction::Minimise => ordering.reverse(),
+        }
+    }
+
+    /// Compares the fitness of two solutions that may not have been evaluated yet, such that the
+    /// better one is greater. An evaluated solution is better than one that is not, and a
+    /// fitness that cannot be compared to itself is worse than any other fitness.
+    pub fn compare_optional<F: PartialOrd>(self, a: &Option<F>, b: &Option<F>) -> cmp::Ordering {
+        let rank = |fitness: &Option<F>| match fitness {
+            Some(fitness) if is_comparable(fitness) => 2,
+            Some(_) => 1,
+            None => 0,
+        };
+
+        match (a, b) {
+            (Some(a), Some(b)) if is_comparable(a) && is_comparable(b) => self.compare(a, b),
+            _ => rank(a).cmp(&rank(b)),
+        }
+    }
+
+    /// How much better the numeric fitness `a` is than `b`. It is negative when `a` is worse.
+    pub fn difference(self, a: f64, b: f64) -> f64 {
+        match self {
+            Direction::Maximise => a - b,
+            Direction::Minimise => b - a,
+        }
+    }
+}
+
+/// Whether the fitness can be compared to itself. It cannot when it is or contains NaN.
+pub fn is_comparable<F: PartialOrd>(fitness: &F) -> bool {
+    fitness.partial_cmp(fitness).is_some()
+}
+
+/// What the evolutionary algorithm does with an evaluation that gives an invalid fitness, i.e. a
+/// fitness that cannot be compared to itself. See [is_comparable].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidFitnessPolicy {
+    /// The evaluation fails with an error, and the individual remains unevaluated.
+    Reject,
+    /// The fitness is kept, but the individual is considered less fit than any individual with a
+    /// valid fitness.
+    Worst,
+    /// The phenotype is evaluated again, at most the given number of times. When its fitness is
+    /// still invalid, it is treated as [Worst](InvalidFitnessPolicy::Worst).
+    Reevaluate(usize),
+}
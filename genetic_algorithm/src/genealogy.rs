@@ -0,0 +1,179 @@
+use super::{Genotype, Individual, Operator, Phenotype};
+use super::fitness::Fitness;
+use super::logging::json_number;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Records how an individual came into existence.
+#[derive(Debug, Clone)]
+pub struct LineageRecord {
+    pub id: u64,
+    pub parents: Vec<u64>,
+    pub birth_generation: usize,
+    pub operators: Vec<Operator>,
+    pub fitness: Option<f64>,
+}
+
+/// The format to export a lineage graph in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineageFormat {
+    /// A Graphviz DOT digraph with edges from parent to child.
+    Dot,
+    /// A JSON object with the id of the root individual and a list of all its ancestors.
+    Json,
+}
+
+/// Keeps the lineage records of individuals so that their ancestry can be traced back to the
+/// initial population.
+///
+/// To bound memory usage, the records of individuals that have no living descendants are
+/// discarded every generation. See [Genealogy::prune].
+#[derive(Debug, Default)]
+pub struct Genealogy {
+    records: HashMap<u64, LineageRecord>,
+}
+
+impl Genealogy {
+    pub fn new() -> Self {
+        Genealogy {
+            records: HashMap::new()
+        }
+    }
+
+    /// Adds or updates the record for the given individual.
+    pub fn record<P: Phenotype, G: Genotype<P>>(&mut self, individual: &Individual<P, G>) {
+        self.records.insert(individual.id, LineageRecord {
+            id: individual.id,
+            parents: individual.parents.clone(),
+            birth_generation: individual.birth_generation,
+            operators: individual.operators.clone(),
+            fitness: individual.fitness.map(Fitness::to_f64),
+        });
+    }
+
+    pub fn get(&self, id: u64) -> Option<&LineageRecord> {
+        self.records.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns the records of the individual and all its known ancestors. The individual itself
+    /// comes first, followed by its ancestors ordered by decreasing birth generation.
+    pub fn ancestry(&self, id: u64) -> Vec<&LineageRecord> {
+        let mut visited = HashSet::new();
+        let mut pending = vec![id];
+        let mut ancestry = Vec::new();
+
+        while let Some(id) = pending.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+
+            if let Some(record) = self.records.get(&id) {
+                pending.extend(record.parents.iter());
+                ancestry.push(record);
+            }
+        }
+
+        // The individual itself is always first as no ancestor can be born after it
+        ancestry.sort_by(|a, b| {
+            b.birth_generation.cmp(&a.birth_generation).then(b.id.cmp(&a.id))
+        });
+
+        ancestry
+    }
+
+    /// Discards the records of all individuals that are not an ancestor of (or equal to) any of
+    /// the given individuals.
+    pub fn prune(&mut self, living: impl Iterator<Item = u64>) {
+        let mut keep = HashSet::new();
+        let mut pending: Vec<u64> = living.collect();
+
+        while let Some(id) = pending.pop() {
+            if keep.insert(id) {
+                if let Some(record) = self.records.get(&id) {
+                    pending.extend(record.parents.iter());
+                }
+            }
+        }
+
+        self.records.retain(|id, _| keep.contains(id));
+    }
+
+    /// Exports the lineage graph of the given individual.
+    pub fn export(&self, id: u64, format: LineageFormat) -> String {
+        match format {
+            LineageFormat::Dot => self.to_dot(id),
+            LineageFormat::Json => self.to_json(id),
+        }
+    }
+
+    pub fn to_dot(&self, id: u64) -> String {
+        let ancestry = self.ancestry(id);
+        let mut out = String::new();
+
+        out.push_str("digraph lineage {\n");
+        out.push_str("    rankdir=BT;\n");
+        for record in ancestry.iter() {
+            let fitness = match record.fitness {
+                Some(fitness) => format!("{}", fitness),
+                None => String::from("?"),
+            };
+            writeln!(
+                out, "    n{} [label=\"#{}\\ngen {}\\nfitness {}\"];",
+                record.id, record.id, record.birth_generation, fitness
+            ).unwrap();
+        }
+        for record in ancestry.iter() {
+            let label = edge_label(&record.operators);
+            for parent in record.parents.iter() {
+                if self.records.contains_key(parent) {
+                    writeln!(out, "    n{} -> n{} [label=\"{}\"];", parent, record.id, label)
+                        .unwrap();
+                }
+            }
+        }
+        out.push_str("}\n");
+
+        out
+    }
+
+    pub fn to_json(&self, id: u64) -> String {
+        let mut out = String::new();
+
+        write!(out, "{{\"root\":{},\"individuals\":[", id).unwrap();
+        for (i, record) in self.ancestry(id).iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let parents: Vec<String> = record.parents.iter().map(|p| p.to_string()).collect();
+            let operators: Vec<String> = record.operators.iter().map(
+                |op| format!("\"{}\"", op.name())
+            ).collect();
+            let fitness = record.fitness.map_or(String::from("null"), json_number);
+            write!(
+                out, "{{\"id\":{},\"parents\":[{}],\"generation\":{},\"operators\":[{}],\
+                \"fitness\":{}}}",
+                record.id, parents.join(","), record.birth_generation, operators.join(","),
+                fitness
+            ).unwrap();
+        }
+        out.push_str("]}");
+
+        out
+    }
+}
+
+fn edge_label(operators: &[Operator]) -> String {
+    if operators.is_empty() {
+        String::from("clone")
+    } else {
+        operators.iter().map(|op| op.name()).collect::<Vec<_>>().join("+")
+    }
+}
@@ -0,0 +1,518 @@
+//! Grammatical evolution, which evolves programs in a language that is defined by a grammar.
+//!
+//! The genotype is a sequence of integer codons. It is mapped to a program by a derivation from
+//! the start symbol of the grammar: the leftmost non-terminal is repeatedly replaced by one of its
+//! productions, which is chosen by the next codon modulo the number of productions. Rules with a
+//! single production do not use a codon. When the codons run out before the program is
+//! complete, reading wraps around to the first codon again, up to a maximum number of wraps.
+//!
+//! Genotypes whose derivation is still incomplete after the last wrap are invalid, and do not
+//! map to a program. Their phenotype typically gets the worst possible fitness, or an invalid
+//! fitness such as NaN so that the [InvalidFitnessPolicy](super::fitness::InvalidFitnessPolicy)
+//! applies.
+//!
+//! Grammars are written in BNF. Each rule consists of a non-terminal, `::=` and its productions,
+//! separated by `|`. Productions can continue on the next lines, when these start with `|`.
+//! The first rule defines the start symbol. Within a production, non-terminals are written
+//! between angle brackets, and all other text is literal, including spaces between symbols.
+//! Text between double or single quotes is literal as well, which allows productions to contain
+//! `|`, `<` or quotes, or to be empty. Lines starting with `#` are comments.
+//!
+//! ```text
+//! <expr> ::= (<expr> <op> <expr>) | <var>
+//! <op>   ::= + | - | *
+//! <var>  ::= x | "1"
+//! ```
+
+use super::{EvolutionError, Mutation, Recombination};
+use super::binary::{BinaryChromosome, LengthLimits};
+use super::diversity::GenotypeDistance;
+use super::random;
+use bit_vec::BitVec;
+use rand::Rng;
+use std::sync::Arc;
+use std::{clone, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Symbol {
+    Terminal(String),
+    NonTerminal(usize),
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: Arc<str>,
+    productions: Vec<Vec<Symbol>>,
+}
+
+/// A context-free grammar.
+#[derive(Debug)]
+pub struct Grammar {
+    rules: Vec<Rule>,
+}
+
+fn invalid_grammar(msg: String) -> EvolutionError {
+    EvolutionError::InvalidGrammar(msg)
+}
+
+/// Splits the right-hand side of a rule into its productions, at each `|` outside quotes.
+fn split_productions(rhs: &str) -> Vec<&str> {
+    let mut productions = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+
+    for (i, c) in rhs.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '|') => {
+                productions.push(&rhs[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    productions.push(&rhs[start..]);
+
+    productions
+}
+
+/// Parses a production into literal text and the names of non-terminals.
+fn parse_production(production: &str) -> Result<Vec<(bool, String)>, EvolutionError> {
+    let production = production.trim();
+    if production.is_empty() {
+        return Err(invalid_grammar(String::from("Empty production, use \"\" instead")));
+    }
+
+    let mut symbols = Vec::new();
+    let mut literal = String::new();
+    let mut chars = production.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '<' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for next in chars.by_ref() {
+                    if next == '>' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(next);
+                }
+                if name.is_empty() || !closed {
+                    return Err(invalid_grammar(format!("Invalid non-terminal in {}", production)));
+                }
+                if !literal.is_empty() {
+                    symbols.push((false, std::mem::take(&mut literal)));
+                }
+                symbols.push((true, name));
+            },
+            '"' | '\'' => {
+                let mut closed = false;
+                for next in chars.by_ref() {
+                    if next == c {
+                        closed = true;
+                        break;
+                    }
+                    literal.push(next);
+                }
+                if !closed {
+                    return Err(invalid_grammar(format!("Unclosed quote in {}", production)));
+                }
+            },
+            _ => literal.push(c),
+        }
+    }
+    if !literal.is_empty() {
+        symbols.push((false, literal));
+    }
+
+    Ok(symbols)
+}
+
+impl Grammar {
+    /// Parses a grammar in BNF, see the [module documentation](self). Fails when the grammar
+    /// has no rules, uses a non-terminal that is not defined, or defines a non-terminal twice.
+    /// It also fails when a non-terminal can never be derived into a program, as the mapping of
+    /// a genotype could then go on forever.
+    pub fn parse(bnf: &str) -> Result<Self, EvolutionError> {
+        // The name and right-hand side of each rule
+        let mut definitions: Vec<(String, String)> = Vec::new();
+
+        for line in bnf.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some((lhs, rhs)) = line.split_once("::=") {
+                let lhs = lhs.trim();
+                let name = lhs.strip_prefix('<').and_then(|lhs| lhs.strip_suffix('>'))
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| invalid_grammar(format!("Invalid rule name {}", lhs)))?;
+                if definitions.iter().any(|(defined, _)| defined == name) {
+                    return Err(invalid_grammar(format!("<{}> is defined twice", name)));
+                }
+                definitions.push((String::from(name), String::from(rhs)));
+            } else if trimmed.starts_with('|') && !definitions.is_empty() {
+                definitions.last_mut().unwrap().1.push_str(trimmed);
+            } else {
+                return Err(invalid_grammar(format!("Expected a rule: {}", trimmed)));
+            }
+        }
+
+        if definitions.is_empty() {
+            return Err(invalid_grammar(String::from("There are no rules")));
+        }
+
+        let names: Vec<Arc<str>> = definitions.iter().map(|(name, _)| Arc::from(name.as_str()))
+            .collect();
+        let mut rules = Vec::with_capacity(definitions.len());
+        for (name, (_, rhs)) in names.iter().zip(definitions.iter()) {
+            let mut productions = Vec::new();
+            for production in split_productions(rhs) {
+                let symbols = parse_production(production)?.into_iter().map(
+                    |(is_non_terminal, text)| if is_non_terminal {
+                        names.iter().position(|name| **name == *text).map(Symbol::NonTerminal)
+                            .ok_or_else(|| invalid_grammar(format!("<{}> is not defined", text)))
+                    } else {
+                        Ok(Symbol::Terminal(text))
+                    }
+                ).collect::<Result<_, _>>()?;
+                productions.push(symbols);
+            }
+            rules.push(Rule {
+                name: Arc::clone(name),
+                productions,
+            });
+        }
+
+        let grammar = Grammar { rules };
+        grammar.check_productive()?;
+        Ok(grammar)
+    }
+
+    /// Checks that every non-terminal has a production that eventually consists of terminals
+    /// only.
+    fn check_productive(&self) -> Result<(), EvolutionError> {
+        let mut productive = vec![false; self.rules.len()];
+
+        loop {
+            let mut changed = false;
+            for (i, rule) in self.rules.iter().enumerate() {
+                if !productive[i] && rule.productions.iter().any(|production| {
+                    production.iter().all(|symbol| match symbol {
+                        Symbol::Terminal(_) => true,
+                        Symbol::NonTerminal(j) => productive[*j],
+                    })
+                }) {
+                    productive[i] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        match productive.iter().position(|productive| !productive) {
+            Some(i) => Err(invalid_grammar(
+                format!("<{}> can never be derived into terminals", self.rules[i].name)
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The name of the start symbol, without angle brackets.
+    pub fn start(&self) -> &str {
+        &self.rules[0].name
+    }
+
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Derives a program from the start symbol, choosing productions using the given codons.
+    /// Returns `None` when the codons run out after the maximum number of wraps.
+    pub fn map(&self, codons: &[u8], max_wraps: usize) -> Option<Program> {
+        let mut reader = CodonReader {
+            codons,
+            num_read: 0,
+            max_reads: codons.len() * (max_wraps + 1),
+        };
+        let mut text = String::new();
+        let tree = self.derive(0, &mut reader, &mut text)?;
+
+        Some(Program {
+            text,
+            tree,
+            codons_used: reader.num_read,
+        })
+    }
+
+    fn derive(
+        &self, rule: usize, reader: &mut CodonReader, text: &mut String
+    ) -> Option<Derivation> {
+        let productions = &self.rules[rule].productions;
+        let production = match productions.len() {
+            1 => 0,
+            len => reader.next()? as usize % len,
+        };
+
+        let children = productions[production].iter().map(|symbol| match symbol {
+            Symbol::Terminal(terminal) => {
+                text.push_str(terminal);
+                Some(Derivation::Terminal(terminal.clone()))
+            },
+            Symbol::NonTerminal(rule) => self.derive(*rule, reader, text),
+        }).collect::<Option<_>>()?;
+
+        Some(Derivation::NonTerminal {
+            rule: Arc::clone(&self.rules[rule].name),
+            production,
+            children,
+        })
+    }
+}
+
+/// Reads codons, wrapping around to the start until the maximum number of reads.
+struct CodonReader<'a> {
+    codons: &'a [u8],
+    num_read: usize,
+    max_reads: usize,
+}
+
+impl<'a> CodonReader<'a> {
+    fn next(&mut self) -> Option<u8> {
+        if self.num_read == self.max_reads {
+            return None;
+        }
+        let codon = self.codons[self.num_read % self.codons.len()];
+        self.num_read += 1;
+        Some(codon)
+    }
+}
+
+/// The derivation tree of a program, which is its abstract syntax tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Derivation {
+    Terminal(String),
+    /// A non-terminal, which was replaced by the production with the given index in its rule.
+    NonTerminal {
+        rule: Arc<str>,
+        production: usize,
+        children: Vec<Derivation>,
+    },
+}
+
+/// A program that a genotype is mapped to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// The text of the program, which consists of the terminals of the derivation.
+    pub text: String,
+    pub tree: Derivation,
+    /// The number of codons that the derivation used, counting codons that were read again
+    /// after wrapping.
+    pub codons_used: usize,
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Reads the bits as 8-bit codons, most significant bit first. Trailing bits that do not form a
+/// whole codon are ignored.
+pub fn codons_from_bits(bits: &BitVec) -> Vec<u8> {
+    let mut codons = bits.to_bytes();
+    codons.truncate(bits.len() / 8);
+    codons
+}
+
+/// Maps genotypes to programs, using a grammar and a maximum number of wraps.
+#[derive(Debug)]
+pub struct Mapper {
+    grammar: Grammar,
+    max_wraps: usize,
+}
+
+impl Mapper {
+    pub fn new(grammar: Grammar, max_wraps: usize) -> Self {
+        Mapper {
+            grammar,
+            max_wraps,
+        }
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    pub fn max_wraps(&self) -> usize {
+        self.max_wraps
+    }
+
+    /// Maps the codons to a program. Returns `None` for invalid genotypes.
+    pub fn map(&self, codons: &[u8]) -> Option<Program> {
+        self.grammar.map(codons, self.max_wraps)
+    }
+
+    /// Maps a binary chromosome to a program, reading it as 8-bit codons. See
+    /// [codons_from_bits].
+    pub fn map_binary(&self, chromosome: &BinaryChromosome) -> Option<Program> {
+        self.map(&codons_from_bits(&chromosome.bits))
+    }
+}
+
+/// A chromosome of integer codons, for grammatical evolution. It shares the mapper that turns
+/// it into a program with the chromosomes it is cloned into.
+pub struct CodonChromosome {
+    pub codons: Vec<u8>,
+    mapper: Arc<Mapper>,
+}
+
+impl CodonChromosome {
+    /// Creates a chromosome of `len` random codons.
+    pub fn new(len: usize, mapper: Arc<Mapper>) -> Self {
+        let mut rng = random::rng();
+
+        CodonChromosome {
+            codons: (0..len).map(|_| rng.gen()).collect(),
+            mapper,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.codons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codons.is_empty()
+    }
+
+    pub fn mapper(&self) -> &Arc<Mapper> {
+        &self.mapper
+    }
+
+    /// Maps the chromosome to a program. Returns `None` when it is invalid.
+    pub fn map(&self) -> Option<Program> {
+        self.mapper.map(&self.codons)
+    }
+}
+
+impl clone::Clone for CodonChromosome {
+    fn clone(&self) -> Self {
+        CodonChromosome {
+            codons: self.codons.clone(),
+            mapper: Arc::clone(&self.mapper),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.codons.clone_from(&source.codons);
+        self.mapper = Arc::clone(&source.mapper);
+    }
+}
+
+impl PartialEq for CodonChromosome {
+    fn eq(&self, other: &Self) -> bool {
+        self.codons == other.codons
+    }
+}
+
+impl fmt::Debug for CodonChromosome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CodonChromosome")
+            .field("codons", &self.codons)
+            .finish()
+    }
+}
+
+impl GenotypeDistance for CodonChromosome {
+    /// Returns the number of codons that differ. When the lengths differ, each codon beyond the
+    /// end of the shorter chromosome counts as a difference.
+    fn distance(&self, other: &Self) -> f32 {
+        let differences = self.codons.iter().zip(other.codons.iter()).filter(|(a, b)| a != b)
+            .count();
+
+        (differences + self.len().abs_diff(other.len())) as f32
+    }
+}
+
+/// Replaces codons by random ones.
+#[derive(Debug)]
+pub struct CodonMutation {
+    mutate_prob: f32,
+}
+
+impl CodonMutation {
+    /// Creates a new Codon Mutation operator. The mutation probability is the probability that
+    /// each codon is replaced. It should be in range [0, 1].
+    pub fn new(mutate_prob: f32) -> Result<Self, EvolutionError> {
+        if !(0.0..=1.0).contains(&mutate_prob) {
+            return Err(EvolutionError::InvalidParameter("mutate_prob", "in range [0, 1]"));
+        }
+
+        Ok(CodonMutation {
+            mutate_prob
+        })
+    }
+}
+
+impl Mutation for CodonMutation {
+    type Genotype = CodonChromosome;
+
+    fn mutate(&self, target: &mut Self::Genotype) {
+        let mut rng = random::rng();
+
+        for codon in target.codons.iter_mut() {
+            if rng.gen::<f32>() < self.mutate_prob {
+                *codon = rng.gen();
+            }
+        }
+    }
+}
+
+/// Cut-and-splice crossover of codon chromosomes, which is the usual crossover of grammatical
+/// evolution. See [BinaryCutAndSpliceCrossover](super::binary::BinaryCutAndSpliceCrossover).
+#[derive(Debug)]
+pub struct CodonCutAndSpliceCrossover {
+    limits: LengthLimits,
+}
+
+impl CodonCutAndSpliceCrossover {
+    pub fn new(limits: LengthLimits) -> Self {
+        CodonCutAndSpliceCrossover {
+            limits
+        }
+    }
+}
+
+impl Recombination for CodonCutAndSpliceCrossover {
+    type Genotype = CodonChromosome;
+
+    fn recombine(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype
+    ) -> Result<Self::Genotype, EvolutionError> {
+        let mut child = parent1.clone();
+        self.recombine_into(parent1, parent2, &mut child)?;
+
+        Ok(child)
+    }
+
+    fn recombine_into(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype, child: &mut Self::Genotype
+    ) -> Result<(), EvolutionError> {
+        let len2 = parent2.len();
+        let (head, tail) = self.limits.random_splice(parent1.len(), len2)?;
+
+        child.mapper = Arc::clone(&parent1.mapper);
+        child.codons.clear();
+        child.codons.extend_from_slice(&parent1.codons[..head]);
+        child.codons.extend_from_slice(&parent2.codons[len2 - tail..]);
+
+        Ok(())
+    }
+}
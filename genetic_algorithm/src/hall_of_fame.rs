@@ -0,0 +1,201 @@
+//! An archive of the best individuals found during a run.
+//!
+//! The population only contains the current generation, so a good solution can be lost when the
+//! next generation is bred. The hall of fame keeps the fittest individuals ever evaluated, so that
+//! the best solutions are still available at the end of the run.
+//!
+//! The hall of fame is included in the run summary, see
+//! [log_summary](crate::EvolutionaryAlgorithm::log_summary). It can also be written to a file
+//! and read back, see [HallOfFame::write], so that it survives when a run is interrupted and
+//! started again.
+
+use super::{reserve_ids, EvolutionError, Genotype, Individual, Phenotype};
+use super::fitness::is_comparable;
+use std::io::{self, BufRead, Write};
+use std::fmt;
+
+/// Encodes a value as a single line of text, so that it can be written to a file and read back.
+/// The encoding must not contain any tabs or newlines.
+pub trait TextEncoding: Sized {
+    fn encode(&self) -> String;
+
+    /// Returns `None` when the text is not a valid encoding.
+    fn decode(text: &str) -> Option<Self>;
+}
+
+macro_rules! impl_numeric_encoding {
+    ($($t:ty),*) => {
+        $(
+            impl TextEncoding for $t {
+                fn encode(&self) -> String {
+                    self.to_string()
+                }
+
+                fn decode(text: &str) -> Option<Self> {
+                    text.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric_encoding!(f32, f64, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+/// An individual in the hall of fame.
+#[derive(Debug)]
+pub struct HallOfFameEntry<P: Phenotype, G: Genotype<P>> {
+    pub id: u64,
+    pub genotype: G,
+    pub phenotype: P,
+    pub fitness: P::Fitness,
+    /// The generation in which the individual was evaluated.
+    pub generation: usize,
+}
+
+/// Keeps the fittest distinct individuals that were ever evaluated, up to a maximum number.
+///
+/// Individuals are always distinct by id, so an elite that survives several generations is only
+/// listed once. Optionally, individuals with equal genotypes can be considered duplicates too.
+pub struct HallOfFame<P: Phenotype, G: Genotype<P>> {
+    capacity: usize,
+    // Sorted by fitness, fittest first
+    entries: Vec<HallOfFameEntry<P, G>>,
+    is_duplicate: fn(&G, &G) -> bool,
+}
+
+impl<P: Phenotype, G: Genotype<P>> HallOfFame<P, G> {
+    /// Creates a hall of fame that keeps at most `capacity` individuals.
+    pub fn new(capacity: usize) -> Self {
+        HallOfFame {
+            capacity,
+            entries: Vec::with_capacity(capacity),
+            is_duplicate: |_, _| false,
+        }
+    }
+
+    /// Creates a hall of fame that keeps at most `capacity` individuals, which all have a
+    /// different genotype. Of individuals with equal genotypes, only the first one is kept.
+    pub fn with_unique_genotypes(capacity: usize) -> Self where G: PartialEq {
+        HallOfFame {
+            is_duplicate: |a, b| a == b,
+            ..Self::new(capacity)
+        }
+    }
+
+    /// Adds an evaluated individual when it is fit enough. Its phenotype is expressed again, as
+    /// the hall of fame keeps it after the individual is gone.
+    pub fn consider(&mut self, individual: &Individual<P, G>, generation: usize) {
+        if let Some(fitness) = individual.fitness() {
+            self.insert(individual.id(), individual.genotype(), fitness, generation);
+        }
+    }
+
+    fn insert(&mut self, id: u64, genotype: &G, fitness: P::Fitness, generation: usize) {
+        if !is_comparable(&fitness) {
+            return;
+        }
+        if self.entries.len() == self.capacity && !self.entries.last().is_some_and(
+            |worst| P::DIRECTION.is_better(fitness, worst.fitness)
+        ) {
+            return;
+        }
+        if self.entries.iter().any(
+            |entry| entry.id == id || (self.is_duplicate)(&entry.genotype, genotype)
+        ) {
+            return;
+        }
+
+        let index = self.entries.partition_point(
+            |entry| P::DIRECTION.is_at_least_as_good(entry.fitness, fitness)
+        );
+        self.entries.insert(index, HallOfFameEntry {
+            id,
+            genotype: genotype.clone(),
+            phenotype: genotype.express(),
+            fitness,
+            generation,
+        });
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Writes the individuals, fittest first, so that they can be read back with
+    /// [read](HallOfFame::read). Each line contains the id, generation, fitness and genotype of
+    /// one individual, separated by tabs.
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()>
+    where
+        P::Fitness: TextEncoding,
+        G: TextEncoding,
+    {
+        for entry in self.entries.iter() {
+            writeln!(
+                out, "{}\t{}\t{}\t{}",
+                entry.id, entry.generation, entry.fitness.encode(), entry.genotype.encode()
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads individuals that were written by [write](HallOfFame::write), and considers them as
+    /// if they were just evaluated. Their phenotypes are expressed again. Individuals created
+    /// from then on get a higher id than any individual that was read, so that they are not
+    /// mistaken for each other.
+    pub fn read(&mut self, input: impl BufRead) -> Result<(), EvolutionError>
+    where
+        P::Fitness: TextEncoding,
+        G: TextEncoding,
+    {
+        for (i, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let entry = match fields[..] {
+                [id, generation, fitness, genotype] => id.parse().ok().zip(generation.parse().ok())
+                    .zip(P::Fitness::decode(fitness)).zip(G::decode(genotype)),
+                _ => None,
+            };
+            let (((id, generation), fitness), genotype) = entry.ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidData, format!("Invalid hall of fame entry on line {}", i + 1)
+            ))?;
+
+            reserve_ids(id);
+            self.insert(id, &genotype, fitness, generation);
+        }
+
+        Ok(())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The fittest individual ever evaluated.
+    pub fn best(&self) -> Option<&HallOfFameEntry<P, G>> {
+        self.entries.first()
+    }
+
+    /// Returns the individuals, fittest first.
+    pub fn entries(&self) -> &[HallOfFameEntry<P, G>] {
+        &self.entries
+    }
+}
+
+impl<P: Phenotype, G: Genotype<P>> fmt::Debug for HallOfFame<P, G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HallOfFame")
+            .field("capacity", &self.capacity)
+            .field("entries", &self.entries)
+            .finish()
+    }
+}
@@ -1,5 +1,96 @@
-use std::{clone, fmt, slice};
-use rand::{self, Rng};
+use std::{clone, cmp, error, fmt, io, iter, slice};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use rand::Rng;
+use genealogy::{Genealogy, LineageFormat};
+use logging::{HallOfFameSnapshot, IndividualSnapshot, LogSink, RunSummary};
+use builder::EvolutionaryAlgorithmBuilder;
+use hall_of_fame::HallOfFame;
+use diversity::{Diversity, GenotypeDistance};
+use memetic::{LearningMode, LocalSearch, MemeticConfig};
+use optimiser::Optimiser;
+use fitness::{is_comparable, Direction, Fitness, InvalidFitnessPolicy, MeanFitness};
+use noise::{FitnessSamples, NoiseConfig, NoiseHandling};
+
+/// Errors reported by the evolutionary algorithm and its operators.
+#[derive(Debug)]
+pub enum EvolutionError {
+    /// A configuration parameter is outside its valid range. Contains the name of the parameter
+    /// and a description of the valid range.
+    InvalidParameter(&'static str, &'static str),
+    /// Two sequences that should have the same length do not, such as two parents, or the
+    /// phenotypes given to an evaluator and the fitness values it returned. Contains both lengths.
+    LengthMismatch(usize, usize),
+    /// The chromosome is too short for the operator. Contains the actual and minimum length.
+    ChromosomeTooShort(usize, usize),
+    /// The operation requires a population, but there is none yet. To create one, use
+    /// [EvolutionaryAlgorithm::start].
+    NoPopulation,
+    /// Writing a log or other output failed.
+    Io(io::Error),
+    /// An external evaluation did not complete in time.
+    EvaluationTimeout,
+    /// An external worker process exited or could not be started, or a worker thread panicked.
+    WorkerCrashed(String),
+    /// An external worker sent or was asked to send something that does not follow the protocol.
+    ProtocolViolation(String),
+    /// The handle does not refer to an individual that is awaiting evaluation. It may have been
+    /// evaluated already, or belong to an earlier generation.
+    UnknownHandle(Handle),
+    /// The evaluation of an individual gave an invalid fitness, which is rejected. Contains the
+    /// id of the individual, or for optimisers without individuals, the index of the solution in
+    /// its generation. See [InvalidFitnessPolicy].
+    InvalidFitness(u64),
+    /// The population contains individuals that were neither evaluated nor handed out for
+    /// evaluation. Contains their number.
+    Unevaluated(usize),
+    /// A grammar could not be parsed, or is not usable. Contains a description of the problem.
+    InvalidGrammar(String),
+}
+
+impl fmt::Display for EvolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvolutionError::InvalidParameter(name, range) => {
+                write!(f, "Parameter {} out of range, it should be {}", name, range)
+            },
+            EvolutionError::LengthMismatch(len1, len2) => {
+                write!(f, "Length mismatch: {} != {}", len1, len2)
+            },
+            EvolutionError::ChromosomeTooShort(len, min_len) => {
+                write!(f, "Chromosome too short: length is {}, minimum is {}", len, min_len)
+            },
+            EvolutionError::NoPopulation => write!(f, "There is no population"),
+            EvolutionError::Io(err) => write!(f, "I/O error: {}", err),
+            EvolutionError::EvaluationTimeout => write!(f, "Evaluation timed out"),
+            EvolutionError::WorkerCrashed(msg) => write!(f, "Worker crashed: {}", msg),
+            EvolutionError::ProtocolViolation(msg) => write!(f, "Protocol violation: {}", msg),
+            EvolutionError::UnknownHandle(handle) => write!(f, "Unknown handle: {}", handle.0),
+            EvolutionError::InvalidFitness(id) => {
+                write!(f, "Invalid fitness for individual {}", id)
+            },
+            EvolutionError::Unevaluated(num) => {
+                write!(f, "{} individuals have not been evaluated", num)
+            },
+            EvolutionError::InvalidGrammar(msg) => write!(f, "Invalid grammar: {}", msg),
+        }
+    }
+}
+
+impl error::Error for EvolutionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            EvolutionError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EvolutionError {
+    fn from(err: io::Error) -> Self {
+        EvolutionError::Io(err)
+    }
+}
 
 /// A phenotype represents a solution to the optimisation problem. How good the solution is is
 /// expressed by its fitness, which influences selection by the evolutionary algorithm. 
@@ -13,11 +104,29 @@ use rand::{self, Rng};
 ///   can have a big impact on the quality of the search. In this case, the phenotype remains the
 ///   same, as they all try to solve the same problem.
 pub trait Phenotype : 'static + fmt::Debug {
+    /// The type of fitness. See [Fitness].
+    type Fitness: Fitness;
+
+    /// Whether the fitness is maximised or minimised. Defaults to maximisation.
+    const DIRECTION: Direction = Direction::Maximise;
+
     /// Evaluates the fitness for the phenotype
     ///
     /// TODO: Generalize to support cases where the fitness cannot be determined in isolation.
     /// E.g. where fitness is based on interaction with other individuals in the population.
-    fn evaluate(&self) -> f32;
+    fn evaluate(&self) -> Self::Fitness;
+}
+
+/// An evaluator determines the fitness of phenotypes on behalf of the evolutionary algorithm. It
+/// can be used to evaluate phenotypes in bulk, for example concurrently or elsewhere. By default,
+/// the algorithm evaluates each phenotype in turn using [Phenotype::evaluate].
+pub trait Evaluator<P: Phenotype>: fmt::Debug {
+    /// Evaluates the given phenotypes. Returns their fitness, in the same order.
+    ///
+    /// It must return exactly one fitness per phenotype. When it does not, the optimiser fails
+    /// with [EvolutionError::LengthMismatch]. A phenotype that could not be evaluated should get
+    /// an invalid fitness instead, see [InvalidFitnessPolicy].
+    fn evaluate(&self, phenotypes: &[&P]) -> Vec<P::Fitness>;
 }
 
 /// A genotype encodes a solution to the optimisation problem.
@@ -25,6 +134,11 @@ pub trait Genotype<P: Phenotype> : 'static + fmt::Debug + clone::Clone {
 
     fn express(&self) -> P;
 
+    /// The length of the genome, for genotypes whose length can vary. It is used to report
+    /// genome length statistics.
+    fn genome_length(&self) -> Option<usize> {
+        None
+    }
 }
 
 pub trait Mutation {
@@ -37,8 +151,18 @@ pub trait Recombination {
     type Genotype;
 
     fn recombine(
-        &self, parent1: &Self::Genotype, parent1: &Self::Genotype
-    ) -> Self::Genotype;
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype
+    ) -> Result<Self::Genotype, EvolutionError>;
+
+    /// Recombines the parents into an existing child, replacing its contents. This lets
+    /// implementations re-use the storage of the child. By default, it replaces the child by the
+    /// result of [recombine].
+    fn recombine_into(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype, child: &mut Self::Genotype
+    ) -> Result<(), EvolutionError> {
+        *child = self.recombine(parent1, parent2)?;
+        Ok(())
+    }
 }
 
 pub trait GenotypeFactory<P: Phenotype, G: Genotype<P>> {
@@ -47,27 +171,138 @@ pub trait GenotypeFactory<P: Phenotype, G: Genotype<P>> {
 
 pub trait GenotypeManipulation<P: Phenotype, G: Genotype<P>> {
     fn mutate(&self, target: &mut G);
-    fn recombine(&self, parent1: &G, parent2: &G) -> G;
+    fn recombine(&self, parent1: &G, parent2: &G) -> Result<G, EvolutionError>;
+
+    /// Recombines the parents into an existing child. See [Recombination::recombine_into].
+    fn recombine_into(
+        &self, parent1: &G, parent2: &G, child: &mut G
+    ) -> Result<(), EvolutionError> {
+        *child = self.recombine(parent1, parent2)?;
+        Ok(())
+    }
 }
 
 pub trait GenotypeConfig<P: Phenotype, G: Genotype<P>>: 
     GenotypeFactory<P, G> + GenotypeManipulation<P, G> + fmt::Debug {}
 
+/// The genetic operators that can be applied to create a new individual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Recombination,
+    Mutation,
+    LocalSearch,
+}
+
+impl Operator {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operator::Recombination => "recombination",
+            Operator::Mutation => "mutation",
+            Operator::LocalSearch => "local search",
+        }
+    }
+}
+
+static NEXT_INDIVIDUAL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Makes sure that individuals created from now on get a higher id than `id`, which belongs to
+/// an individual of an earlier run.
+pub(crate) fn reserve_ids(id: u64) {
+    NEXT_INDIVIDUAL_ID.fetch_max(id.saturating_add(1), Ordering::Relaxed);
+}
+
 #[derive(Debug)]
 pub struct Individual<P: Phenotype, G: Genotype<P>> {
-    genotype: Box<G>,
-    phenotype: Option<Box<P>>,
-    fitness: Option<f32>,
+    id: u64,
+    parents: Vec<u64>,
+    birth_generation: usize,
+    operators: Vec<Operator>,
+    genotype: G,
+    phenotype: Option<P>,
+    fitness: Option<P::Fitness>,
+    // Only kept when noise handling is enabled
+    samples: Option<FitnessSamples>,
 }
 
 impl<P: Phenotype, G: Genotype<P>> Individual<P, G> {
-    pub fn new(genotype: Box<G>) -> Self {
+    /// Creates a new individual without parents. It is assigned an id that is unique within the
+    /// running process.
+    pub fn new(genotype: G) -> Self {
         Individual {
+            id: NEXT_INDIVIDUAL_ID.fetch_add(1, Ordering::Relaxed),
+            parents: Vec::new(),
+            birth_generation: 0,
+            operators: Vec::new(),
             genotype,
             phenotype: None,
-            fitness: None
+            fitness: None,
+            samples: None,
         }
     }
+
+    /// Turns this into a new individual without origin, phenotype or fitness. The genotype is
+    /// kept, so that its storage can be re-used.
+    fn reset(&mut self, birth_generation: usize) {
+        self.id = NEXT_INDIVIDUAL_ID.fetch_add(1, Ordering::Relaxed);
+        self.parents.clear();
+        self.birth_generation = birth_generation;
+        self.operators.clear();
+        self.phenotype = None;
+        self.fitness = None;
+        self.samples = None;
+    }
+
+    /// Turns this into a copy of the given individual, for when it survives into the next
+    /// generation. As it is the same individual, it keeps its id, origin, fitness and fitness
+    /// samples. Only the phenotype is not copied.
+    fn copy_from(&mut self, other: &Self) {
+        self.id = other.id;
+        self.parents.clone_from(&other.parents);
+        self.birth_generation = other.birth_generation;
+        self.operators.clone_from(&other.operators);
+        self.genotype.clone_from(&other.genotype);
+        self.phenotype = None;
+        self.fitness = other.fitness;
+        self.samples = other.samples;
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The ids of the parents. It is empty for individuals of the initial population.
+    pub fn parents(&self) -> &[u64] {
+        &self.parents
+    }
+
+    pub fn birth_generation(&self) -> usize {
+        self.birth_generation
+    }
+
+    /// The operators that were applied, in order, to create this individual from its parents.
+    /// When it is empty, the individual is a copy of its parent.
+    pub fn operators(&self) -> &[Operator] {
+        &self.operators
+    }
+
+    pub fn genotype(&self) -> &G {
+        &self.genotype
+    }
+
+    pub fn phenotype(&self) -> Option<&P> {
+        self.phenotype.as_ref()
+    }
+
+    /// The fitness of the individual. When noise handling is enabled, it is estimated from the
+    /// samples. See [NoiseHandling].
+    pub fn fitness(&self) -> Option<P::Fitness> {
+        self.fitness
+    }
+
+    /// The fitness samples, when noise handling is enabled.
+    pub fn samples(&self) -> Option<&FitnessSamples> {
+        self.samples.as_ref()
+    }
 }
 
 pub struct Population<P: Phenotype, G: Genotype<P>> {
@@ -81,12 +316,10 @@ impl<P: Phenotype, G: Genotype<P>> Population<P, G> {
         }
     }
  
-    // TODO: Change GenotypeConfig to GenotypeFactory. This requires cast of trait to super trait.
-    // See: https://users.rust-lang.org/t/casting-traitobject-to-super-trait/33524/8
-    pub fn populate(&mut self, size: usize, genotype_factory: &(dyn GenotypeConfig<P, G>)) {
+    pub fn populate(&mut self, size: usize, genotype_factory: &dyn GenotypeFactory<P, G>) {
         while self.individuals.len() < size {
             self.individuals.push(
-                Individual::new(Box::new(genotype_factory.create()))
+                Individual::new(genotype_factory.create())
             );
         }
     }
@@ -95,6 +328,16 @@ impl<P: Phenotype, G: Genotype<P>> Population<P, G> {
         self.individuals.push(individual);
     }
 
+    /// Returns the individual at the given index, so that it can be overwritten. When there is
+    /// no individual there yet, it is created with a copy of the given genotype.
+    fn slot(&mut self, index: usize, genotype: &G) -> &mut Individual<P, G> {
+        if index >= self.individuals.len() {
+            self.individuals.push(Individual::new(genotype.clone()));
+        }
+
+        &mut self.individuals[index]
+    }
+
     pub fn size(&self) -> usize {
         self.individuals.len()
     }
@@ -123,13 +366,117 @@ pub trait Selector<P: Phenotype, G: Genotype<P>> {
 }
 
 pub trait SelectionFactory<P: Phenotype, G: Genotype<P>>: fmt::Debug {
-    fn select_from(&self, population: Population<P, G>) -> Box<dyn Selector<P, G>>;
+    fn select_from<'a>(
+        &self, population: &'a Population<P, G>
+    ) -> Box<dyn Selector<P, G> + 'a>;
 }
 
-#[derive(Debug)]
+/// Identifies an individual that was handed out for evaluation. See [EvolutionaryAlgorithm::ask].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Handle(u64);
+
+impl Handle {
+    /// The id of the individual.
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LengthStats {
+    pub min: usize,
+    pub max: usize,
+    pub avg: f32,
+}
+
+impl LengthStats {
+    fn from_lengths(lengths: impl Iterator<Item = usize>) -> Option<Self> {
+        let mut stats: Option<LengthStats> = None;
+        let mut sum = 0;
+        let mut num = 0;
+
+        for length in lengths {
+            sum += length;
+            num += 1;
+            stats = Some(match stats {
+                None => LengthStats { min: length, max: length, avg: 0.0 },
+                Some(stats) => LengthStats {
+                    min: stats.min.min(length),
+                    max: stats.max.max(length),
+                    avg: 0.0
+                }
+            });
+        }
+
+        stats.map(|stats| LengthStats { avg: sum as f32 / num as f32, ..stats })
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Stats {
-    max_fitness: f32,
-    avg_fitness: f32
+    /// The fitness of the fittest individual, as a number. See [Fitness::to_f64].
+    pub best_fitness: f64,
+    pub avg_fitness: f64,
+    /// Whether the fitness is maximised or minimised.
+    pub direction: Direction,
+    /// The number of evaluations in the generation that gave an invalid fitness. See
+    /// [InvalidFitnessPolicy].
+    pub invalid_evaluations: usize,
+    /// Genome length statistics. Only available for genotypes that report their length, see
+    /// [Genotype::genome_length].
+    pub genome_length: Option<LengthStats>,
+    /// Diversity of the population. Only available when diversity measurement is enabled, see
+    /// [EvolutionaryAlgorithm::enable_diversity].
+    pub diversity: Option<Diversity>,
+}
+
+impl Stats {
+    /// Computes the statistics of a generation, from the fitness of each individual and their
+    /// genotypes. The fitness is `None` for individuals that are not yet evaluated. Invalid
+    /// fitness values are counted, but otherwise ignored. Returns `None` when no individual is
+    /// evaluated.
+    pub(crate) fn from_generation<P: Phenotype, G: Genotype<P>>(
+        fitnesses: impl Iterator<Item = Option<P::Fitness>>,
+        genotypes: &[&G],
+        diversity: Option<fn(&[&G]) -> Diversity>
+    ) -> Option<Self> {
+        let mut best: Option<P::Fitness> = None;
+        let mut sum: f64 = 0.0;
+        let mut num: usize = 0;
+        let mut num_invalid: usize = 0;
+
+        for fitness in fitnesses.flatten() {
+            if !is_comparable(&fitness) {
+                num_invalid += 1;
+                continue;
+            }
+
+            sum += fitness.to_f64();
+            num += 1;
+            if best.is_none_or(|best| P::DIRECTION.is_better(fitness, best)) {
+                best = Some(fitness);
+            }
+        }
+
+        if num + num_invalid == 0 {
+            return None;
+        }
+        let best_fitness = best.map_or(f64::NAN, Fitness::to_f64);
+        let avg_fitness = sum / (num as f64);
+        let genome_length = LengthStats::from_lengths(
+            genotypes.iter().filter_map(|genotype| genotype.genome_length())
+        );
+        let diversity = diversity.map(|diversity| diversity(genotypes));
+
+        Some(Stats {
+            best_fitness,
+            avg_fitness,
+            direction: P::DIRECTION,
+            invalid_evaluations: num_invalid,
+            genome_length,
+            diversity
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -137,25 +484,140 @@ pub struct EvolutionaryAlgorithm<P: Phenotype, G: Genotype<P>> {
     pop_size: usize,
     recombination_prob: f32,
     mutation_prob: f32,
+    elitism: usize,
     selection: Box<dyn SelectionFactory<P, G>>,
     config: Box<dyn GenotypeConfig<P, G>>,
     population: Option<Population<P, G>>,
+    // The population of the previous generation. Its individuals are re-used when breeding.
+    offspring: Option<Population<P, G>>,
+    generation: usize,
+    evaluations: usize,
+    invalid_fitness_policy: InvalidFitnessPolicy,
+    // The number of evaluations in the current generation that gave an invalid fitness
+    invalid_evaluations: usize,
+    genealogy: Option<Genealogy>,
+    hall_of_fame: Option<HallOfFame<P, G>>,
+    // Measures the diversity of the population. It is only set when the genotype supports it.
+    diversity: Option<fn(&[&G]) -> Diversity>,
+    memetic: Option<MemeticConfig<P, G>>,
+    noise: Option<NoiseConfig<P::Fitness>>,
+    log_sinks: Vec<Box<dyn LogSink>>,
+    evaluator: Option<Box<dyn Evaluator<P>>>,
+    // Maps the ids of individuals that were asked for, but not yet told about, to their index in
+    // the population.
+    pending: HashMap<u64, usize>,
 }
 
 impl<P: Phenotype, G: Genotype<P>> EvolutionaryAlgorithm<P, G> {
+    /// Returns a builder, for configuring the algorithm fluently.
+    pub fn builder() -> EvolutionaryAlgorithmBuilder<P, G> {
+        EvolutionaryAlgorithmBuilder::new()
+    }
+
     pub fn new(
         pop_size: usize,
         config: Box<dyn GenotypeConfig<P, G>>,
         selection: Box<dyn SelectionFactory<P, G>>
-    ) -> Self {
-        EvolutionaryAlgorithm {
+    ) -> Result<Self, EvolutionError> {
+        if pop_size == 0 {
+            return Err(EvolutionError::InvalidParameter("pop_size", "at least 1"));
+        }
+
+        Ok(EvolutionaryAlgorithm {
             pop_size,
             config,
             recombination_prob: 0.8,
             mutation_prob: 0.8,
+            elitism: 0,
             selection,
             population: None,
+            offspring: None,
+            generation: 0,
+            evaluations: 0,
+            invalid_fitness_policy: InvalidFitnessPolicy::Worst,
+            invalid_evaluations: 0,
+            genealogy: None,
+            hall_of_fame: None,
+            diversity: None,
+            memetic: None,
+            noise: None,
+            log_sinks: Vec::new(),
+            evaluator: None,
+            pending: HashMap::new(),
+        })
+    }
+
+    /// Sets the evaluator that is used to determine the fitness of individuals. See [Evaluator].
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator<P>>) {
+        self.evaluator = Some(evaluator);
+    }
+
+    /// Adds a sink that the run log is written to. See [log_generation].
+    pub fn add_log_sink(&mut self, sink: Box<dyn LogSink>) {
+        self.log_sinks.push(sink);
+    }
+
+    /// Enables recording of the lineage of individuals. This makes it possible to trace how an
+    /// individual arose. It should be invoked before [start].
+    pub fn enable_genealogy(&mut self) {
+        self.genealogy = Some(Genealogy::new());
+    }
+
+    pub fn genealogy(&self) -> Option<&Genealogy> {
+        self.genealogy.as_ref()
+    }
+
+    /// Sets the hall of fame, which keeps the fittest individuals ever evaluated. See
+    /// [HallOfFame].
+    pub fn set_hall_of_fame(&mut self, hall_of_fame: HallOfFame<P, G>) {
+        self.hall_of_fame = Some(hall_of_fame);
+    }
+
+    pub fn hall_of_fame(&self) -> Option<&HallOfFame<P, G>> {
+        self.hall_of_fame.as_ref()
+    }
+
+    /// Enables measurement of the diversity of the population, which is then included in the
+    /// statistics. See [GenotypeDistance].
+    pub fn enable_diversity(&mut self) where G: GenotypeDistance {
+        self.diversity = Some(G::diversity);
+    }
+
+    /// Sets a local search that improves children after breeding, which turns the algorithm into
+    /// a memetic algorithm. Each child is improved with the given probability, which should be in
+    /// range [0, 1]. See [LearningMode] for how children inherit from the local search.
+    ///
+    /// The local search evaluates phenotypes itself, using [Phenotype::evaluate]. Improved
+    /// children therefore already have a fitness after breeding.
+    pub fn set_local_search(
+        &mut self, local_search: Box<dyn LocalSearch<P, G>>, mode: LearningMode, prob: f32
+    ) -> Result<(), EvolutionError> {
+        if !(0.0..=1.0).contains(&prob) {
+            return Err(EvolutionError::InvalidParameter("prob", "in range [0, 1]"));
         }
+
+        self.memetic = Some(MemeticConfig { local_search, mode, prob });
+        Ok(())
+    }
+
+    /// Sets what to do with evaluations that give an invalid fitness, such as NaN. By default,
+    /// such individuals are considered the least fit. See [InvalidFitnessPolicy].
+    pub fn set_invalid_fitness_policy(&mut self, policy: InvalidFitnessPolicy) {
+        self.invalid_fitness_policy = policy;
+    }
+
+    /// Enables handling of noisy fitness: each phenotype is evaluated several times, survivors
+    /// are sampled again, and the fitness is estimated from the samples. See [NoiseHandling].
+    ///
+    /// It only applies to [evaluate]. Fitness values reported with [tell] and by a local search
+    /// are used as they are.
+    pub fn set_noise_handling(&mut self, handling: NoiseHandling) where P::Fitness: MeanFitness {
+        self.noise = Some(NoiseConfig::new(handling));
+    }
+
+    /// The number of generations bred since the algorithm started.
+    pub fn generation(&self) -> usize {
+        self.generation
     }
 
     pub fn start(&mut self) {
@@ -163,90 +625,507 @@ impl<P: Phenotype, G: Genotype<P>> EvolutionaryAlgorithm<P, G> {
         population.populate(self.pop_size, &*(self.config));
 
         self.population = Some(population);
+        self.generation = 0;
+        self.evaluations = 0;
+        self.invalid_evaluations = 0;
+        self.pending.clear();
     }
 
     pub fn grow(&mut self) {
         if let Some(population) = &mut self.population {
             for indiv in population.iter_mut() {
-                if let None = indiv.phenotype {
-                    (*indiv).phenotype = Some(Box::new(indiv.genotype.express()));
+                if indiv.phenotype.is_none() {
+                    indiv.phenotype = Some(indiv.genotype.express());
                 }
             }
         }
     }
 
-    pub fn evaluate(&mut self) {
-        if let Some(population) = &mut self.population {
-            for indiv in population.iter_mut() {
-                if let Some(phenotype) = &indiv.phenotype {
-                    if let None = indiv.fitness {
-                        (*indiv).fitness = Some(phenotype.evaluate());
+    /// Evaluates the individuals that have a phenotype but no fitness yet. Evaluations that give
+    /// an invalid fitness are handled according to the policy, see
+    /// [set_invalid_fitness_policy]. When the policy rejects them, the other individuals are
+    /// still evaluated before the error is returned.
+    ///
+    /// When noise handling is enabled, each individual is evaluated several times, and survivors
+    /// of earlier generations are sampled again. See [set_noise_handling]. Only individuals
+    /// without any valid sample are handled according to the policy.
+    pub fn evaluate(&mut self) -> Result<(), EvolutionError> {
+        let population = match &mut self.population {
+            Some(population) => population,
+            None => return Ok(()),
+        };
+
+        if let Some(noise) = &self.noise {
+            let generation = self.generation;
+            let survivors: Vec<&mut Individual<P, G>> = population.iter_mut().filter(|indiv| {
+                indiv.phenotype.is_some()
+                    && indiv.samples.is_some_and(|samples| samples.generation < generation)
+            }).collect();
+            let num_samples = noise.handling.survivor_samples();
+            let phenotypes: Vec<&P> = survivors.iter().filter_map(
+                |indiv| indiv.phenotype.as_ref()
+            ).collect();
+            let samples = sample(self.evaluator.as_deref(), &phenotypes, num_samples)?;
+            self.evaluations += phenotypes.len() * num_samples;
+
+            for (indiv, values) in survivors.into_iter().zip(samples) {
+                if let Some(samples) = &mut indiv.samples {
+                    samples.generation = generation;
+                    self.invalid_evaluations += values.iter().filter(
+                        |value| !is_comparable(*value)
+                    ).count();
+                    if let Some(fitness) = noise.record(samples, &values, P::DIRECTION) {
+                        indiv.fitness = Some(fitness);
                     }
                 }
             }
         }
+
+        let mut pending: Vec<&mut Individual<P, G>> = population.iter_mut().filter(
+            |indiv| indiv.phenotype.is_some() && indiv.fitness.is_none()
+        ).collect();
+        let num_samples = self.noise.as_ref().map_or(1, |noise| noise.handling.samples());
+        let mut num_reevaluations = 0;
+        let mut rejected = None;
+
+        while !pending.is_empty() {
+            let phenotypes: Vec<&P> = pending.iter().filter_map(
+                |indiv| indiv.phenotype.as_ref()
+            ).collect();
+            let samples = sample(self.evaluator.as_deref(), &phenotypes, num_samples)?;
+            self.evaluations += phenotypes.len() * num_samples;
+
+            let mut invalid = Vec::new();
+            for (indiv, values) in pending.into_iter().zip(samples) {
+                let fitness = match &self.noise {
+                    Some(noise) => {
+                        let samples = indiv.samples.get_or_insert(
+                            FitnessSamples::new(self.generation)
+                        );
+                        self.invalid_evaluations += values.iter().filter(
+                            |value| !is_comparable(*value)
+                        ).count();
+                        match noise.record(samples, &values, P::DIRECTION) {
+                            Some(fitness) => fitness,
+                            // Every sample is invalid, so the policy decides
+                            None => values[0],
+                        }
+                    },
+                    None => {
+                        if !is_comparable(&values[0]) {
+                            self.invalid_evaluations += 1;
+                        }
+                        values[0]
+                    },
+                };
+
+                if !is_comparable(&fitness) {
+                    match self.invalid_fitness_policy {
+                        InvalidFitnessPolicy::Reject => {
+                            rejected = rejected.or(Some(indiv.id));
+                            continue;
+                        },
+                        InvalidFitnessPolicy::Reevaluate(max) if num_reevaluations < max => {
+                            invalid.push(indiv);
+                            continue;
+                        },
+                        _ => {},
+                    }
+                }
+
+                indiv.fitness = Some(fitness);
+                if let Some(genealogy) = &mut self.genealogy {
+                    genealogy.record(indiv);
+                }
+                if let Some(hall_of_fame) = &mut self.hall_of_fame {
+                    hall_of_fame.consider(indiv, self.generation);
+                }
+            }
+
+            pending = invalid;
+            num_reevaluations += 1;
+        }
+
+        match rejected {
+            Some(id) => Err(EvolutionError::InvalidFitness(id)),
+            None => Ok(()),
+        }
     }
 
     /// Breeds a new generation of individuals. Their parents are selected from the current
     /// generation based on their fitness. The individuals will have a genotype, but their
     /// phenotype and fitness have not yet been determined. For this, use [grow] and [evaluate].
-    pub fn breed(&mut self) {
-        let old_population = self.population.take();
-        let selector = (*self.selection).select_from(old_population.unwrap());
-        let mut population = Population::with_capacity(self.pop_size);
+    ///
+    /// When elitism is configured, the fittest individuals survive unchanged into the new
+    /// generation, retaining their fitness.
+    ///
+    /// When a local search is configured, it is applied to the children. See
+    /// [set_local_search]. When it gives an invalid fitness, the child is left to be evaluated
+    /// as usual.
+    ///
+    /// Breeding fails when the current generation contains individuals that were neither
+    /// evaluated nor handed out by [ask]. When breeding fails, the current generation is left
+    /// unchanged.
+    pub fn breed(&mut self) -> Result<(), EvolutionError> {
+        let old_population = self.population.as_ref().ok_or(EvolutionError::NoPopulation)?;
+        let num_unevaluated = old_population.iter().filter(
+            |indiv| indiv.fitness.is_none() && !self.pending.contains_key(&indiv.id)
+        ).count();
+        if num_unevaluated > 0 {
+            return Err(EvolutionError::Unevaluated(num_unevaluated));
+        }
+
+        let selector = self.selection.select_from(old_population);
+        let birth_generation = self.generation + 1;
+        let mut num_bred = 0;
+        let mut num_elites = 0;
+
+        // The new generation overwrites the individuals of the previous one, which re-uses their
+        // storage.
+        let mut offspring = self.offspring.take().unwrap_or_else(
+            || Population::with_capacity(self.pop_size)
+        );
+
+        if self.elitism > 0 {
+            let mut ranked: Vec<&Individual<P, G>> = old_population.iter().collect();
+            ranked.sort_by(|a, b| P::DIRECTION.compare_optional(&b.fitness, &a.fitness));
+
+            for elite in ranked.into_iter().take(self.elitism) {
+                offspring.slot(num_bred, &elite.genotype).copy_from(elite);
+                num_bred += 1;
+            }
+            num_elites = num_bred;
+        }
+
+        while num_bred < self.pop_size {
+            let recombine = random::rng().gen::<f32>() < self.recombination_prob;
+            let parent1 = selector.select();
+            let child = offspring.slot(num_bred, &parent1.genotype);
+
+            child.reset(birth_generation);
+            child.parents.push(parent1.id);
+            if recombine {
+                let parent2 = selector.select();
+                child.parents.push(parent2.id);
+                child.operators.push(Operator::Recombination);
+                self.config.recombine_into(
+                    &parent1.genotype, &parent2.genotype, &mut child.genotype
+                )?;
+            } else {
+                child.genotype.clone_from(&parent1.genotype);
+            }
+
+            if random::rng().gen::<f32>() < self.mutation_prob {
+                child.operators.push(Operator::Mutation);
+                self.config.mutate(&mut child.genotype)
+            }
+
+            num_bred += 1;
+        }
+        offspring.individuals.truncate(self.pop_size);
+
+        // Release the borrow of the old population before replacing it
+        drop(selector);
+        self.invalid_evaluations = 0;
 
-        while population.size() < self.pop_size {
-            let mut genotype = Box::new(
-                if rand::thread_rng().gen::<f32>() < self.recombination_prob {
-                    let parent1 = selector.select();
-                    let parent2 = selector.select();
-                    self.config.recombine(&parent1.genotype, &parent2.genotype)
-                } else {
-                    let parent = selector.select();
-                    (*parent.genotype).clone()
+        if let Some(memetic) = &self.memetic {
+            for child in offspring.individuals[num_elites..].iter_mut() {
+                if random::rng().gen::<f32>() >= memetic.prob {
+                    continue;
                 }
-            );
 
-            if rand::thread_rng().gen::<f32>() < self.mutation_prob {
-                self.config.mutate(&mut genotype)
+                let local_search = &memetic.local_search;
+                let improved = match memetic.mode {
+                    LearningMode::Lamarckian => local_search.improve(&mut child.genotype),
+                    LearningMode::Baldwinian => local_search.improve(&mut child.genotype.clone()),
+                };
+                child.operators.push(Operator::LocalSearch);
+                self.evaluations += improved.evaluations;
+                if !is_comparable(&improved.fitness) {
+                    self.invalid_evaluations += 1;
+                    continue;
+                }
+                child.fitness = Some(improved.fitness);
+
+                if let Some(genealogy) = &mut self.genealogy {
+                    genealogy.record(child);
+                }
+                if let Some(hall_of_fame) = &mut self.hall_of_fame {
+                    hall_of_fame.consider(child, birth_generation);
+                }
             }
+        }
 
-            population.add(Individual::new(genotype))
+        if let Some(genealogy) = &mut self.genealogy {
+            genealogy.prune(offspring.iter().flat_map(
+                |indiv| iter::once(indiv.id).chain(indiv.parents.iter().copied())
+            ));
         }
 
-        self.population = Some(population);
+        self.offspring = self.population.replace(offspring);
+        self.generation += 1;
+        self.pending.clear();
+
+        Ok(())
     }
 
-    pub fn get_stats(&self) -> Option<Stats> {
-        if let Some(population) = &self.population {
-            let mut max: Option<f32> = None;
-            let mut sum: f32 = 0f32;
-            let mut num: usize = 0;
-
-            for individual in population.individuals.iter() {
-                if let Some(fitness) = individual.fitness {
-                    sum += fitness;
-                    num += 1;
-                    max = Some(
-                        match max {
-                            None => fitness,
-                            Some(current_max) => current_max.max(fitness)
-                        }
-                    )
-                }
+    /// Hands out the individuals in the current generation that still need to be evaluated. This,
+    /// together with [tell], is an alternative to [evaluate] for when the evaluation is driven
+    /// externally. For example, by an event loop, a batch job or by a human.
+    ///
+    /// The phenotype of each individual is available via [phenotype]. Individuals are only
+    /// handed out once, so subsequent invocations only return new individuals (if any).
+    /// Individuals can be evaluated in any order. It is not required to evaluate all of them
+    /// before breeding the next generation. Unevaluated individuals are then considered less fit
+    /// than any evaluated individual. They can still be selected, for example by a tournament in
+    /// which no individual was evaluated.
+    pub fn ask(&mut self) -> Result<Vec<Handle>, EvolutionError> {
+        self.grow();
+
+        let population = self.population.as_ref().ok_or(EvolutionError::NoPopulation)?;
+        let mut handles = Vec::new();
+        for (i, indiv) in population.iter().enumerate() {
+            if indiv.fitness.is_none() && !self.pending.contains_key(&indiv.id) {
+                self.pending.insert(indiv.id, i);
+                handles.push(Handle(indiv.id));
             }
+        }
 
-            if let Some(max_fitness) = max {
-                let avg_fitness = sum / (num as f32);
-                Some(Stats { max_fitness, avg_fitness })
-            } else {
-                None
+        Ok(handles)
+    }
+
+    /// Returns the phenotype of an individual that was handed out by [ask].
+    pub fn phenotype(&self, handle: Handle) -> Option<&P> {
+        let i = self.pending.get(&handle.0)?;
+
+        self.population.as_ref()?.individuals[*i].phenotype.as_ref()
+    }
+
+    /// Records the fitness of an individual that was handed out by [ask]. When the fitness is
+    /// invalid and the policy rejects it or asks for re-evaluation, it fails, and the individual
+    /// remains awaiting evaluation. See [set_invalid_fitness_policy].
+    pub fn tell(&mut self, handle: Handle, fitness: P::Fitness) -> Result<(), EvolutionError> {
+        let population = self.population.as_mut().ok_or(EvolutionError::NoPopulation)?;
+        let i = *self.pending.get(&handle.0).ok_or(EvolutionError::UnknownHandle(handle))?;
+        let indiv = &mut population.individuals[i];
+
+        self.evaluations += 1;
+        if !is_comparable(&fitness) {
+            self.invalid_evaluations += 1;
+            if self.invalid_fitness_policy != InvalidFitnessPolicy::Worst {
+                return Err(EvolutionError::InvalidFitness(handle.0));
+            }
+        }
+
+        self.pending.remove(&handle.0);
+        indiv.fitness = Some(fitness);
+        if let Some(genealogy) = &mut self.genealogy {
+            genealogy.record(indiv);
+        }
+        if let Some(hall_of_fame) = &mut self.hall_of_fame {
+            hall_of_fame.consider(indiv, self.generation);
+        }
+
+        Ok(())
+    }
+
+    /// The number of individuals that were handed out by [ask] but whose fitness is not yet
+    /// known.
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the fittest evaluated individual in the current population.
+    pub fn best_individual(&self) -> Option<&Individual<P, G>> {
+        self.population.as_ref()?.iter().filter(
+            |indiv| indiv.fitness.is_some()
+        ).fold(None, |best: Option<&Individual<P, G>>, indiv| {
+            match best {
+                Some(best) if P::DIRECTION.compare_optional(
+                    &best.fitness, &indiv.fitness
+                ) != cmp::Ordering::Less => Some(best),
+                _ => Some(indiv),
+            }
+        })
+    }
+
+    /// Exports the lineage graph of the fittest individual in the current population. It
+    /// requires that genealogy tracking was enabled, see [enable_genealogy].
+    pub fn export_best_lineage(&self, format: LineageFormat) -> Option<String> {
+        let genealogy = self.genealogy.as_ref()?;
+        let best = self.best_individual()?;
+
+        Some(genealogy.export(best.id, format))
+    }
+
+    /// Writes the statistics of the current generation to all log sinks. Sinks that requested
+    /// population snapshots also receive the population when it is due. It should be invoked
+    /// after [evaluate].
+    pub fn log_generation(&mut self) -> Result<(), EvolutionError> {
+        if self.log_sinks.is_empty() {
+            return Ok(());
+        }
+
+        let generation = self.generation;
+        if let Some(stats) = self.get_stats() {
+            for sink in self.log_sinks.iter_mut() {
+                sink.log_generation(generation, &stats)?;
+            }
+        }
+
+        let wants_snapshot = |sink: &dyn LogSink| {
+            sink.snapshot_interval().is_some_and(|interval| generation.is_multiple_of(interval))
+        };
+        if let (Some(population), true) = (
+            &self.population, self.log_sinks.iter().any(|sink| wants_snapshot(sink.as_ref()))
+        ) {
+            let snapshot: Vec<IndividualSnapshot> = population.iter().map(
+                |indiv| IndividualSnapshot {
+                    id: indiv.id,
+                    fitness: indiv.fitness.map(Fitness::to_f64),
+                    genotype: format!("{:?}", indiv.genotype),
+                }
+            ).collect();
+
+            for sink in self.log_sinks.iter_mut().filter(|sink| wants_snapshot(sink.as_ref())) {
+                sink.log_population(generation, &snapshot)?;
             }
+        }
+
+        for sink in self.log_sinks.iter_mut() {
+            sink.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Summarises the run so far. It includes the hall of fame, when there is one.
+    pub fn summary(&self) -> RunSummary {
+        let hall_of_fame = match &self.hall_of_fame {
+            Some(hall_of_fame) => hall_of_fame.entries().iter().map(
+                |entry| HallOfFameSnapshot {
+                    id: entry.id,
+                    generation: entry.generation,
+                    fitness: entry.fitness.to_f64(),
+                    genotype: format!("{:?}", entry.genotype),
+                    phenotype: format!("{:?}", entry.phenotype),
+                }
+            ).collect(),
+            None => Vec::new(),
+        };
+
+        RunSummary {
+            generations: self.generation,
+            stats: self.get_stats(),
+            hall_of_fame,
+        }
+    }
+
+    /// Writes the summary of the run to all log sinks. It should be invoked when the run has
+    /// finished.
+    pub fn log_summary(&mut self) -> Result<(), EvolutionError> {
+        let summary = self.summary();
+
+        for sink in self.log_sinks.iter_mut() {
+            sink.log_summary(&summary)?;
+            sink.flush()?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_stats(&self) -> Option<Stats> {
+        let population = self.population.as_ref()?;
+        let genotypes: Vec<&G> = population.iter().map(|indiv| &indiv.genotype).collect();
+
+        let stats = Stats::from_generation(
+            population.iter().map(|indiv| indiv.fitness), &genotypes, self.diversity
+        )?;
+
+        // Elites were not evaluated in this generation, so only count actual evaluations
+        Some(Stats { invalid_evaluations: self.invalid_evaluations, ..stats })
+    }
+}
+
+impl<P: Phenotype, G: Genotype<P>> Optimiser for EvolutionaryAlgorithm<P, G> {
+    /// Starts the algorithm when it has not yet started, and breeds a new generation otherwise.
+    /// The generation is then grown, evaluated and logged.
+    fn step(&mut self) -> Result<Stats, EvolutionError> {
+        if self.population.is_none() {
+            self.start();
         } else {
-            None
+            self.breed()?;
         }
+
+        self.grow();
+        self.evaluate()?;
+        self.log_generation()?;
+
+        self.get_stats().ok_or(EvolutionError::NoPopulation)
+    }
+
+    fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+}
+
+/// Evaluates the phenotypes, using the evaluator when there is one. Fails when the evaluator does
+/// not return one fitness per phenotype.
+pub(crate) fn evaluate_all<P: Phenotype>(
+    evaluator: Option<&dyn Evaluator<P>>, phenotypes: &[&P]
+) -> Result<Vec<P::Fitness>, EvolutionError> {
+    let fitnesses: Vec<P::Fitness> = match evaluator {
+        Some(evaluator) => evaluator.evaluate(phenotypes),
+        None => phenotypes.iter().map(|phenotype| phenotype.evaluate()).collect(),
+    };
+    if fitnesses.len() != phenotypes.len() {
+        return Err(EvolutionError::LengthMismatch(fitnesses.len(), phenotypes.len()));
     }
+
+    Ok(fitnesses)
+}
+
+/// Evaluates each phenotype the given number of times. Returns the fitness samples of each
+/// phenotype.
+fn sample<P: Phenotype>(
+    evaluator: Option<&dyn Evaluator<P>>, phenotypes: &[&P], num_samples: usize
+) -> Result<Vec<Vec<P::Fitness>>, EvolutionError> {
+    let mut samples: Vec<Vec<P::Fitness>> = phenotypes.iter().map(
+        |_| Vec::with_capacity(num_samples)
+    ).collect();
+
+    for _ in 0..num_samples {
+        let fitnesses = evaluate_all(evaluator, phenotypes)?;
+        for (samples, fitness) in samples.iter_mut().zip(fitnesses) {
+            samples.push(fitness);
+        }
+    }
+
+    Ok(samples)
 }
 
 pub mod selection;
-pub mod binary;
\ No newline at end of file
+pub mod binary;
+pub mod genealogy;
+pub mod logging;
+pub mod external;
+pub mod distributed;
+pub mod composite;
+pub mod builder;
+pub mod hall_of_fame;
+pub mod diversity;
+pub mod memetic;
+pub mod permutation;
+pub mod real;
+pub mod optimiser;
+pub mod eda;
+pub mod trajectory;
+pub mod random;
+pub mod experiment;
+pub mod fitness;
+pub mod grammar;
+pub mod neuro;
+pub mod cgp;
+pub mod novelty;
+pub mod map_elites;
+pub mod noise;
@@ -0,0 +1,383 @@
+use super::Stats;
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{self, Write};
+
+/// A snapshot of a single individual, as written to a population log.
+#[derive(Debug, Clone)]
+pub struct IndividualSnapshot {
+    pub id: u64,
+    /// The fitness as a number. See [Fitness::to_f64](crate::fitness::Fitness::to_f64).
+    pub fitness: Option<f64>,
+    /// The genotype, formatted using its Debug representation.
+    pub genotype: String,
+}
+
+/// A snapshot of an individual in the hall of fame, as written to a run summary.
+#[derive(Debug, Clone)]
+pub struct HallOfFameSnapshot {
+    pub id: u64,
+    /// The generation in which the individual was evaluated.
+    pub generation: usize,
+    /// The fitness as a number. See [Fitness::to_f64](crate::fitness::Fitness::to_f64).
+    pub fitness: f64,
+    /// The genotype, formatted using its Debug representation.
+    pub genotype: String,
+    /// The phenotype, formatted using its Debug representation.
+    pub phenotype: String,
+}
+
+/// A summary of a run, to be written when it has finished.
+#[derive(Debug)]
+pub struct RunSummary {
+    /// The number of generations bred.
+    pub generations: usize,
+    /// The statistics of the final generation.
+    pub stats: Option<Stats>,
+    /// The hall of fame, fittest first. It is empty when the algorithm does not keep one.
+    pub hall_of_fame: Vec<HallOfFameSnapshot>,
+}
+
+/// A destination for the run log of an evolutionary algorithm.
+pub trait LogSink: fmt::Debug {
+    /// Logs the statistics of the given generation.
+    fn log_generation(&mut self, generation: usize, stats: &Stats) -> io::Result<()>;
+
+    /// How often the complete population should be logged. When it returns `Some(n)`, the
+    /// population is logged every `n` generations, starting with the initial one.
+    fn snapshot_interval(&self) -> Option<usize> {
+        None
+    }
+
+    /// Logs all individuals in the population of the given generation.
+    fn log_population(
+        &mut self, _generation: usize, _individuals: &[IndividualSnapshot]
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Logs the probability vector that the given generation was sampled from, for algorithms
+    /// that keep one. See [crate::eda].
+    fn log_probabilities(&mut self, _generation: usize, _probabilities: &[f32]) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Logs the summary of a run.
+    fn log_summary(&mut self, _summary: &RunSummary) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+/// Writes statistics as Comma Separated Values, one row per generation, preceded by a header.
+/// Population snapshots and run summaries have a different layout, so they are written to separate
+/// writers.
+///
+/// Columns are never removed or re-ordered. New columns are only ever added at the end. The
+/// `max_fitness` column predates optimisation directions and holds the same value as
+/// `best_fitness`.
+/// Probability vectors are not written.
+pub struct CsvSink<W: Write> {
+    stats_out: W,
+    snapshot_out: Option<(W, usize)>,
+    summary_out: Option<W>,
+    stats_header_written: bool,
+    snapshot_header_written: bool,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(stats_out: W) -> Self {
+        CsvSink {
+            stats_out,
+            snapshot_out: None,
+            summary_out: None,
+            stats_header_written: false,
+            snapshot_header_written: false,
+        }
+    }
+
+    /// Also writes the population to the given writer, every `interval` generations. Each row
+    /// contains the generation, id, fitness and genotype of one individual.
+    pub fn with_snapshots(mut self, snapshot_out: W, interval: usize) -> Self {
+        self.snapshot_out = Some((snapshot_out, interval.max(1)));
+        self
+    }
+
+    /// Also writes the hall of fame of the run summary to the given writer. Each row contains the
+    /// rank, id, generation, fitness, genotype and phenotype of one individual.
+    pub fn with_summary(mut self, summary_out: W) -> Self {
+        self.summary_out = Some(summary_out);
+        self
+    }
+}
+
+impl<W: Write> fmt::Debug for CsvSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CsvSink")
+    }
+}
+
+impl<W: Write> LogSink for CsvSink<W> {
+    fn log_generation(&mut self, generation: usize, stats: &Stats) -> io::Result<()> {
+        if !self.stats_header_written {
+            writeln!(
+                self.stats_out,
+                "generation,max_fitness,avg_fitness,min_length,avg_length,max_length,\
+                mean_distance,mean_entropy,converged_loci,invalid_evaluations,best_fitness"
+            )?;
+            self.stats_header_written = true;
+        }
+
+        let length = stats.genome_length;
+        let diversity = stats.diversity.as_ref();
+        let loci = diversity.and_then(|diversity| diversity.loci.as_ref());
+        writeln!(
+            self.stats_out, "{},{},{},{},{},{},{},{},{},{},{}",
+            generation, stats.best_fitness, stats.avg_fitness,
+            optional_value(length.map(|l| l.min), ""),
+            optional_value(length.map(|l| l.avg), ""),
+            optional_value(length.map(|l| l.max), ""),
+            optional_value(diversity.map(|d| d.mean_distance), ""),
+            optional_value(loci.map(|l| l.mean_entropy), ""),
+            optional_value(loci.map(|l| l.converged_loci), ""),
+            stats.invalid_evaluations, stats.best_fitness
+        )
+    }
+
+    fn snapshot_interval(&self) -> Option<usize> {
+        self.snapshot_out.as_ref().map(|(_, interval)| *interval)
+    }
+
+    fn log_population(
+        &mut self, generation: usize, individuals: &[IndividualSnapshot]
+    ) -> io::Result<()> {
+        if let Some((out, _)) = &mut self.snapshot_out {
+            if !self.snapshot_header_written {
+                writeln!(out, "generation,id,fitness,genotype")?;
+                self.snapshot_header_written = true;
+            }
+
+            for individual in individuals.iter() {
+                writeln!(
+                    out, "{},{},{},{}",
+                    generation, individual.id, optional_value(individual.fitness, ""),
+                    csv_string(&individual.genotype)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn log_summary(&mut self, summary: &RunSummary) -> io::Result<()> {
+        if let Some(out) = &mut self.summary_out {
+            writeln!(out, "rank,id,generation,fitness,genotype,phenotype")?;
+            for (i, entry) in summary.hall_of_fame.iter().enumerate() {
+                writeln!(
+                    out, "{},{},{},{},{},{}",
+                    i + 1, entry.id, entry.generation, entry.fitness,
+                    csv_string(&entry.genotype), csv_string(&entry.phenotype)
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some((out, _)) = &mut self.snapshot_out {
+            out.flush()?;
+        }
+        if let Some(out) = &mut self.summary_out {
+            out.flush()?;
+        }
+        self.stats_out.flush()
+    }
+}
+
+/// Writes one JSON object per line. Each object has a "type" field, which is "generation" for
+/// statistics, "population" for population snapshots, "probabilities" for probability vectors
+/// and "summary" for the run summary. As with [CsvSink], "max_fitness" holds the same value as
+/// "best_fitness".
+pub struct JsonLinesSink<W: Write> {
+    out: W,
+    snapshot_interval: Option<usize>,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(out: W) -> Self {
+        JsonLinesSink {
+            out,
+            snapshot_interval: None,
+        }
+    }
+
+    /// Also writes the population every `interval` generations.
+    pub fn with_snapshots(mut self, interval: usize) -> Self {
+        self.snapshot_interval = Some(interval.max(1));
+        self
+    }
+}
+
+impl<W: Write> fmt::Debug for JsonLinesSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JsonLinesSink")
+    }
+}
+
+impl<W: Write> LogSink for JsonLinesSink<W> {
+    fn log_generation(&mut self, generation: usize, stats: &Stats) -> io::Result<()> {
+        writeln!(
+            self.out, "{{\"type\":\"generation\",\"generation\":{},{}}}",
+            generation, json_stats_fields(stats)
+        )
+    }
+
+    fn snapshot_interval(&self) -> Option<usize> {
+        self.snapshot_interval
+    }
+
+    fn log_population(
+        &mut self, generation: usize, individuals: &[IndividualSnapshot]
+    ) -> io::Result<()> {
+        let mut line = String::new();
+
+        write!(line, "{{\"type\":\"population\",\"generation\":{},\"individuals\":[", generation)
+            .unwrap();
+        for (i, individual) in individuals.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            write!(
+                line, "{{\"id\":{},\"fitness\":{},\"genotype\":{}}}",
+                individual.id, optional_value(individual.fitness.map(json_number), "null"),
+                json_string(&individual.genotype)
+            ).unwrap();
+        }
+        line.push_str("]}");
+
+        writeln!(self.out, "{}", line)
+    }
+
+    fn log_probabilities(&mut self, generation: usize, probabilities: &[f32]) -> io::Result<()> {
+        writeln!(
+            self.out, "{{\"type\":\"probabilities\",\"generation\":{},\"probabilities\":{}}}",
+            generation, json_array(probabilities)
+        )
+    }
+
+    fn log_summary(&mut self, summary: &RunSummary) -> io::Result<()> {
+        let mut line = String::new();
+
+        write!(
+            line, "{{\"type\":\"summary\",\"generations\":{},\"stats\":{},\"hall_of_fame\":[",
+            summary.generations,
+            optional_value(summary.stats.as_ref().map(|stats| {
+                format!("{{{}}}", json_stats_fields(stats))
+            }), "null")
+        ).unwrap();
+        for (i, entry) in summary.hall_of_fame.iter().enumerate() {
+            if i > 0 {
+                line.push(',');
+            }
+            write!(
+                line, "{{\"id\":{},\"generation\":{},\"fitness\":{},\"genotype\":{},\
+                \"phenotype\":{}}}",
+                entry.id, entry.generation, json_number(entry.fitness),
+                json_string(&entry.genotype), json_string(&entry.phenotype)
+            ).unwrap();
+        }
+        line.push_str("]}");
+
+        writeln!(self.out, "{}", line)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Formats the statistics as the fields of a JSON object, without the surrounding braces.
+fn json_stats_fields(stats: &Stats) -> String {
+    let genome_length = match stats.genome_length {
+        Some(length) => format!(
+            "{{\"min\":{},\"avg\":{},\"max\":{}}}",
+            length.min, json_number(length.avg), length.max
+        ),
+        None => String::from("null"),
+    };
+
+    let diversity = match &stats.diversity {
+        Some(diversity) => format!(
+            "{{\"mean_distance\":{},\"loci\":{}}}",
+            json_number(diversity.mean_distance),
+            optional_value(diversity.loci.as_ref().map(|loci| format!(
+                "{{\"mean_entropy\":{},\"converged_loci\":{},\"allele_frequencies\":{},\
+                \"entropy\":{}}}",
+                json_number(loci.mean_entropy), loci.converged_loci,
+                json_array(&loci.allele_frequencies), json_array(&loci.entropy)
+            )), "null")
+        ),
+        None => String::from("null"),
+    };
+
+    format!(
+        "\"max_fitness\":{},\"avg_fitness\":{},\"genome_length\":{},\"diversity\":{},\
+        \"invalid_evaluations\":{},\"best_fitness\":{}",
+        json_number(stats.best_fitness), json_number(stats.avg_fitness), genome_length, diversity,
+        stats.invalid_evaluations, json_number(stats.best_fitness)
+    )
+}
+
+fn optional_value<T: fmt::Display>(value: Option<T>, missing: &str) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => String::from(missing),
+    }
+}
+
+/// Formats a number for use in JSON, which does not support NaN and infinity.
+pub(crate) fn json_number<T: Copy + Into<f64> + fmt::Display>(value: T) -> String {
+    if value.into().is_finite() {
+        value.to_string()
+    } else {
+        String::from("null")
+    }
+}
+
+/// Formats numbers as a JSON array.
+fn json_array(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|value| json_number(*value)).collect();
+
+    format!("[{}]", values.join(","))
+}
+
+/// Formats a string as a quoted JSON string.
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+
+    out
+}
+
+/// Formats a string as a CSV field, quoting it when needed.
+pub(crate) fn csv_string(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
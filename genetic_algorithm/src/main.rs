@@ -1,11 +1,54 @@
 use evolutionary_alg::{
     Genotype, Phenotype, GenotypeFactory, GenotypeManipulation, GenotypeConfig, 
-    Mutation, Recombination, EvolutionaryAlgorithm
+    Mutation, Recombination, EvolutionaryAlgorithm, EvolutionError, SelectionFactory, Evaluator
 };
-use evolutionary_alg::binary::{BinaryChromosome, BinaryBitMutation, BinaryNPointBitCrossover};
+use evolutionary_alg::binary::{
+    count_ones, BinaryChromosome, BinaryBitMutation, BinaryNPointBitCrossover,
+    BinaryUniformRecombination, BinaryBitFlipHillClimbing,
+    BinaryInsertionMutation, BinaryDeletionMutation, BinaryDuplicationMutation,
+    BinaryCutAndSpliceCrossover, LengthLimits
+};
+use evolutionary_alg::composite::{CompositeMutation, CompositeRecombination};
+use evolutionary_alg::builder::WeightedMutation;
 use evolutionary_alg::selection::RankBasedSelection;
+use evolutionary_alg::genealogy::LineageFormat;
+use evolutionary_alg::hall_of_fame::HallOfFame;
+use evolutionary_alg::memetic::{Improvement, LearningMode, LocalSearch};
+use evolutionary_alg::permutation::{
+    PermutationChromosome, PermutationOrderCrossover, PermutationSwapMutation, PermutationTwoOpt
+};
+use evolutionary_alg::real::{
+    RealArithmeticCrossover, RealChromosome, RealCoordinateSearch, RealGaussianMutation
+};
+use evolutionary_alg::eda::{CompactGa, Eda, Pbil, Umda};
+use evolutionary_alg::optimiser::{Optimiser, Termination, TerminationReason};
+use evolutionary_alg::experiment::Experiment;
+use evolutionary_alg::fitness::{Direction, InvalidFitnessPolicy};
+use evolutionary_alg::random;
+use evolutionary_alg::cgp::{self, CgpChromosome, CgpLayout, CgpPointMutation, CgpProgram};
+use evolutionary_alg::grammar::{
+    CodonChromosome, CodonCutAndSpliceCrossover, CodonMutation, Derivation, Grammar, Mapper, Program
+};
+use evolutionary_alg::map_elites::{FeatureRange, GridArchive, MapElites};
+use evolutionary_alg::noise::{Estimate, NoiseHandling};
+use evolutionary_alg::novelty::{Behaviour, NoveltySelection};
+use evolutionary_alg::neuro::{
+    Activation, FeedForwardNetwork, Neat, NeatConfig, NeatGenome, NeatNetwork, Topology
+};
+use evolutionary_alg::trajectory::{
+    CoolingSchedule, HillClimbing, MoveStrategy, SimulatedAnnealing, TabuSearch, TrajectorySearch
+};
+use evolutionary_alg::diversity::{mean_pairwise_distance, Diversity, GenotypeDistance};
+use evolutionary_alg::logging::{CsvSink, JsonLinesSink};
+use evolutionary_alg::external::{LineEncoding, WorkerPool};
+use evolutionary_alg::distributed::{Coordinator, Heartbeat, Worker};
 use bit_vec::BitVec;
-use std::{fmt};
+use rand::Rng;
+use std::io::Read;
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, io, thread};
 
 #[derive(Debug)]
 struct MaxOnesPhenotype {
@@ -13,9 +56,11 @@ struct MaxOnesPhenotype {
 }
 
 impl Phenotype for MaxOnesPhenotype {
+    type Fitness = f32;
+
     fn evaluate(&self) -> f32 {
         // Count the number of ones
-        self.bits.iter().filter(|x| *x).count() as f32 / self.bits.len() as f32
+        count_ones(&self.bits) as f32 / self.bits.len() as f32
     }
 }
 
@@ -31,12 +76,22 @@ impl fmt::Display for MaxOnesPhenotype {
     }
 }
 
+impl LineEncoding for MaxOnesPhenotype {
+    fn encode(&self) -> String {
+        self.to_string()
+    }
+}
+
 impl Genotype<MaxOnesPhenotype> for BinaryChromosome {
     fn express(&self) -> MaxOnesPhenotype {
         MaxOnesPhenotype {
             bits: self.bits.clone()
         }
     }
+
+    fn genome_length(&self) -> Option<usize> {
+        Some(self.bits.len())
+    }
 } 
 
 #[derive(Debug)]
@@ -46,11 +101,11 @@ struct MaxOnesConfig {
 }
 
 impl MaxOnesConfig {
-    fn new() -> Self {
-        MaxOnesConfig {
-            mutation: BinaryBitMutation::new(0.02),
+    fn new() -> Result<Self, EvolutionError> {
+        Ok(MaxOnesConfig {
+            mutation: BinaryBitMutation::new(0.02)?,
             recombination: BinaryNPointBitCrossover::new(2)
-        }
+        })
     }
 }
 
@@ -65,13 +120,457 @@ impl GenotypeManipulation<MaxOnesPhenotype, BinaryChromosome> for MaxOnesConfig
         self.mutation.mutate(target);
     }
 
-    fn recombine(&self, parent1: &BinaryChromosome, parent2: &BinaryChromosome) -> BinaryChromosome {
+    fn recombine(
+        &self, parent1: &BinaryChromosome, parent2: &BinaryChromosome
+    ) -> Result<BinaryChromosome, EvolutionError> {
         self.recombination.recombine(parent1, parent2)
     }
 }
 
 impl GenotypeConfig<MaxOnesPhenotype, BinaryChromosome> for MaxOnesConfig {}
 
+/// A round trip along cities that lie on a circle. Visiting them in order gives the shortest trip.
+#[derive(Debug)]
+struct CircleTourPhenotype {
+    order: Vec<usize>,
+}
+
+impl Phenotype for CircleTourPhenotype {
+    type Fitness = f32;
+
+    // Shorter trips are fitter
+    const DIRECTION: Direction = Direction::Minimise;
+
+    fn evaluate(&self) -> f32 {
+        let num = self.order.len();
+        let position = |city: usize| {
+            let angle = 2.0 * std::f32::consts::PI * city as f32 / num as f32;
+            (angle.cos(), angle.sin())
+        };
+
+        (0..num).map(|i| {
+            let (x1, y1) = position(self.order[i]);
+            let (x2, y2) = position(self.order[(i + 1) % num]);
+            ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt()
+        }).sum::<f32>()
+    }
+}
+
+impl Genotype<CircleTourPhenotype> for PermutationChromosome {
+    fn express(&self) -> CircleTourPhenotype {
+        CircleTourPhenotype {
+            order: self.order.clone()
+        }
+    }
+}
+
+/// The sphere function, whose optimum is at the origin.
+#[derive(Debug)]
+struct SpherePhenotype {
+    values: Vec<f32>,
+}
+
+impl Phenotype for SpherePhenotype {
+    type Fitness = f64;
+    const DIRECTION: Direction = Direction::Minimise;
+
+    fn evaluate(&self) -> f64 {
+        self.values.iter().map(|value| (*value as f64).powi(2)).sum()
+    }
+}
+
+/// Prefers chromosomes that start with a long run of ones. Ties are broken by the total number of
+/// ones, which makes the fitness a lexicographic tuple.
+#[derive(Debug)]
+struct LeadingOnesPhenotype {
+    bits: BitVec,
+}
+
+impl Phenotype for LeadingOnesPhenotype {
+    type Fitness = (usize, usize);
+
+    fn evaluate(&self) -> (usize, usize) {
+        (self.bits.iter().take_while(|bit| *bit).count(), count_ones(&self.bits))
+    }
+}
+
+impl Genotype<LeadingOnesPhenotype> for BinaryChromosome {
+    fn express(&self) -> LeadingOnesPhenotype {
+        LeadingOnesPhenotype {
+            bits: self.bits.clone()
+        }
+    }
+}
+
+/// Like [MaxOnesPhenotype], but its evaluation sometimes fails and gives NaN.
+#[derive(Debug)]
+struct UnreliablePhenotype {
+    bits: BitVec,
+}
+
+impl Phenotype for UnreliablePhenotype {
+    type Fitness = f32;
+
+    fn evaluate(&self) -> f32 {
+        if random::rng().gen::<f32>() < 0.2 {
+            f32::NAN
+        } else {
+            count_ones(&self.bits) as f32 / self.bits.len() as f32
+        }
+    }
+}
+
+/// Evaluates [MaxOnesPhenotype], but gives NaN to the phenotype without any ones.
+#[derive(Debug)]
+struct ZeroIsInvalid;
+
+impl Evaluator<MaxOnesPhenotype> for ZeroIsInvalid {
+    fn evaluate(&self, phenotypes: &[&MaxOnesPhenotype]) -> Vec<f32> {
+        phenotypes.iter().map(|phenotype| match phenotype.evaluate() {
+            fitness if fitness > 0.0 => fitness,
+            _ => f32::NAN,
+        }).collect()
+    }
+}
+
+impl Genotype<UnreliablePhenotype> for BinaryChromosome {
+    fn express(&self) -> UnreliablePhenotype {
+        UnreliablePhenotype {
+            bits: self.bits.clone()
+        }
+    }
+}
+
+impl Genotype<SpherePhenotype> for RealChromosome {
+    fn express(&self) -> SpherePhenotype {
+        SpherePhenotype {
+            values: self.values.clone()
+        }
+    }
+}
+
+/// An arithmetic expression in `x`, which should approximate `x^2 + x + 1`. Its fitness is the
+/// sum of the absolute errors at a number of points.
+#[derive(Debug)]
+struct RegressionPhenotype {
+    program: Option<Program>,
+}
+
+fn evaluate_expression(tree: &Derivation, x: f64) -> f64 {
+    match tree {
+        Derivation::Terminal(text) if text == "x" => x,
+        Derivation::Terminal(text) => text.parse().unwrap_or(f64::NAN),
+        Derivation::NonTerminal { children, .. } => match children.as_slice() {
+            [child] => evaluate_expression(child, x),
+            [_, a, Derivation::Terminal(op), b, _] => {
+                let (a, b) = (evaluate_expression(a, x), evaluate_expression(b, x));
+                match op.as_str() {
+                    " + " => a + b,
+                    " - " => a - b,
+                    _ => a * b,
+                }
+            },
+            _ => f64::NAN,
+        },
+    }
+}
+
+impl Phenotype for RegressionPhenotype {
+    type Fitness = f64;
+    const DIRECTION: Direction = Direction::Minimise;
+
+    fn evaluate(&self) -> f64 {
+        // Invalid individuals get NaN, which the evolutionary algorithm treats as the worst fitness
+        let program = match &self.program {
+            Some(program) => program,
+            None => return f64::NAN,
+        };
+
+        (-10..=10).map(|i| {
+            let x = i as f64 / 5.0;
+            (evaluate_expression(&program.tree, x) - (x * x + x + 1.0)).abs()
+        }).sum()
+    }
+}
+
+impl Genotype<RegressionPhenotype> for CodonChromosome {
+    fn express(&self) -> RegressionPhenotype {
+        RegressionPhenotype {
+            program: self.map()
+        }
+    }
+
+    fn genome_length(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+/// The sum of the squared errors of a network that should compute the exclusive or.
+fn xor_error(activate: impl Fn(&[f32]) -> f32) -> f64 {
+    [([0.0, 0.0], 0.0), ([0.0, 1.0], 1.0), ([1.0, 0.0], 1.0), ([1.0, 1.0], 0.0)].iter().map(
+        |(inputs, expected)| (activate(inputs) as f64 - expected).powi(2)
+    ).sum()
+}
+
+fn xor_topology() -> Topology {
+    Topology::new(&[2, 3, 1], Activation::Tanh, Activation::Sigmoid).unwrap()
+}
+
+/// A network with a fixed topology that should compute the exclusive or.
+#[derive(Debug)]
+struct XorNetworkPhenotype {
+    network: FeedForwardNetwork,
+}
+
+impl Phenotype for XorNetworkPhenotype {
+    type Fitness = f64;
+    const DIRECTION: Direction = Direction::Minimise;
+
+    fn evaluate(&self) -> f64 {
+        xor_error(|inputs| self.network.activate(inputs)[0])
+    }
+}
+
+impl Genotype<XorNetworkPhenotype> for RealChromosome {
+    fn express(&self) -> XorNetworkPhenotype {
+        XorNetworkPhenotype {
+            network: FeedForwardNetwork::new(xor_topology(), &self.values).unwrap()
+        }
+    }
+}
+
+/// A NEAT network that should compute the exclusive or.
+#[derive(Debug)]
+struct XorNeatPhenotype {
+    network: NeatNetwork,
+}
+
+impl Phenotype for XorNeatPhenotype {
+    type Fitness = f64;
+    const DIRECTION: Direction = Direction::Minimise;
+
+    fn evaluate(&self) -> f64 {
+        xor_error(|inputs| self.network.activate(inputs)[0])
+    }
+}
+
+impl Genotype<XorNeatPhenotype> for NeatGenome {
+    fn express(&self) -> XorNeatPhenotype {
+        XorNeatPhenotype {
+            network: self.network(Activation::SteepenedSigmoid)
+        }
+    }
+}
+
+/// The number of time steps that a pole on a cart is simulated for.
+const CART_POLE_STEPS: usize = 1000;
+
+/// Balances a pole that is hinged on a cart, by pushing the cart left or right. The controller
+/// gets the position and velocity of the cart, and the angle and angular velocity of the pole,
+/// all roughly scaled to range [-1, 1]. When its output exceeds 0.5 the cart is pushed right,
+/// otherwise left. Returns the number of steps until the pole falls or the cart leaves the track.
+fn balance_pole(initial_angle: f32, controller: impl Fn(&[f32]) -> f32) -> usize {
+    const GRAVITY: f32 = 9.8;
+    const CART_MASS: f32 = 1.0;
+    const POLE_MASS: f32 = 0.1;
+    const HALF_POLE_LENGTH: f32 = 0.5;
+    const FORCE: f32 = 10.0;
+    const TIME_STEP: f32 = 0.02;
+    const MAX_POSITION: f32 = 2.4;
+    const MAX_ANGLE: f32 = 0.21;
+
+    let total_mass = CART_MASS + POLE_MASS;
+    let (mut x, mut velocity) = (0.0f32, 0.0f32);
+    let (mut angle, mut angular_velocity) = (initial_angle, 0.0f32);
+
+    for step in 0..CART_POLE_STEPS {
+        if x.abs() > MAX_POSITION || angle.abs() > MAX_ANGLE {
+            return step;
+        }
+
+        let inputs = [x / MAX_POSITION, velocity / 2.0, angle / MAX_ANGLE, angular_velocity / 2.0];
+        let force = if controller(&inputs) > 0.5 { FORCE } else { -FORCE };
+
+        let (sin, cos) = angle.sin_cos();
+        let temp = (force + POLE_MASS * HALF_POLE_LENGTH * angular_velocity.powi(2) * sin)
+            / total_mass;
+        let angular_acceleration = (GRAVITY * sin - cos * temp) / (
+            HALF_POLE_LENGTH * (4.0 / 3.0 - POLE_MASS * cos.powi(2) / total_mass)
+        );
+        let acceleration = temp
+            - POLE_MASS * HALF_POLE_LENGTH * angular_acceleration * cos / total_mass;
+
+        x += TIME_STEP * velocity;
+        velocity += TIME_STEP * acceleration;
+        angle += TIME_STEP * angular_velocity;
+        angular_velocity += TIME_STEP * angular_acceleration;
+    }
+
+    CART_POLE_STEPS
+}
+
+/// A NEAT network that balances a pole on a cart, starting with the pole tilted either way.
+#[derive(Debug)]
+struct CartPolePhenotype {
+    network: NeatNetwork,
+}
+
+impl Phenotype for CartPolePhenotype {
+    type Fitness = usize;
+
+    fn evaluate(&self) -> usize {
+        [-0.1, 0.05, 0.15].iter().map(
+            |&angle| balance_pole(angle, |inputs| self.network.activate(inputs)[0])
+        ).sum()
+    }
+}
+
+impl Genotype<CartPolePhenotype> for NeatGenome {
+    fn express(&self) -> CartPolePhenotype {
+        CartPolePhenotype {
+            network: self.network(Activation::SteepenedSigmoid)
+        }
+    }
+}
+
+/// Max ones, observed through uniform noise that is larger than the differences between
+/// neighbouring solutions.
+#[derive(Debug)]
+struct NoisyMaxOnesPhenotype {
+    ones: usize,
+}
+
+impl Phenotype for NoisyMaxOnesPhenotype {
+    type Fitness = f64;
+
+    fn evaluate(&self) -> f64 {
+        self.ones as f64 + random::rng().gen_range(-10.0..10.0)
+    }
+}
+
+impl Genotype<NoisyMaxOnesPhenotype> for BinaryChromosome {
+    fn express(&self) -> NoisyMaxOnesPhenotype {
+        NoisyMaxOnesPhenotype {
+            ones: count_ones(&self.bits)
+        }
+    }
+}
+
+const MAZE_STEPS: usize = 30;
+const MAZE_GOAL: (f32, f32) = (0.0, 10.0);
+// A cup that opens towards the start, between the start and the goal
+const MAZE_WALLS: [((f32, f32), (f32, f32)); 3] = [
+    ((-5.0, 5.0), (5.0, 5.0)),
+    ((-5.0, 1.0), (-5.0, 5.0)),
+    ((5.0, 1.0), (5.0, 5.0)),
+];
+
+/// A robot that takes steps through a deceptive maze, starting at the origin. A step that would
+/// cross a wall is not taken. The robot should end at the goal, but getting closer to it leads
+/// into the cup. The behaviour is where the robot ends.
+#[derive(Debug)]
+struct MazePhenotype {
+    end: (f32, f32),
+}
+
+impl MazePhenotype {
+    fn walk(steps: &[f32]) -> (f32, f32) {
+        let crosses = |(p1, p2): ((f32, f32), (f32, f32)), (q1, q2): ((f32, f32), (f32, f32))| {
+            let side = |a: (f32, f32), b: (f32, f32), c: (f32, f32)| {
+                (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+            };
+            side(p1, p2, q1) * side(p1, p2, q2) <= 0.0 && side(q1, q2, p1) * side(q1, q2, p2) <= 0.0
+        };
+
+        steps.chunks(2).fold((0.0, 0.0), |position, step| {
+            let next = (
+                position.0 + step[0].clamp(-1.0, 1.0),
+                position.1 + step[1].clamp(-1.0, 1.0)
+            );
+            if MAZE_WALLS.iter().any(|&wall| crosses((position, next), wall)) {
+                position
+            } else {
+                next
+            }
+        })
+    }
+}
+
+impl Phenotype for MazePhenotype {
+    // The distance to the goal
+    type Fitness = f32;
+    const DIRECTION: Direction = Direction::Minimise;
+
+    fn evaluate(&self) -> f32 {
+        (self.end.0 - MAZE_GOAL.0).hypot(self.end.1 - MAZE_GOAL.1)
+    }
+}
+
+impl Behaviour for MazePhenotype {
+    fn behaviour(&self) -> Vec<f32> {
+        vec![self.end.0, self.end.1]
+    }
+}
+
+impl Genotype<MazePhenotype> for RealChromosome {
+    fn express(&self) -> MazePhenotype {
+        MazePhenotype {
+            end: MazePhenotype::walk(&self.values)
+        }
+    }
+}
+
+const PARITY_BITS: usize = 4;
+
+/// A circuit of AND, OR, NAND and NOR gates that should compute the even parity of its inputs.
+/// Each bit of a value is a row of the truth table, so that the rows are computed together.
+#[derive(Debug)]
+struct EvenParityPhenotype {
+    circuit: CgpProgram,
+}
+
+impl EvenParityPhenotype {
+    const NUM_FUNCTIONS: usize = 4;
+
+    fn gate(function: usize, arguments: &[u32]) -> u32 {
+        let (a, b) = (arguments[0], arguments[1]);
+        match function {
+            0 => a & b,
+            1 => a | b,
+            2 => !(a & b),
+            _ => !(a | b),
+        }
+    }
+}
+
+impl Phenotype for EvenParityPhenotype {
+    // The number of rows of the truth table that are correct
+    type Fitness = u32;
+    const DIRECTION: Direction = Direction::Maximise;
+
+    fn evaluate(&self) -> u32 {
+        let rows = 1 << PARITY_BITS;
+        let inputs: Vec<u32> = (0..PARITY_BITS).map(
+            |bit| (0..rows).filter(|row| row >> bit & 1 == 1).fold(0, |mask, row| mask | 1 << row)
+        ).collect();
+        let expected = (0..rows)
+            .filter(|row: &u32| row.count_ones().is_multiple_of(2))
+            .fold(0, |mask, row| mask | 1 << row);
+
+        let output = self.circuit.run(&inputs, EvenParityPhenotype::gate)[0];
+        let mask = if rows == 32 { u32::MAX } else { (1 << rows) - 1 };
+        (!(output ^ expected) & mask).count_ones()
+    }
+}
+
+impl Genotype<EvenParityPhenotype> for CgpChromosome {
+    fn express(&self) -> EvenParityPhenotype {
+        EvenParityPhenotype {
+            circuit: self.decode()
+        }
+    }
+}
+
 fn test_creation() {
     for _ in 0..10 {
         let chromosome = BinaryChromosome::new(20);
@@ -79,12 +578,12 @@ fn test_creation() {
     }
 }
 
-fn test_mutation() {
+fn test_mutation() -> Result<(), EvolutionError> {
     let len = 256;
     let chromosome = BinaryChromosome::zeroes(len);
 
     let prob = 0.1;
-    let mutation = BinaryBitMutation::new(prob);
+    let mutation = BinaryBitMutation::new(prob)?;
     let n = 1000;
     let mut total_flipped = 0;
     for _ in 0..n {
@@ -95,9 +594,19 @@ fn test_mutation() {
         total_flipped += flipped;
     }
     println!("flipped = {}, expected = {}", total_flipped, prob * (len * n) as f32);
+
+    // A mutation probability of 0 leaves the chromosome unchanged
+    let mutation = BinaryBitMutation::new(0.0)?;
+    let mut mutated = chromosome.clone();
+    for _ in 0..n {
+        mutation.mutate(&mut mutated);
+    }
+    assert_eq!(mutated, chromosome);
+
+    Ok(())
 }
 
-fn test_recombination() {
+fn test_recombination() -> Result<(), EvolutionError> {
     let len = 100;
     let parent1 = BinaryChromosome::zeroes(len);
     let parent2 = BinaryChromosome::ones(len);
@@ -105,48 +614,1053 @@ fn test_recombination() {
     let max_n = 10;
     for n in 1..max_n+1 {
         let recombination = BinaryNPointBitCrossover::new(n);
-        let child = recombination.recombine(&parent1, &parent2);
+        let child = recombination.recombine(&parent1, &parent2)?;
 
         println!("{:?}", child);
     }
+
+    Ok(())
 }
 
-fn test_init_population() {
-    let ga_config = MaxOnesConfig::new();
-    let mut ga = EvolutionaryAlgorithm::new(
-        10, Box::new(ga_config), Box::new(RankBasedSelection::new(2))
+/// Returns how many standard deviations the observed count is away from the expected count, when
+/// each of the trials succeeds with the given probability.
+fn z_score(observed: usize, trials: usize, prob: f64) -> f64 {
+    let expected = trials as f64 * prob;
+    let stddev = (trials as f64 * prob * (1.0 - prob)).sqrt();
+
+    if stddev == 0.0 {
+        if observed as f64 == expected { 0.0 } else { f64::INFINITY }
+    } else {
+        (observed as f64 - expected) / stddev
+    }
+}
+
+/// The largest z-score per bit that is accepted. It corresponds to a significance of about 0.001
+/// across 100 bits, after a Bonferroni correction.
+const MAX_Z_PER_BIT: f64 = 4.5;
+
+fn test_uniform_distribution() -> Result<(), EvolutionError> {
+    // The assertions are statistical, so the run is seeded to make them reproducible
+    random::seed(35);
+    let len = 100;
+    let n = 2000;
+    let parent1 = BinaryChromosome::zeroes(len);
+    let parent2 = BinaryChromosome::ones(len);
+
+    for bias in [0.0, 0.3, 0.75, 0.99] {
+        let recombination = BinaryUniformRecombination::new(bias)?;
+        let mut counts = vec![0; len];
+        for _ in 0..n {
+            let child = recombination.recombine(&parent1, &parent2)?;
+            for (i, bit) in child.bits.iter().enumerate() {
+                counts[i] += bit as usize;
+            }
+        }
+
+        // Bits are taken from the second parent when a random float is at least the limit
+        let prob = 1.0 - 0.5 * (1.0 + bias as f64);
+        let total = counts.iter().sum();
+        let max_z = counts.iter().map(|c| z_score(*c, n, prob).abs()).fold(0.0, f64::max);
+        println!(
+            "bias = {}, z = {:.2}, max z per bit = {:.2}",
+            bias, z_score(total, n * len, prob), max_z
+        );
+        assert!(max_z < MAX_Z_PER_BIT, "Bits are not taken with probability {}", prob);
+    }
+
+    Ok(())
+}
+
+/// The original, bit-by-bit implementation of n-point crossover. It serves as a reference for the
+/// distribution of the block-based implementation.
+fn reference_n_point(n: usize, parent1: &BitVec, parent2: &BitVec) -> BitVec {
+    let range = parent1.len();
+    let mut points: Vec<usize> = (0..n).map(
+        |_| random::rng().gen_range(1..range)
+    ).collect();
+    points.sort_unstable();
+    if n % 2 == 1 {
+        points.push(range);
+    }
+
+    let mut child = parent1.clone();
+    for i in 0..points.len() / 2 {
+        for j in points[i * 2]..points[i * 2 + 1] {
+            child.set(j, parent2.get(j).unwrap());
+        }
+    }
+
+    child
+}
+
+fn test_n_point_distribution() -> Result<(), EvolutionError> {
+    random::seed(35);
+    let len = 70;
+    let trials = 4000;
+    let parent1 = BinaryChromosome::zeroes(len);
+    let parent2 = BinaryChromosome::ones(len);
+
+    for n in 1..5 {
+        let recombination = BinaryNPointBitCrossover::new(n);
+        let mut counts = vec![0; len];
+        let mut reference_counts = vec![0; len];
+        for _ in 0..trials {
+            let child = recombination.recombine(&parent1, &parent2)?;
+            let reference = reference_n_point(n, &parent1.bits, &parent2.bits);
+            for i in 0..len {
+                counts[i] += child.bits[i] as usize;
+                reference_counts[i] += reference[i] as usize;
+            }
+        }
+
+        // Two-sample z-test for the proportion of bits from the second parent, for each position
+        let max_z = (0..len).map(|i| {
+            let pooled = (counts[i] + reference_counts[i]) as f64 / (2 * trials) as f64;
+            let stddev = (2.0 * pooled * (1.0 - pooled) / trials as f64).sqrt();
+            let diff = (counts[i] as f64 - reference_counts[i] as f64) / trials as f64;
+            if stddev == 0.0 { 0.0 } else { (diff / stddev).abs() }
+        }).fold(0.0, f64::max);
+        println!("n = {}, max z per bit = {:.2}", n, max_z);
+        assert!(max_z < MAX_Z_PER_BIT, "{}-point crossover differs from the reference", n);
+    }
+
+    Ok(())
+}
+
+fn test_composite() -> Result<(), EvolutionError> {
+    let parent1 = (BinaryChromosome::zeroes(8), BinaryChromosome::zeroes(16));
+    let parent2 = (BinaryChromosome::ones(8), BinaryChromosome::ones(16));
+
+    let recombination = CompositeRecombination::new(
+        (BinaryNPointBitCrossover::new(1), BinaryUniformRecombination::new(0.0)?)
     );
+    let mutation = CompositeMutation::new(
+        (BinaryBitMutation::new(0.5)?, BinaryBitMutation::new(0.5)?), &[1.0, 0.0]
+    )?;
+
+    let mut child = recombination.recombine(&parent1, &parent2)?;
+    println!("{:?}", child);
+    mutation.mutate(&mut child);
+    println!("{:?}", child);
+
+    Ok(())
+}
+
+fn test_init_population() -> Result<(), EvolutionError> {
+    let ga_config = MaxOnesConfig::new()?;
+    let mut ga = EvolutionaryAlgorithm::new(
+        10, Box::new(ga_config), Box::new(RankBasedSelection::new(2)?)
+    )?;
 
     ga.start();
     println!("{:?}", ga);
     ga.grow();
-    ga.evaluate();
+    ga.evaluate()?;
     println!("{:?}", ga);    
+
+    Ok(())
 }
 
-fn test_selection() {
-    let ga_config = MaxOnesConfig::new();
+fn test_fitness() -> Result<(), EvolutionError> {
+    assert!(Direction::Maximise.is_better((3, 1), (3, 0)));
+    assert!(Direction::Minimise.is_better(-1, 2));
+    assert!(!Direction::Maximise.is_better(f32::NAN, 0.0));
+
+    // The numeric value of a tuple is its first element, so the target is the number of leading
+    // ones
+    let len = 32;
+    let mut ga: EvolutionaryAlgorithm<LeadingOnesPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(20)
+        .elitism(1)
+        .operators(
+            move || BinaryChromosome::new(len),
+            BinaryBitMutation::new(1.0 / len as f32)?,
+            BinaryUniformRecombination::new(0.5)?
+        )
+        .build()?;
+    let termination = Termination::new().target_fitness(len as f64).max_evaluations(100_000);
+    let reason = ga.run(&termination)?;
+    let best = ga.best_individual().and_then(|best| best.fitness());
+    println!(
+        "leading ones: {:?} after {} evaluations, best = {:?}", reason, ga.evaluations(), best
+    );
+
+    // Minimisation: the target is reached once the fitness is at most the target
+    let mut sphere: TrajectorySearch<SpherePhenotype, _, _, _> = TrajectorySearch::new(
+        || RealChromosome::new(4, -1.0, 1.0), RealGaussianMutation::new(0.5, 0.1)?,
+        HillClimbing::new(4)?
+    );
+    let reason = sphere.run(&Termination::new().target_fitness(0.001).max_evaluations(100_000))?;
+    let best = sphere.best().map(|(_, fitness)| fitness);
+    println!("sphere: {:?} after {} evaluations, best = {:?}", reason, sphere.evaluations(), best);
+    assert!(reason != TerminationReason::TargetReached || best.is_some_and(|best| best <= 0.001));
+
+    Ok(())
+}
+
+fn test_selection() -> Result<(), EvolutionError> {
+    let ga_config = MaxOnesConfig::new()?;
     let mut ga = EvolutionaryAlgorithm::new(
-        20, Box::new(ga_config), Box::new(RankBasedSelection::new(2))
+        20, Box::new(ga_config), Box::new(RankBasedSelection::new(2)?)
+    )?;
+
+    ga.start();
+
+    for _ in 0..100 {
+        ga.grow();
+        ga.evaluate()?;
+        if let Some(stats) = ga.get_stats() {
+            println!("{:?}", stats);
+        }
+
+        ga.breed()?;
+    }
+
+    Ok(())
+}
+
+fn test_builder_elitism() -> Result<(), EvolutionError> {
+    let mut ga: EvolutionaryAlgorithm<MaxOnesPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(20)
+        .mutation_prob(0.5)
+        .elitism(1)
+        .selection(RankBasedSelection::new(2)?)
+        .operators(
+            || BinaryChromosome::new(32),
+            BinaryBitMutation::new(0.02)?,
+            BinaryNPointBitCrossover::new(2)
+        )
+        .build()?;
+
+    ga.start();
+
+    // The elite survives unchanged, so the best fitness never decreases
+    let mut best_fitness = f64::NEG_INFINITY;
+    for _ in 0..100 {
+        ga.grow();
+        ga.evaluate()?;
+        if let Some(stats) = ga.get_stats() {
+            println!("{:?}", stats);
+            assert!(stats.best_fitness >= best_fitness, "The elite was lost");
+            best_fitness = stats.best_fitness;
+        }
+
+        ga.breed()?;
+    }
+
+    Ok(())
+}
+
+fn test_diversity() -> Result<(), EvolutionError> {
+    // The mean distance derived from allele counts should match the one from comparing all pairs
+    let genotypes: Vec<BinaryChromosome> = (0..30).map(|_| BinaryChromosome::new(45)).collect();
+    let refs: Vec<&BinaryChromosome> = genotypes.iter().collect();
+    println!(
+        "mean distance = {}, pairwise = {}",
+        BinaryChromosome::diversity(&refs).mean_distance, mean_pairwise_distance(&refs)
     );
 
+    let mut ga: EvolutionaryAlgorithm<MaxOnesPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(20)
+        .diversity()
+        .operators(
+            || BinaryChromosome::new(32),
+            BinaryBitMutation::new(0.01)?,
+            BinaryUniformRecombination::new(0.0)?
+        )
+        .build()?;
+
     ga.start();
 
+    for generation in 0..30 {
+        ga.grow();
+        ga.evaluate()?;
+        let diversity = ga.get_stats().and_then(|stats| stats.diversity);
+        if let Some(Diversity { mean_distance, loci: Some(loci) }) = diversity {
+            println!(
+                "generation {}: mean distance = {:.2}, mean entropy = {:.3}, converged loci = {}",
+                generation, mean_distance, loci.mean_entropy, loci.converged_loci
+            );
+        }
+
+        ga.breed()?;
+    }
+
+    Ok(())
+}
+
+fn test_local_search() -> Result<(), EvolutionError> {
+    for mode in [LearningMode::Lamarckian, LearningMode::Baldwinian] {
+        let mut ga: EvolutionaryAlgorithm<MaxOnesPhenotype, _> = EvolutionaryAlgorithm::builder()
+            .pop_size(10)
+            .local_search(BinaryBitFlipHillClimbing::new(Improvement::First, 10)?, mode, 0.5)
+            .operators(
+                || BinaryChromosome::new(64),
+                BinaryBitMutation::new(0.02)?,
+                BinaryNPointBitCrossover::new(2)
+            )
+            .build()?;
+
+        ga.start();
+        for _ in 0..10 {
+            ga.grow();
+            ga.evaluate()?;
+            ga.breed()?;
+        }
+        ga.grow();
+        ga.evaluate()?;
+
+        if let Some(stats) = ga.get_stats() {
+            println!("{:?}: best fitness = {}", mode, stats.best_fitness);
+        }
+    }
+
+    let mut tour = PermutationChromosome::new(12);
+    let before = tour.express().evaluate();
+    let after = LocalSearch::<CircleTourPhenotype, _>::improve(
+        &PermutationTwoOpt::new(Improvement::Best, 10_000)?, &mut tour
+    );
+    println!("2-opt: {} -> {}, {:?}", before, after.fitness, tour.order);
+
+    let mut ga: EvolutionaryAlgorithm<CircleTourPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(20)
+        .elitism(1)
+        .operators(
+            || PermutationChromosome::new(12), PermutationSwapMutation, PermutationOrderCrossover
+        )
+        .build()?;
+
+    ga.start();
+    for _ in 0..50 {
+        ga.grow();
+        ga.evaluate()?;
+        ga.breed()?;
+    }
+    ga.grow();
+    ga.evaluate()?;
+    if let Some(best) = ga.best_individual() {
+        println!("tour: {:?}, {:?}", best.fitness(), best.genotype().order);
+    }
+
+    let mut point = RealChromosome::new(3, -5.0, 5.0);
+    let before = Genotype::<SpherePhenotype>::express(&point).evaluate();
+    let after = LocalSearch::<SpherePhenotype, _>::improve(
+        &RealCoordinateSearch::new(Improvement::First, 1.0, 1e-3, 1000)?, &mut point
+    );
+    println!(
+        "coordinate search: {} -> {} in {} evaluations, {:?}",
+        before, after.fitness, after.evaluations, point.values
+    );
+
+    Ok(())
+}
+
+/// Runs the optimiser until it solves the problem, and prints how long it took.
+fn run_to_target(name: &str, optimiser: &mut dyn Optimiser) -> Result<(), EvolutionError> {
+    let termination = Termination::new().target_fitness(1.0).max_evaluations(100_000);
+    let reason = optimiser.run(&termination)?;
+
+    println!("{}: {:?} after {} evaluations", name, reason, optimiser.evaluations());
+    Ok(())
+}
+
+fn test_eda() -> Result<(), EvolutionError> {
+    let len = 64;
+
+    let mut pbil: Eda<MaxOnesPhenotype, _> = Eda::new(
+        len, Pbil::new(20, 0.1)?.with_mutation(0.02, 0.05)?
+    )?;
+    run_to_target("PBIL", &mut pbil)?;
+
+    let mut umda: Eda<MaxOnesPhenotype, _> = Eda::new(len, Umda::new(50, 25)?)?;
+    umda.add_log_sink(Box::new(JsonLinesSink::new(io::stdout())));
+    umda.step()?;
+    run_to_target("UMDA", &mut umda)?;
+
+    let mut cga: Eda<MaxOnesPhenotype, _> = Eda::new(len, CompactGa::new(50)?)?;
+    run_to_target("cGA", &mut cga)?;
+    let probs: Vec<String> = cga.probabilities().iter().map(|p| format!("{:.2}", p)).collect();
+    println!("cGA probabilities after {} generations: {}", cga.generation(), probs.join(" "));
+
+    let mut ga: EvolutionaryAlgorithm<MaxOnesPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(20)
+        .elitism(1)
+        .operators(
+            move || BinaryChromosome::new(len),
+            BinaryBitMutation::new(1.0 / len as f32)?,
+            BinaryUniformRecombination::new(0.0)?
+        )
+        .build()?;
+    run_to_target("GA", &mut ga)
+}
+
+fn test_experiment() -> Result<(), EvolutionError> {
+    let len = 64;
+    let termination = Termination::new().target_fitness(1.0).max_evaluations(5_000);
+    let mut experiment = Experiment::new((0..10).collect(), termination)?;
+
+    for pop_size in [10, 40] {
+        for mutation_rate in [0.5, 2.0] {
+            experiment.add_configuration(
+                &format!("GA, pop {}, mutation {}/len", pop_size, mutation_rate),
+                move || {
+                    let ga: EvolutionaryAlgorithm<MaxOnesPhenotype, _> =
+                        EvolutionaryAlgorithm::builder()
+                        .pop_size(pop_size)
+                        .elitism(1)
+                        .operators(
+                            move || BinaryChromosome::new(len),
+                            BinaryBitMutation::new(mutation_rate / len as f32)?,
+                            BinaryUniformRecombination::new(0.5)?
+                        )
+                        .build()?;
+                    Ok(Box::new(ga) as Box<dyn Optimiser>)
+                }
+            );
+        }
+    }
+    experiment.add_configuration("cGA", move || {
+        let cga: Eda<MaxOnesPhenotype, _> = Eda::new(len, CompactGa::new(50)?)?;
+        Ok(Box::new(cga) as Box<dyn Optimiser>)
+    });
+
+    let summary = experiment.run()?;
+    print!("{}", summary);
+
+    // Seeded runs are reproducible, regardless of the thread that does them
+    experiment.set_num_threads(1)?;
+    let rerun = experiment.run()?;
+    let same_curves = summary.configurations.iter().zip(rerun.configurations.iter()).all(
+        |(a, b)| a.runs.iter().zip(b.runs.iter()).all(|(a, b)| a.curve == b.curve)
+    );
+    println!("rerun on one thread gives the same curves: {}", same_curves);
+    assert!(same_curves);
+
+    Ok(())
+}
+
+fn test_trajectory() -> Result<(), EvolutionError> {
+    let len = 64;
+    let factory = move || BinaryChromosome::new(len);
+    let mutation = || BinaryBitMutation::new(1.0 / len as f32);
+
+    let mut hill_climbing: TrajectorySearch<MaxOnesPhenotype, _, _, _> = TrajectorySearch::new(
+        factory, mutation()?, HillClimbing::new(4)?
+    );
+    run_to_target("hill climbing", &mut hill_climbing)?;
+
+    for schedule in [
+        CoolingSchedule::Geometric { initial: 0.05, factor: 0.99 },
+        CoolingSchedule::Linear { initial: 0.05, decrement: 0.0001 },
+        CoolingSchedule::Logarithmic { initial: 0.01 },
+    ] {
+        let mut annealing: TrajectorySearch<MaxOnesPhenotype, _, _, _> = TrajectorySearch::new(
+            factory, mutation()?, SimulatedAnnealing::new(schedule)?
+        );
+        run_to_target(&format!("annealing, {:?}", schedule), &mut annealing)?;
+    }
+
+    let mut tabu: TrajectorySearch<MaxOnesPhenotype, _, _, _> = TrajectorySearch::new(
+        factory, mutation()?, TabuSearch::new(64, 5)?
+    );
+    run_to_target("tabu search", &mut tabu)?;
+    if let Some((best, fitness)) = tabu.best() {
+        println!("best = {:?}, fitness = {}", best, fitness);
+    }
+
+    // Starting from an invalid fitness, every strategy moves on to a valid one
+    run_from_invalid("hill climbing", HillClimbing::new(4)?)?;
+    let schedule = CoolingSchedule::Geometric { initial: 0.05, factor: 0.99 };
+    run_from_invalid("annealing", SimulatedAnnealing::new(schedule)?)?;
+    run_from_invalid("tabu search", TabuSearch::new(4, 5)?)?;
+
+    Ok(())
+}
+
+fn run_from_invalid(
+    name: &str, strategy: impl MoveStrategy<MaxOnesPhenotype, BinaryChromosome>
+) -> Result<(), EvolutionError> {
+    let len = 64;
+    let mut search = TrajectorySearch::new(
+        move || BinaryChromosome::zeroes(len), BinaryBitMutation::new(1.0 / len as f32)?, strategy
+    );
+    search.set_evaluator(Box::new(ZeroIsInvalid));
+    for _ in 0..20 {
+        search.step()?;
+    }
+
+    let current = search.current().map(|(_, fitness)| fitness);
+    let best = search.best().map(|(_, fitness)| fitness);
+    println!("{} from an invalid start: current = {:?}, best = {:?}", name, current, best);
+    assert!(current.is_some_and(|fitness| fitness > 0.0), "Stuck at the invalid start");
+    assert!(best.is_some_and(|fitness| fitness > 0.0), "Kept the invalid start as best");
+
+    Ok(())
+}
+
+fn run_maze(
+    name: &str, selection: impl SelectionFactory<MazePhenotype, RealChromosome> + 'static
+) -> Result<(), EvolutionError> {
+    let mut ga: EvolutionaryAlgorithm<MazePhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(100)
+        .elitism(2)
+        .recombination_prob(0.0)
+        .mutation_prob(1.0)
+        .selection(selection)
+        .operators(
+            || RealChromosome::new(2 * MAZE_STEPS, -1.0, 1.0),
+            RealGaussianMutation::new(0.1, 0.3)?,
+            RealArithmeticCrossover
+        )
+        .build()?;
+    let reason = ga.run(&Termination::new().target_fitness(0.5).max_generations(500))?;
+    let best = ga.best_individual().and_then(|best| best.phenotype().zip(best.fitness()));
+    println!(
+        "maze by {}: {:?} after {} generations, best = {:?}",
+        name, reason, ga.generation(), best
+    );
+
+    Ok(())
+}
+
+fn test_novelty_search() -> Result<(), EvolutionError> {
+    // Fitness alone leads into the cup
+    run_maze("fitness", RankBasedSelection::new(2)?)?;
+
+    // Clones share the archive, so it can be inspected after the run
+    let novelty = NoveltySelection::new(15, 2)?;
+    run_maze("novelty", novelty.clone())?;
+    println!("archive of {} behaviours", novelty.archive_size());
+
+    let mixed = NoveltySelection::new(15, 2)?.with_fitness_weight(0.3)?;
+    run_maze("novelty and fitness", mixed)?;
+
+    Ok(())
+}
+
+fn test_map_elites() -> Result<(), EvolutionError> {
+    // A map of where the robot can end, and how close to the goal it can get from there
+    let archive: GridArchive<MazePhenotype, _> = GridArchive::new(vec![
+        FeatureRange::new("x", -12.0, 12.0, 12)?,
+        FeatureRange::new("y", -12.0, 12.0, 12)?,
+    ])?;
+    let mut map_elites = MapElites::new(
+        archive,
+        || RealChromosome::new(2 * MAZE_STEPS, -1.0, 1.0),
+        RealGaussianMutation::new(0.1, 0.3)?,
+        RealArithmeticCrossover
+    ).with_recombination_prob(0.1)?;
+
+    for _ in 0..10 {
+        for _ in 0..50 {
+            map_elites.step()?;
+        }
+        let archive = map_elites.archive();
+        println!(
+            "generation {}: coverage = {:.3}, qd-score = {:.1}, best = {:?}",
+            map_elites.generation(), archive.coverage(), archive.qd_score(50.0),
+            archive.best().map(|elite| elite.fitness)
+        );
+    }
+
+    let mut csv = Vec::new();
+    map_elites.archive().write_csv(&mut csv)?;
+    let csv = String::from_utf8_lossy(&csv);
+    println!("{} ({} rows)", csv.lines().next().unwrap_or(""), csv.lines().count() - 1);
+
+    Ok(())
+}
+
+fn run_noisy_max_ones(name: &str, noise: Option<NoiseHandling>) -> Result<(), EvolutionError> {
+    let len = 64;
+    let mut builder = EvolutionaryAlgorithm::builder()
+        .pop_size(50)
+        .elitism(5)
+        .operators(
+            move || BinaryChromosome::new(len),
+            BinaryBitMutation::new(1.0 / len as f32)?,
+            BinaryUniformRecombination::new(0.0)?
+        );
+    if let Some(noise) = noise {
+        builder = builder.noise_handling(noise);
+    }
+    let mut ga: EvolutionaryAlgorithm<NoisyMaxOnesPhenotype, BinaryChromosome> = builder.build()?;
+    ga.run(&Termination::new().max_evaluations(50_000))?;
+
+    let best = ga.best_individual().ok_or(EvolutionError::NoPopulation)?;
+    println!(
+        "noisy max ones, {}: best has {} of {} ones, fitness = {:.2?}, samples = {:?}",
+        name, count_ones(&best.genotype().bits), len, best.fitness(),
+        best.samples().map(|samples| (samples.count(), samples.mean(), samples.standard_error()))
+    );
+
+    Ok(())
+}
+
+fn test_noisy_fitness() -> Result<(), EvolutionError> {
+    // Evaluated once, the fitness of an elite is as lucky as it ever gets, and it is never
+    // corrected. Samples give an estimate that is closer to the number of ones.
+    run_noisy_max_ones("single evaluation", None)?;
+    run_noisy_max_ones("mean of 4 samples", Some(NoiseHandling::new(4)?))?;
+
+    let bound = NoiseHandling::new(4)?
+        .with_survivor_samples(2)
+        .with_estimate(Estimate::ConfidenceBound(1.0))?;
+    run_noisy_max_ones("lower confidence bound", Some(bound))?;
+
+    Ok(())
+}
+
+fn test_cartesian_gp() -> Result<(), EvolutionError> {
+    let layout = CgpLayout::new(PARITY_BITS, 1, 100, EvenParityPhenotype::NUM_FUNCTIONS, 2)?;
+    let chromosome = CgpChromosome::new(layout);
+    println!(
+        "{} genes, {} of {} nodes active",
+        layout.num_genes(), chromosome.active_nodes().len(), layout.num_nodes()
+    );
+
+    // With a grid of 2 rows and a levels back of 3, only nodes in the first three columns can
+    // connect to the inputs, and any node only to nodes in the three columns before it
+    let grid = CgpLayout::new(PARITY_BITS, 1, 10, EvenParityPhenotype::NUM_FUNCTIONS, 2)?
+        .with_rows(2)?
+        .with_levels_back(3)?;
+    let mut chromosome = CgpChromosome::new(grid);
+    let mutation = CgpPointMutation::new(0.5)?;
     for _ in 0..100 {
+        mutation.mutate(&mut chromosome);
+        for node in 0..grid.num_nodes() {
+            let column = node / grid.rows();
+            let connections = &chromosome.genes()[node * 3 + 1..node * 3 + 3];
+            for &address in connections {
+                let source_column = address.checked_sub(PARITY_BITS).map(|node| node / 2);
+                match source_column {
+                    None => assert!(column < 3, "Node {} connects to an input", node),
+                    Some(source) => assert!(source < column && column - source <= 3),
+                }
+            }
+        }
+    }
+
+    let mut es: TrajectorySearch<EvenParityPhenotype, _, _, _> = cgp::one_plus_lambda(
+        layout, CgpPointMutation::new(0.03)?, 4
+    )?;
+    let target = 1 << PARITY_BITS;
+    let reason = es.run(
+        &Termination::new().target_fitness(target as f64).max_evaluations(200_000)
+    )?;
+    let (best, fitness) = es.best().ok_or(EvolutionError::NoPopulation)?;
+    println!(
+        "even-{}-parity: {:?} after {} evaluations, {} of {} rows, {} active gates",
+        PARITY_BITS, reason, es.evaluations(), fitness, target, best.active_nodes().len()
+    );
+
+    Ok(())
+}
+
+fn test_genealogy() -> Result<(), EvolutionError> {
+    let ga_config = MaxOnesConfig::new()?;
+    let mut ga = EvolutionaryAlgorithm::new(
+        10, Box::new(ga_config), Box::new(RankBasedSelection::new(2)?)
+    )?;
+
+    ga.enable_genealogy();
+    ga.start();
+
+    for _ in 0..5 {
+        ga.grow();
+        ga.evaluate()?;
+        ga.breed()?;
+    }
+    ga.grow();
+    ga.evaluate()?;
+
+    if let Some(dot) = ga.export_best_lineage(LineageFormat::Dot) {
+        println!("{}", dot);
+    }
+    if let Some(json) = ga.export_best_lineage(LineageFormat::Json) {
+        println!("{}", json);
+    }
+
+    Ok(())
+}
+
+fn test_logging() -> Result<(), EvolutionError> {
+    let ga_config = MaxOnesConfig::new()?;
+    let mut ga = EvolutionaryAlgorithm::new(
+        4, Box::new(ga_config), Box::new(RankBasedSelection::new(2)?)
+    )?;
+
+    ga.add_log_sink(Box::new(CsvSink::new(io::stdout())));
+    ga.add_log_sink(Box::new(JsonLinesSink::new(io::stdout()).with_snapshots(2)));
+    ga.start();
+
+    for _ in 0..3 {
+        ga.grow();
+        ga.evaluate()?;
+        ga.log_generation()?;
+        ga.breed()?;
+    }
+
+    Ok(())
+}
+
+fn test_invalid_fitness() -> Result<(), EvolutionError> {
+    // Which evaluations fail is random, so the run is seeded to make the assertions reproducible
+    random::seed(43);
+    let ga = |policy| EvolutionaryAlgorithm::<UnreliablePhenotype, _>::builder()
+        .pop_size(20)
+        .invalid_fitness_policy(policy)
+        .operators(
+            || BinaryChromosome::new(32),
+            BinaryBitMutation::new(0.02)?,
+            BinaryUniformRecombination::new(0.5)?
+        )
+        .build();
+
+    // Breeding a population that was never evaluated is an error
+    let mut worst = ga(InvalidFitnessPolicy::Worst)?;
+    worst.start();
+    assert!(matches!(worst.breed(), Err(EvolutionError::Unevaluated(20))));
+
+    worst.grow();
+    worst.evaluate()?;
+    for _ in 0..10 {
+        worst.step()?;
+    }
+    let stats = worst.get_stats().ok_or(EvolutionError::NoPopulation)?;
+    println!("worst: {:?}", stats);
+    assert!(stats.avg_fitness.is_finite() && stats.best_fitness.is_finite());
+
+    let mut reevaluate = ga(InvalidFitnessPolicy::Reevaluate(10))?;
+    let mut num_invalid = 0;
+    for _ in 0..10 {
+        num_invalid += reevaluate.step()?.invalid_evaluations;
+    }
+    let stats = reevaluate.get_stats().ok_or(EvolutionError::NoPopulation)?;
+    println!(
+        "re-evaluate: {} invalid, {} evaluations, best = {}",
+        num_invalid, reevaluate.evaluations(), stats.best_fitness
+    );
+    assert!(num_invalid > 0);
+    assert!(stats.avg_fitness.is_finite() && stats.best_fitness.is_finite());
+
+    // With 20 individuals, at least one evaluation fails
+    let mut reject = ga(InvalidFitnessPolicy::Reject)?;
+    let result = reject.step();
+    println!("reject: {:?}", result);
+    assert!(matches!(result, Err(EvolutionError::InvalidFitness(_))));
+
+    Ok(())
+}
+
+fn test_hall_of_fame() -> Result<(), EvolutionError> {
+    let mut ga: EvolutionaryAlgorithm<MaxOnesPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(10)
+        .hall_of_fame(HallOfFame::with_unique_genotypes(3))
+        .operators(
+            || BinaryChromosome::new(16),
+            BinaryBitMutation::new(0.1)?,
+            BinaryUniformRecombination::new(0.0)?
+        )
+        .build()?;
+
+    ga.add_log_sink(Box::new(CsvSink::new(io::stdout()).with_summary(io::stdout())));
+    ga.add_log_sink(Box::new(JsonLinesSink::new(io::stdout())));
+    ga.start();
+
+    for _ in 0..20 {
+        ga.grow();
+        ga.evaluate()?;
+        ga.breed()?;
+    }
+    ga.grow();
+    ga.evaluate()?;
+
+    let hall_of_fame = ga.hall_of_fame().ok_or(EvolutionError::NoPopulation)?;
+    if let Some(best) = hall_of_fame.best() {
+        println!("best = {:?}, found in generation {}", best.phenotype, best.generation);
+    }
+
+    // The hall of fame can be written, and read back when the run is started again
+    let mut saved = Vec::new();
+    hall_of_fame.write(&mut saved)?;
+    let mut restored: HallOfFame<MaxOnesPhenotype, BinaryChromosome> = HallOfFame::new(3);
+    restored.read(&saved[..])?;
+    assert_eq!(restored.len(), hall_of_fame.len());
+    for (entry, original) in restored.entries().iter().zip(hall_of_fame.entries()) {
+        assert_eq!(
+            (entry.id, entry.generation, entry.fitness, &entry.genotype),
+            (original.id, original.generation, original.fitness, &original.genotype)
+        );
+    }
+    assert!(restored.read("1\t0\t0.5\t012".as_bytes()).is_err());
+
+    ga.log_summary()
+}
+
+fn test_external_evaluation() -> Result<(), EvolutionError> {
+    let ga_config = MaxOnesConfig::new()?;
+    let mut ga = EvolutionaryAlgorithm::new(
+        20, Box::new(ga_config), Box::new(RankBasedSelection::new(2)?)
+    )?;
+
+    // Evaluates MaxOnes using shell worker processes that count the number of ones
+    let script = "while read bits; do printf '%s' \"$bits\" | tr -cd 1 | wc -c; done";
+    ga.set_evaluator(Box::new(WorkerPool::new("sh", &["-c", script], 4)?));
+    ga.start();
+
+    for _ in 0..10 {
+        ga.grow();
+        ga.evaluate()?;
+        if let Some(stats) = ga.get_stats() {
+            println!("{:?}", stats);
+        }
+
+        ga.breed()?;
+    }
+
+    Ok(())
+}
+
+fn test_neuroevolution() -> Result<(), EvolutionError> {
+    // Fixed topology: only the weights evolve
+    let num_weights = xor_topology().num_weights();
+    let mut ga: EvolutionaryAlgorithm<XorNetworkPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(100)
+        .elitism(2)
+        .operators(
+            move || RealChromosome::new(num_weights, -2.0, 2.0),
+            RealGaussianMutation::new(0.2, 0.5)?,
+            RealArithmeticCrossover
+        )
+        .build()?;
+    let reason = ga.run(&Termination::new().target_fitness(0.01).max_generations(500))?;
+    let best = ga.best_individual().and_then(|best| best.fitness());
+    println!(
+        "fixed topology xor: {:?} after {} evaluations, error = {:?}",
+        reason, ga.evaluations(), best
+    );
+
+    // NEAT grows the hidden nodes that the exclusive or needs
+    let mut neat: Neat<XorNeatPhenotype> = Neat::new(150, NeatConfig::new(2, 1))?;
+    let reason = neat.run(&Termination::new().target_fitness(0.01).max_generations(300))?;
+    let (genome, error) = neat.best().ok_or(EvolutionError::NoPopulation)?;
+    println!(
+        "neat xor: {:?} after {} generations, error = {:.4}, {} hidden nodes, {} species",
+        reason, neat.generation(), error, genome.num_hidden(), neat.num_species()
+    );
+
+    let config = NeatConfig {
+        add_connection_prob: 0.3,
+        ..NeatConfig::new(4, 1)
+    };
+    let mut neat: Neat<CartPolePhenotype> = Neat::new(100, config)?;
+    let target = 3 * CART_POLE_STEPS;
+    let reason = neat.run(&Termination::new().target_fitness(target as f64).max_generations(100))?;
+    let (genome, steps) = neat.best().ok_or(EvolutionError::NoPopulation)?;
+    println!(
+        "neat cart-pole: {:?} after {} generations, {} of {} steps, {} connections",
+        reason, neat.generation(), steps, target, genome.connections().len()
+    );
+
+    Ok(())
+}
+
+fn test_grammatical_evolution() -> Result<(), EvolutionError> {
+    // The operators are part of the expression rule, so that the evaluation can find them
+    let grammar = Grammar::parse("
+        <expr> ::= (<expr> + <expr>) | (<expr> - <expr>) | (<expr> * <expr>)
+                 | <var> | <var>
+        <var>  ::= x | \"1\"
+    ")?;
+    let mapper = Arc::new(Mapper::new(grammar, 2));
+
+    // Without wrapping, the codons run out. With wrapping, they are re-used.
+    println!("{:?}", Grammar::parse("<a> ::= <a><a> | x")?.map(&[0, 0, 0], 0));
+    let program = mapper.map(&[0, 3, 1, 4]).map(|program| program.text);
+    assert_eq!(program.as_deref(), Some("(1 + x)"));
+    println!("{:?}", mapper.map_binary(&BinaryChromosome::new(64)).map(|program| program.text));
+    assert!(Grammar::parse("<a> ::= <a> x").is_err());
+    assert!(Grammar::parse("<a> ::= <b>").is_err());
+
+    let limits = LengthLimits::new(10, 100)?;
+    let mut ga: EvolutionaryAlgorithm<RegressionPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(100)
+        .elitism(2)
+        .operators(
+            move || CodonChromosome::new(30, Arc::clone(&mapper)),
+            CodonMutation::new(0.05)?,
+            CodonCutAndSpliceCrossover::new(limits)
+        )
+        .build()?;
+    let reason = ga.run(&Termination::new().target_fitness(0.0).max_generations(100))?;
+    let best = ga.best_individual().ok_or(EvolutionError::NoPopulation)?;
+    println!(
+        "grammatical evolution: {:?}, best = {:?} with fitness {:?}", reason,
+        best.genotype().map().map(|program| program.text), best.fitness()
+    );
+
+    Ok(())
+}
+
+fn test_distributed_evaluation() -> Result<(), EvolutionError> {
+    let heartbeat = Heartbeat::new(Duration::from_millis(50), Duration::from_millis(300))?;
+    let coordinator = Coordinator::bind("127.0.0.1:0", heartbeat)?.with_batch_size(4)?;
+    let addr = coordinator.local_addr();
+
+    // A worker that never replies, not even with heartbeats, and one that disconnects as soon
+    // as it receives a batch. Both batches are handed out again to the other workers.
+    let silent = TcpStream::connect(addr)?;
+    let crashing = thread::spawn(move || -> io::Result<()> {
+        let mut stream = TcpStream::connect(addr)?;
+        loop {
+            let mut len = [0; 4];
+            stream.read_exact(&mut len)?;
+            let mut frame = vec![0; u32::from_be_bytes(len) as usize];
+            stream.read_exact(&mut frame)?;
+            if frame[0] == 1 {
+                return Ok(());
+            }
+        }
+    });
+
+    let workers: Vec<_> = (0..3).map(|_| {
+        thread::spawn(move || Worker::connect(addr, heartbeat)?.run(
+            |bits| bits.chars().filter(|&bit| bit == '1').count() as f32 / bits.len() as f32
+        ))
+    }).collect();
+    while coordinator.num_workers() < 5 {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    // With five batches for five workers, the silent and the crashing worker each get one. All
+    // phenotypes are still evaluated, so their batches must have been handed out again.
+    let ga_config = MaxOnesConfig::new()?;
+    let phenotypes: Vec<MaxOnesPhenotype> = (0..20).map(|_| ga_config.create().express())
+        .collect();
+    let encoded: Vec<String> = phenotypes.iter().map(|phenotype| phenotype.encode()).collect();
+    let fitnesses = coordinator.evaluate_batch(&encoded)?;
+    for (phenotype, fitness) in phenotypes.iter().zip(fitnesses) {
+        assert!(fitness.is_finite(), "Phenotype {} was not evaluated", phenotype);
+        assert_eq!(fitness, phenotype.evaluate());
+    }
+
+    let mut ga = EvolutionaryAlgorithm::new(
+        20, Box::new(ga_config), Box::new(RankBasedSelection::new(2)?)
+    )?;
+    ga.set_evaluator(Box::new(coordinator));
+    ga.start();
+
+    for _ in 0..10 {
+        ga.grow();
+        ga.evaluate()?;
+        if let Some(stats) = ga.get_stats() {
+            println!("{:?}", stats);
+            assert_eq!(stats.invalid_evaluations, 0);
+        }
+
+        ga.breed()?;
+    }
+
+    // Dropping the coordinator closes the connections, which stops the workers
+    drop(ga);
+    drop(silent);
+    crashing.join().unwrap()?;
+    for worker in workers {
+        worker.join().unwrap()?;
+    }
+
+    Ok(())
+}
+
+fn test_ask_tell() -> Result<(), EvolutionError> {
+    let ga_config = MaxOnesConfig::new()?;
+    let mut ga = EvolutionaryAlgorithm::new(
+        20, Box::new(ga_config), Box::new(RankBasedSelection::new(2)?)
+    )?;
+
+    ga.start();
+
+    for _ in 0..10 {
+        // The "rater" evaluates the individuals in reverse order, and prefers zeroes
+        let handles = ga.ask()?;
+        for handle in handles.into_iter().rev() {
+            let rating = match ga.phenotype(handle) {
+                Some(phenotype) => 1.0 - phenotype.evaluate(),
+                None => 0.0,
+            };
+            ga.tell(handle, rating)?;
+        }
+
+        if let Some(stats) = ga.get_stats() {
+            println!("{:?}", stats);
+        }
+
+        ga.breed()?;
+    }
+
+    Ok(())
+}
+
+fn test_variable_length() -> Result<(), EvolutionError> {
+    let limits = LengthLimits::new(8, 64)?;
+    let mutation = WeightedMutation::new()
+        .with(1.0, BinaryBitMutation::new(0.02)?)?
+        .with(1.0, BinaryInsertionMutation::new(limits, 4))?
+        .with(1.0, BinaryDeletionMutation::new(limits, 4))?
+        .with(1.0, BinaryDuplicationMutation::new(limits, 4))?;
+    let mut ga: EvolutionaryAlgorithm<MaxOnesPhenotype, _> = EvolutionaryAlgorithm::builder()
+        .pop_size(20)
+        .operators(
+            || BinaryChromosome::new(16), mutation, BinaryCutAndSpliceCrossover::new(limits)
+        )
+        .build()?;
+
+    ga.start();
+
+    for _ in 0..20 {
         ga.grow();
-        ga.evaluate();
+        ga.evaluate()?;
         if let Some(stats) = ga.get_stats() {
             println!("{:?}", stats);
         }
 
-        ga.breed();
+        ga.breed()?;
     }
+
+    Ok(())
 }
 
-fn main() {
+fn main() -> Result<(), EvolutionError> {
     test_creation();
-    test_mutation();
-    test_recombination();
-    test_init_population();
-    test_selection();
+    test_mutation()?;
+    test_recombination()?;
+    test_uniform_distribution()?;
+    test_n_point_distribution()?;
+    test_composite()?;
+    test_init_population()?;
+    test_selection()?;
+    test_builder_elitism()?;
+    test_fitness()?;
+    test_diversity()?;
+    test_local_search()?;
+    test_eda()?;
+    test_trajectory()?;
+    test_grammatical_evolution()?;
+    test_neuroevolution()?;
+    test_novelty_search()?;
+    test_map_elites()?;
+    test_cartesian_gp()?;
+    test_noisy_fitness()?;
+    test_experiment()?;
+    test_genealogy()?;
+    test_logging()?;
+    test_invalid_fitness()?;
+    test_hall_of_fame()?;
+    test_external_evaluation()?;
+    test_distributed_evaluation()?;
+    test_ask_tell()?;
+    test_variable_length()?;
+
+    Ok(())
 }
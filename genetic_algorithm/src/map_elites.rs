@@ -0,0 +1,413 @@
+//! MAP-Elites, a quality-diversity algorithm that finds the fittest solution for each kind of
+//! behaviour, rather than a single optimum.
+//!
+//! The space of behaviours is divided into a grid. Each dimension of the grid is a feature of the
+//! behaviour of a phenotype, see [Behaviour]. Each cell of the grid keeps the fittest solution
+//! found with features in that cell, its elite. New solutions are bred from elites that are
+//! chosen uniformly at random, using the usual mutation and recombination operators. The filled
+//! cells of the archive show how fitness varies across the behaviour space, which can be plotted
+//! as a heat map, see [GridArchive::write_csv].
+
+use super::{EvolutionError, Evaluator, Genotype, Mutation, Phenotype, Recombination, Stats};
+use super::fitness::{is_comparable, Fitness, InvalidFitnessPolicy};
+use super::logging::{csv_string, LogSink};
+use super::novelty::Behaviour;
+use super::optimiser::{Evaluation, Optimiser};
+use super::random;
+use rand::Rng;
+use std::io::{self, Write};
+use std::fmt;
+
+/// A dimension of the grid: a feature, its range and the number of bins it is divided into.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureRange {
+    name: String,
+    min: f32,
+    max: f32,
+    bins: usize,
+}
+
+impl FeatureRange {
+    /// Creates a dimension that divides the range [min, max] into bins of equal width. Feature
+    /// values outside the range are put in the first or last bin. The name is used as the
+    /// column header when the archive is exported.
+    pub fn new(name: &str, min: f32, max: f32, bins: usize) -> Result<Self, EvolutionError> {
+        if !min.is_finite() || !max.is_finite() || min >= max {
+            return Err(EvolutionError::InvalidParameter("min, max", "finite, with min < max"));
+        }
+        if bins == 0 {
+            return Err(EvolutionError::InvalidParameter("bins", "at least 1"));
+        }
+
+        Ok(FeatureRange {
+            name: String::from(name),
+            min,
+            max,
+            bins,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    /// Returns the bin of the feature value, or `None` when it is NaN.
+    pub fn bin(&self, value: f32) -> Option<usize> {
+        if value.is_nan() {
+            return None;
+        }
+        let position = (value - self.min) / (self.max - self.min) * self.bins as f32;
+
+        Some((position.max(0.0) as usize).min(self.bins - 1))
+    }
+}
+
+/// The fittest solution found in a cell of the archive.
+#[derive(Debug, Clone)]
+pub struct Elite<P: Phenotype, G: Genotype<P>> {
+    pub genotype: G,
+    pub fitness: P::Fitness,
+    pub features: Vec<f32>,
+}
+
+/// A grid of cells over the feature space, each of which holds at most one elite.
+pub struct GridArchive<P: Phenotype, G: Genotype<P>> {
+    dimensions: Vec<FeatureRange>,
+    cells: Vec<Option<Elite<P, G>>>,
+    num_filled: usize,
+}
+
+impl<P: Phenotype, G: Genotype<P>> GridArchive<P, G> {
+    /// Creates an empty archive with a dimension for each feature.
+    pub fn new(dimensions: Vec<FeatureRange>) -> Result<Self, EvolutionError> {
+        if dimensions.is_empty() {
+            return Err(EvolutionError::InvalidParameter("dimensions", "at least 1"));
+        }
+        let num_cells = dimensions.iter().map(FeatureRange::bins).product();
+
+        Ok(GridArchive {
+            dimensions,
+            cells: (0..num_cells).map(|_| None).collect(),
+            num_filled: 0,
+        })
+    }
+
+    pub fn dimensions(&self) -> &[FeatureRange] {
+        &self.dimensions
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn num_filled(&self) -> usize {
+        self.num_filled
+    }
+
+    /// The fraction of cells that hold an elite.
+    pub fn coverage(&self) -> f64 {
+        self.num_filled as f64 / self.cells.len() as f64
+    }
+
+    /// The quality-diversity score: the sum over all elites of how much fitter they are than the
+    /// baseline. The baseline should be the worst fitness possible, so that filling a cell
+    /// never lowers the score.
+    pub fn qd_score(&self, baseline: f64) -> f64 {
+        self.elites().map(
+            |(_, elite)| P::DIRECTION.difference(elite.fitness.to_f64(), baseline)
+        ).sum()
+    }
+
+    /// The elite with the best fitness.
+    pub fn best(&self) -> Option<&Elite<P, G>> {
+        self.elites().map(|(_, elite)| elite).reduce(|best, elite| {
+            if P::DIRECTION.is_better(elite.fitness, best.fitness) { elite } else { best }
+        })
+    }
+
+    /// Returns the cell that the features fall in, or `None` when they cannot be placed. The
+    /// cell is given as the bin in each dimension.
+    pub fn cell(&self, features: &[f32]) -> Option<Vec<usize>> {
+        if features.len() != self.dimensions.len() {
+            return None;
+        }
+
+        self.dimensions.iter().zip(features).map(|(dimension, value)| dimension.bin(*value))
+            .collect()
+    }
+
+    /// Returns the elite in the given cell, if any.
+    pub fn get(&self, cell: &[usize]) -> Option<&Elite<P, G>> {
+        self.index(cell).and_then(|index| self.cells[index].as_ref())
+    }
+
+    /// The filled cells and their elites.
+    pub fn elites(&self) -> impl Iterator<Item = (Vec<usize>, &Elite<P, G>)> + '_ {
+        self.cells.iter().enumerate().filter_map(
+            move |(index, elite)| elite.as_ref().map(|elite| (self.coordinates(index), elite))
+        )
+    }
+
+    /// Adds the solution when its cell is empty, or when it is fitter than the elite in its
+    /// cell. Returns whether it was added. Solutions with an invalid fitness, or features that
+    /// cannot be placed, are never added.
+    pub fn insert(&mut self, genotype: G, fitness: P::Fitness, features: Vec<f32>) -> bool {
+        if !is_comparable(&fitness) {
+            return false;
+        }
+        let index = match self.cell(&features).and_then(|cell| self.index(&cell)) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        match &self.cells[index] {
+            Some(elite) if !P::DIRECTION.is_better(fitness, elite.fitness) => return false,
+            Some(_) => {},
+            None => self.num_filled += 1,
+        }
+        self.cells[index] = Some(Elite { genotype, fitness, features });
+
+        true
+    }
+
+    /// Writes the elites as CSV, for plotting the archive as a heat map. Each row contains the
+    /// bin in each dimension, the features, the fitness and the genotype of one elite.
+    pub fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        let names: Vec<String> = self.dimensions.iter().map(|dimension| csv_string(&dimension.name))
+            .collect();
+        let bins: Vec<String> = self.dimensions.iter().map(
+            |dimension| csv_string(&format!("{}_bin", dimension.name))
+        ).collect();
+        writeln!(out, "{},{},fitness,genotype", bins.join(","), names.join(","))?;
+
+        for (cell, elite) in self.elites() {
+            let cell: Vec<String> = cell.iter().map(usize::to_string).collect();
+            let features: Vec<String> = elite.features.iter().map(f32::to_string).collect();
+            writeln!(
+                out, "{},{},{},{}",
+                cell.join(","), features.join(","), elite.fitness.to_f64(),
+                csv_string(&format!("{:?}", elite.genotype))
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The index of a cell in row-major order, or `None` when it is outside the grid.
+    fn index(&self, cell: &[usize]) -> Option<usize> {
+        if cell.len() != self.dimensions.len() {
+            return None;
+        }
+
+        self.dimensions.iter().zip(cell).try_fold(0, |index, (dimension, bin)| {
+            (*bin < dimension.bins).then_some(index * dimension.bins + bin)
+        })
+    }
+
+    fn coordinates(&self, mut index: usize) -> Vec<usize> {
+        let mut cell = vec![0; self.dimensions.len()];
+        for (bin, dimension) in cell.iter_mut().zip(self.dimensions.iter()).rev() {
+            *bin = index % dimension.bins;
+            index /= dimension.bins;
+        }
+
+        cell
+    }
+
+    /// Chooses an elite uniformly at random.
+    fn random_elite(&self) -> Option<&Elite<P, G>> {
+        if self.num_filled == 0 {
+            return None;
+        }
+        let n = random::rng().gen_range(0..self.num_filled);
+
+        self.elites().nth(n).map(|(_, elite)| elite)
+    }
+}
+
+impl<P: Phenotype, G: Genotype<P>> fmt::Debug for GridArchive<P, G> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GridArchive")
+            .field("dimensions", &self.dimensions)
+            .field("num_filled", &self.num_filled)
+            .field("num_cells", &self.cells.len())
+            .finish()
+    }
+}
+
+/// The MAP-Elites algorithm. Each generation, it breeds a batch of solutions from the elites in
+/// the archive, evaluates them and adds them to the archive where they are fitter. The first
+/// generation consists of random solutions instead.
+pub struct MapElites<P, G, M, R>
+where
+    P: Behaviour,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G>,
+    R: Recombination<Genotype = G>,
+{
+    factory: Box<dyn Fn() -> G>,
+    mutation: M,
+    recombination: R,
+    recombination_prob: f32,
+    batch_size: usize,
+    initial_size: usize,
+    archive: GridArchive<P, G>,
+    generation: usize,
+    evaluation: Evaluation<P>,
+}
+
+impl<P, G, M, R> MapElites<P, G, M, R>
+where
+    P: Behaviour,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G>,
+    R: Recombination<Genotype = G>,
+{
+    /// Creates the algorithm with an initially empty archive. Random solutions are created by
+    /// the factory. By default, it breeds 100 solutions per generation, of which half are
+    /// recombined. All of them are mutated.
+    pub fn new(
+        archive: GridArchive<P, G>, factory: impl Fn() -> G + 'static, mutation: M, recombination: R
+    ) -> Self {
+        MapElites {
+            factory: Box::new(factory),
+            mutation,
+            recombination,
+            recombination_prob: 0.5,
+            batch_size: 100,
+            initial_size: 100,
+            archive,
+            generation: 0,
+            evaluation: Evaluation::new(),
+        }
+    }
+
+    /// Sets the number of solutions that are bred per generation, and the number of random
+    /// solutions in the first generation. Both should be at least 1.
+    pub fn with_batch_size(
+        mut self, batch_size: usize, initial_size: usize
+    ) -> Result<Self, EvolutionError> {
+        if batch_size == 0 {
+            return Err(EvolutionError::InvalidParameter("batch_size", "at least 1"));
+        }
+        if initial_size == 0 {
+            return Err(EvolutionError::InvalidParameter("initial_size", "at least 1"));
+        }
+        self.batch_size = batch_size;
+        self.initial_size = initial_size;
+        Ok(self)
+    }
+
+    /// Sets the probability that a solution is bred by recombining two elites, rather than by
+    /// copying one. It should be in range [0, 1].
+    pub fn with_recombination_prob(
+        mut self, recombination_prob: f32
+    ) -> Result<Self, EvolutionError> {
+        if !(0.0..=1.0).contains(&recombination_prob) {
+            return Err(EvolutionError::InvalidParameter("recombination_prob", "in range [0, 1]"));
+        }
+        self.recombination_prob = recombination_prob;
+        Ok(self)
+    }
+
+    /// Sets the evaluator that is used to determine the fitness of solutions. See [Evaluator].
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator<P>>) {
+        self.evaluation.set_evaluator(evaluator);
+    }
+
+    /// Adds a sink that the run log is written to.
+    pub fn add_log_sink(&mut self, sink: Box<dyn LogSink>) {
+        self.evaluation.add_log_sink(sink);
+    }
+
+    /// Sets what to do with evaluations that give an invalid fitness, such as NaN. By default,
+    /// such solutions are not added to the archive. See [InvalidFitnessPolicy].
+    pub fn set_invalid_fitness_policy(&mut self, policy: InvalidFitnessPolicy) {
+        self.evaluation.set_invalid_fitness_policy(policy);
+    }
+
+    pub fn archive(&self) -> &GridArchive<P, G> {
+        &self.archive
+    }
+
+    /// The number of generations run.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    fn breed(&self) -> Result<Vec<G>, EvolutionError> {
+        let mut rng = random::rng();
+
+        (0..self.batch_size).map(|_| {
+            let parent1 = self.archive.random_elite().ok_or(EvolutionError::NoPopulation)?;
+            let mut child = if rng.gen::<f32>() < self.recombination_prob {
+                let parent2 = self.archive.random_elite().ok_or(EvolutionError::NoPopulation)?;
+                self.recombination.recombine(&parent1.genotype, &parent2.genotype)?
+            } else {
+                parent1.genotype.clone()
+            };
+            self.mutation.mutate(&mut child);
+            Ok(child)
+        }).collect()
+    }
+}
+
+impl<P, G, M, R> fmt::Debug for MapElites<P, G, M, R>
+where
+    P: Behaviour,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G> + fmt::Debug,
+    R: Recombination<Genotype = G> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MapElites")
+            .field("mutation", &self.mutation)
+            .field("recombination", &self.recombination)
+            .field("batch_size", &self.batch_size)
+            .field("generation", &self.generation)
+            .field("archive", &self.archive)
+            .finish()
+    }
+}
+
+impl<P, G, M, R> Optimiser for MapElites<P, G, M, R>
+where
+    P: Behaviour,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G>,
+    R: Recombination<Genotype = G>,
+{
+    /// Creates random solutions in the first generation, or when the archive is still empty.
+    /// Otherwise, it breeds them from the elites. The statistics cover the solutions that were
+    /// evaluated in the generation.
+    fn step(&mut self) -> Result<Stats, EvolutionError> {
+        let solutions = if self.archive.num_filled() == 0 {
+            (0..self.initial_size).map(|_| (self.factory)()).collect()
+        } else {
+            self.breed()?
+        };
+
+        let phenotypes: Vec<P> = solutions.iter().map(|solution| solution.express()).collect();
+        let fitnesses = self.evaluation.evaluate(&phenotypes)?;
+
+        let genotypes: Vec<&G> = solutions.iter().collect();
+        let stats = self.evaluation.stats(&fitnesses, &genotypes, None)?;
+        self.evaluation.log_generation(self.generation, &stats, None)?;
+
+        let evaluated = solutions.into_iter().zip(phenotypes).zip(fitnesses);
+        for ((solution, phenotype), fitness) in evaluated {
+            self.archive.insert(solution, fitness, phenotype.behaviour());
+        }
+        self.generation += 1;
+
+        Ok(stats)
+    }
+
+    fn evaluations(&self) -> usize {
+        self.evaluation.evaluations()
+    }
+}
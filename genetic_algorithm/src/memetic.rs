@@ -0,0 +1,139 @@
+//! Local search, for hybridising the evolutionary algorithm into a memetic algorithm.
+//!
+//! After breeding, children can be improved by a local search before they join the population.
+//! The evolutionary algorithm explores the search space, while the local search exploits the
+//! neighbourhood of each child.
+
+use super::{Genotype, Phenotype};
+use super::random;
+use rand::Rng;
+use std::fmt;
+
+/// Improves a genotype by searching its neighbourhood.
+pub trait LocalSearch<P: Phenotype, G: Genotype<P>>: fmt::Debug {
+    /// Improves the genotype in place. The fitness of the improved genotype is never worse than
+    /// the fitness of the original one.
+    fn improve(&self, genotype: &mut G) -> Improved<P::Fitness>;
+}
+
+/// The outcome of a local search.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Improved<F> {
+    /// The fitness of the improved genotype.
+    pub fitness: F,
+    /// The number of evaluations that the local search used, including the evaluation of the
+    /// original genotype.
+    pub evaluations: usize,
+}
+
+/// Determines what a child inherits from the local search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LearningMode {
+    /// The improved genotype replaces the original one, so that the improvement is inherited.
+    Lamarckian,
+    /// The original genotype is kept, but it is assigned the fitness of the improved genotype.
+    /// Selection then favours genotypes that are easy to improve, without reducing diversity.
+    Baldwinian,
+}
+
+/// A local search, together with how it is applied to children.
+#[derive(Debug)]
+pub(crate) struct MemeticConfig<P: Phenotype, G: Genotype<P>> {
+    pub(crate) local_search: Box<dyn LocalSearch<P, G>>,
+    pub(crate) mode: LearningMode,
+    /// The probability that the local search is applied to a child.
+    pub(crate) prob: f32,
+}
+
+/// Determines which neighbour a local search moves to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Improvement {
+    /// Moves to the first neighbour that is fitter.
+    First,
+    /// Moves to the fittest neighbour, when it is fitter.
+    Best,
+}
+
+/// Moves a genotype to fitter neighbours, within an evaluation budget. The neighbours are given
+/// by a fixed number of moves, which can be applied and undone.
+pub(crate) struct HillClimber {
+    improvement: Improvement,
+    max_evaluations: usize,
+    evaluations: usize,
+}
+
+impl HillClimber {
+    pub(crate) fn new(improvement: Improvement, max_evaluations: usize) -> Self {
+        HillClimber {
+            improvement,
+            max_evaluations,
+            evaluations: 0,
+        }
+    }
+
+    pub(crate) fn has_budget(&self) -> bool {
+        self.evaluations < self.max_evaluations
+    }
+
+    pub(crate) fn improved<F>(&self, fitness: F) -> Improved<F> {
+        Improved {
+            fitness,
+            evaluations: self.evaluations,
+        }
+    }
+
+    pub(crate) fn evaluate<P: Phenotype, G: Genotype<P>>(&mut self, genotype: &G) -> P::Fitness {
+        self.evaluations += 1;
+        genotype.express().evaluate()
+    }
+
+    /// Climbs until no move improves the fitness, or the budget is used up. Returns the fitness
+    /// of the final genotype. Moves are tried starting at a random one, so that the search is not
+    /// biased towards the first moves.
+    ///
+    /// Applying a move returns what is needed to undo it again.
+    pub(crate) fn climb<P, G, U>(
+        &mut self, genotype: &mut G, mut fitness: P::Fitness, num_moves: usize,
+        apply: impl Fn(&mut G, usize) -> U, undo: impl Fn(&mut G, usize, U)
+    ) -> P::Fitness
+    where
+        P: Phenotype,
+        G: Genotype<P>,
+    {
+        if num_moves == 0 {
+            return fitness;
+        }
+
+        loop {
+            let start = random::rng().gen_range(0..num_moves);
+            let mut best: Option<(usize, P::Fitness)> = None;
+
+            for offset in 0..num_moves {
+                if !self.has_budget() {
+                    break;
+                }
+
+                let i = (start + offset) % num_moves;
+                let undo_info = apply(genotype, i);
+                let neighbour_fitness = self.evaluate(genotype);
+                undo(genotype, i, undo_info);
+
+                let best_fitness = best.map_or(fitness, |(_, best_fitness)| best_fitness);
+                if P::DIRECTION.is_better(neighbour_fitness, best_fitness) {
+                    best = Some((i, neighbour_fitness));
+                    if self.improvement == Improvement::First {
+                        break;
+                    }
+                }
+            }
+
+            match best {
+                Some((i, best_fitness)) => {
+                    apply(genotype, i);
+                    fitness = best_fitness;
+                },
+                None => return fitness,
+            }
+        }
+    }
+}
@@ -0,0 +1,938 @@
+//! Neuroevolution, which evolves neural networks.
+//!
+//! With a fixed topology, the structure of the network is given and only its weights evolve. A
+//! [FeedForwardNetwork] takes its weights from a real-valued genotype, such as a
+//! [RealChromosome](super::real::RealChromosome), so that the evolutionary algorithm can be used
+//! as is.
+//!
+//! NEAT (NeuroEvolution of Augmenting Topologies) evolves the structure as well. It starts with
+//! networks that connect the inputs directly to the outputs, and grows them by mutations that
+//! add nodes and connections. Each structural change is identified by an innovation number, so
+//! that genomes can be aligned for crossover and compared. The population is divided into
+//! species of similar genomes, which compete mainly among themselves. This protects new
+//! structures, which often need some generations to optimise their weights. See [Neat].
+
+use super::{EvolutionError, Evaluator, Genotype, Phenotype, Stats};
+use super::fitness::{is_comparable, Direction, Fitness, InvalidFitnessPolicy};
+use super::logging::LogSink;
+use super::optimiser::{Evaluation, Optimiser};
+use super::real::standard_normal;
+use super::random;
+use rand::Rng;
+use std::collections::HashMap;
+use std::{cmp, fmt};
+
+/// The activation function of a neuron.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    Identity,
+    /// The logistic function, with outputs in range <0, 1>.
+    Sigmoid,
+    /// The logistic function with a steeper slope, `1 / (1 + e^(-4.9x))`, as used by NEAT.
+    SteepenedSigmoid,
+    Tanh,
+    Relu,
+}
+
+impl Activation {
+    pub fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Identity => x,
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::SteepenedSigmoid => 1.0 / (1.0 + (-4.9 * x).exp()),
+            Activation::Tanh => x.tanh(),
+            Activation::Relu => x.max(0.0),
+        }
+    }
+}
+
+/// The structure of a fully connected feedforward network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topology {
+    layers: Vec<usize>,
+    hidden: Activation,
+    output: Activation,
+}
+
+impl Topology {
+    /// Creates a topology with the given number of neurons per layer, starting with the inputs
+    /// and ending with the outputs. There should be at least two layers, which are not empty.
+    /// The neurons of the hidden layers and of the output layer use the given activations.
+    pub fn new(
+        layers: &[usize], hidden: Activation, output: Activation
+    ) -> Result<Self, EvolutionError> {
+        if layers.len() < 2 {
+            return Err(EvolutionError::InvalidParameter("layers", "at least 2"));
+        }
+        if layers.contains(&0) {
+            return Err(EvolutionError::InvalidParameter("layers", "all at least 1"));
+        }
+
+        Ok(Topology {
+            layers: layers.to_vec(),
+            hidden,
+            output,
+        })
+    }
+
+    pub fn layers(&self) -> &[usize] {
+        &self.layers
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.layers[0]
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.layers[self.layers.len() - 1]
+    }
+
+    /// The number of weights, including a bias for every neuron that is not an input.
+    pub fn num_weights(&self) -> usize {
+        self.layers.windows(2).map(|layers| (layers[0] + 1) * layers[1]).sum()
+    }
+}
+
+/// A fully connected feedforward network with a fixed topology.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedForwardNetwork {
+    topology: Topology,
+    weights: Vec<f32>,
+}
+
+impl FeedForwardNetwork {
+    /// Creates a network with the given weights. Neurons are stored layer by layer. Each neuron
+    /// has its bias, followed by the weights of the neurons in the previous layer. The number of
+    /// weights should match the topology, see [Topology::num_weights].
+    pub fn new(topology: Topology, weights: &[f32]) -> Result<Self, EvolutionError> {
+        if weights.len() != topology.num_weights() {
+            return Err(EvolutionError::LengthMismatch(weights.len(), topology.num_weights()));
+        }
+
+        Ok(FeedForwardNetwork {
+            topology,
+            weights: weights.to_vec(),
+        })
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// Computes the outputs of the network for the given inputs.
+    ///
+    /// Panics when the number of inputs does not match the topology.
+    pub fn activate(&self, inputs: &[f32]) -> Vec<f32> {
+        assert_eq!(inputs.len(), self.topology.num_inputs(), "Wrong number of inputs");
+
+        let layers = &self.topology.layers;
+        let mut values = inputs.to_vec();
+        let mut offset = 0;
+
+        for (i, &size) in layers.iter().enumerate().skip(1) {
+            let activation = if i == layers.len() - 1 {
+                self.topology.output
+            } else {
+                self.topology.hidden
+            };
+            let len = (values.len() + 1) * size;
+
+            values = self.weights[offset..offset + len].chunks(values.len() + 1).map(|neuron| {
+                let sum: f32 = neuron[1..].iter().zip(values.iter()).map(|(w, v)| w * v).sum();
+                activation.apply(neuron[0] + sum)
+            }).collect();
+            offset += len;
+        }
+
+        values
+    }
+}
+
+/// The role of a node in a NEAT network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Input,
+    /// A node whose value is always 1.
+    Bias,
+    Hidden,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeGene {
+    pub id: usize,
+    pub kind: NodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionGene {
+    /// Identifies the connection between two nodes. It is the same in all genomes of a run.
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+    /// Disabled connections are not part of the network, but can be enabled again by crossover.
+    pub enabled: bool,
+}
+
+/// Assigns innovation numbers to connections and ids to new nodes, so that the same structural
+/// change gets the same number in all genomes.
+#[derive(Debug, Default)]
+struct Innovations {
+    next_innovation: usize,
+    next_node: usize,
+    connections: HashMap<(usize, usize), usize>,
+    // The nodes that were created by splitting each connection, by innovation number
+    splits: HashMap<usize, Vec<usize>>,
+}
+
+impl Innovations {
+    fn connection(&mut self, from: usize, to: usize) -> usize {
+        let next_innovation = &mut self.next_innovation;
+
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            *next_innovation += 1;
+            *next_innovation - 1
+        })
+    }
+
+    /// Returns the node that splits the connection. It is the same node as in other genomes that
+    /// split the connection, unless the genome already contains that node.
+    fn split(&mut self, innovation: usize, genome: &NeatGenome) -> usize {
+        let nodes = self.splits.entry(innovation).or_default();
+
+        match nodes.iter().find(|&&node| !genome.has_node(node)) {
+            Some(&node) => node,
+            None => {
+                nodes.push(self.next_node);
+                self.next_node += 1;
+                self.next_node - 1
+            },
+        }
+    }
+}
+
+/// The genome of a NEAT network. Node ids start with the inputs, followed by the bias and the
+/// outputs. Hidden nodes have higher ids.
+///
+/// Connections never form a cycle, also when disabled connections are included, so the network
+/// is always feedforward.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeatGenome {
+    num_inputs: usize,
+    num_outputs: usize,
+    // Sorted by id
+    nodes: Vec<NodeGene>,
+    // Sorted by innovation number
+    connections: Vec<ConnectionGene>,
+}
+
+impl NeatGenome {
+    /// Creates a genome that connects every input and the bias to every output.
+    fn minimal(num_inputs: usize, num_outputs: usize, innovations: &mut Innovations) -> Self {
+        let mut rng = random::rng();
+        let kind = |id: usize| match id {
+            id if id < num_inputs => NodeKind::Input,
+            id if id == num_inputs => NodeKind::Bias,
+            _ => NodeKind::Output,
+        };
+        let nodes: Vec<NodeGene> = (0..num_inputs + 1 + num_outputs).map(
+            |id| NodeGene { id, kind: kind(id) }
+        ).collect();
+
+        let mut connections = Vec::with_capacity((num_inputs + 1) * num_outputs);
+        for to in num_inputs + 1..nodes.len() {
+            for from in 0..=num_inputs {
+                connections.push(ConnectionGene {
+                    innovation: innovations.connection(from, to),
+                    from,
+                    to,
+                    weight: standard_normal(&mut rng),
+                    enabled: true,
+                });
+            }
+        }
+        connections.sort_by_key(|connection| connection.innovation);
+
+        NeatGenome {
+            num_inputs,
+            num_outputs,
+            nodes,
+            connections,
+        }
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_outputs(&self) -> usize {
+        self.num_outputs
+    }
+
+    pub fn num_hidden(&self) -> usize {
+        self.nodes.len() - self.num_inputs - 1 - self.num_outputs
+    }
+
+    /// The nodes, ordered by id.
+    pub fn nodes(&self) -> &[NodeGene] {
+        &self.nodes
+    }
+
+    /// The connections, ordered by innovation number.
+    pub fn connections(&self) -> &[ConnectionGene] {
+        &self.connections
+    }
+
+    fn has_node(&self, id: usize) -> bool {
+        self.nodes.binary_search_by_key(&id, |node| node.id).is_ok()
+    }
+
+    fn insert_connection(&mut self, connection: ConnectionGene) {
+        let index = self.connections.partition_point(
+            |other| other.innovation < connection.innovation
+        );
+        self.connections.insert(index, connection);
+    }
+
+    /// Whether node `to` can be reached from node `from` by following connections.
+    fn reaches(&self, from: usize, to: usize) -> bool {
+        let mut stack = vec![from];
+        let mut visited = vec![from];
+
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            for connection in self.connections.iter().filter(|connection| connection.from == node) {
+                if !visited.contains(&connection.to) {
+                    visited.push(connection.to);
+                    stack.push(connection.to);
+                }
+            }
+        }
+
+        false
+    }
+
+    fn mutate_weights(&mut self, config: &NeatConfig) {
+        let mut rng = random::rng();
+
+        for connection in self.connections.iter_mut() {
+            if rng.gen::<f32>() < config.weight_replace_prob {
+                connection.weight = standard_normal(&mut rng);
+            } else {
+                connection.weight += config.weight_sigma * standard_normal(&mut rng);
+            }
+        }
+    }
+
+    /// Adds a connection between two nodes that are not connected yet, without creating a
+    /// cycle. Returns false when no such connection was found in a limited number of attempts.
+    fn add_connection(&mut self, innovations: &mut Innovations) -> bool {
+        let mut rng = random::rng();
+        // Inputs and the bias come first, and cannot be the target of a connection
+        let num_sources = self.num_inputs + 1;
+
+        for _ in 0..20 {
+            let from = self.nodes[rng.gen_range(0..self.nodes.len())].id;
+            let to = self.nodes[rng.gen_range(num_sources..self.nodes.len())].id;
+
+            if from == to || self.reaches(to, from) || self.connections.iter().any(
+                |connection| connection.from == from && connection.to == to
+            ) {
+                continue;
+            }
+
+            self.insert_connection(ConnectionGene {
+                innovation: innovations.connection(from, to),
+                from,
+                to,
+                weight: standard_normal(&mut rng),
+                enabled: true,
+            });
+            return true;
+        }
+
+        false
+    }
+
+    /// Splits an enabled connection in two by adding a node in the middle. The connection into
+    /// the new node has weight 1, and the one out of it keeps the original weight, so that the
+    /// network initially behaves much like before. Returns false when there is no enabled
+    /// connection.
+    fn add_node(&mut self, innovations: &mut Innovations) -> bool {
+        let enabled: Vec<usize> = (0..self.connections.len())
+            .filter(|&i| self.connections[i].enabled)
+            .collect();
+        if enabled.is_empty() {
+            return false;
+        }
+
+        let i = enabled[random::rng().gen_range(0..enabled.len())];
+        self.connections[i].enabled = false;
+        let ConnectionGene { innovation, from, to, weight, .. } = self.connections[i];
+
+        let node = innovations.split(innovation, self);
+        let index = self.nodes.partition_point(|other| other.id < node);
+        self.nodes.insert(index, NodeGene {
+            id: node,
+            kind: NodeKind::Hidden,
+        });
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(from, node),
+            from,
+            to: node,
+            weight: 1.0,
+            enabled: true,
+        });
+        self.insert_connection(ConnectionGene {
+            innovation: innovations.connection(node, to),
+            from: node,
+            to,
+            weight,
+            enabled: true,
+        });
+
+        true
+    }
+
+    /// Crosses two genomes, aligning their connections by innovation number. Matching
+    /// connections are inherited from either parent at random. Connections that only one parent
+    /// has are inherited from the fitter parent, so the child has the structure of that parent.
+    fn crossover(fitter: &Self, other: &Self) -> Self {
+        let mut rng = random::rng();
+
+        let connections = fitter.connections.iter().map(|connection| {
+            let matching = other.connections.binary_search_by_key(
+                &connection.innovation, |other| other.innovation
+            ).ok().map(|i| &other.connections[i]);
+
+            match matching {
+                Some(matching) => {
+                    let mut child = if rng.gen() { connection } else { matching }.clone();
+                    // A connection that is disabled in either parent is likely to stay disabled
+                    if !connection.enabled || !matching.enabled {
+                        child.enabled = rng.gen::<f32>() < 0.25;
+                    }
+                    child
+                },
+                None => connection.clone(),
+            }
+        }).collect();
+
+        NeatGenome {
+            connections,
+            ..fitter.clone()
+        }
+    }
+
+    /// The compatibility distance, which determines whether genomes belong to the same species.
+    /// It combines the number of connections that do not match, and the average weight
+    /// difference of the connections that do.
+    pub fn distance(&self, other: &Self, config: &NeatConfig) -> f32 {
+        let (a, b) = (&self.connections, &other.connections);
+        let (mut i, mut j) = (0, 0);
+        let (mut num_matching, mut num_disjoint, mut weight_difference) = (0, 0, 0.0);
+
+        while i < a.len() && j < b.len() {
+            match a[i].innovation.cmp(&b[j].innovation) {
+                cmp::Ordering::Equal => {
+                    num_matching += 1;
+                    weight_difference += (a[i].weight - b[j].weight).abs();
+                    i += 1;
+                    j += 1;
+                },
+                cmp::Ordering::Less => {
+                    num_disjoint += 1;
+                    i += 1;
+                },
+                cmp::Ordering::Greater => {
+                    num_disjoint += 1;
+                    j += 1;
+                },
+            }
+        }
+        // Connections beyond the last innovation of the other genome are excess
+        let num_excess = (a.len() - i) + (b.len() - j);
+
+        // Small genomes are not normalised by their size
+        let size = match a.len().max(b.len()) {
+            size if size < 20 => 1.0,
+            size => size as f32,
+        };
+        let mean_difference = if num_matching > 0 {
+            weight_difference / num_matching as f32
+        } else {
+            0.0
+        };
+
+        (config.excess_coefficient * num_excess as f32
+            + config.disjoint_coefficient * num_disjoint as f32) / size
+            + config.weight_coefficient * mean_difference
+    }
+
+    /// Builds the network that the genome describes, using the given activation for the hidden
+    /// and output nodes.
+    pub fn network(&self, activation: Activation) -> NeatNetwork {
+        let index = |id: usize| self.nodes.binary_search_by_key(&id, |node| node.id).unwrap();
+        let enabled: Vec<&ConnectionGene> = self.connections.iter()
+            .filter(|connection| connection.enabled)
+            .collect();
+
+        // Orders the nodes such that each one comes after the nodes it receives input from
+        let mut num_incoming = vec![0; self.nodes.len()];
+        for connection in enabled.iter() {
+            num_incoming[index(connection.to)] += 1;
+        }
+        let mut ready: Vec<usize> = (0..self.nodes.len()).filter(|&i| num_incoming[i] == 0)
+            .collect();
+        let mut neurons = Vec::with_capacity(self.nodes.len());
+
+        while let Some(i) = ready.pop() {
+            let id = self.nodes[i].id;
+            if id > self.num_inputs {
+                let incoming = enabled.iter().filter(|connection| connection.to == id)
+                    .map(|connection| (index(connection.from), connection.weight))
+                    .collect();
+                neurons.push((i, incoming));
+            }
+            for connection in enabled.iter().filter(|connection| connection.from == id) {
+                let to = index(connection.to);
+                num_incoming[to] -= 1;
+                if num_incoming[to] == 0 {
+                    ready.push(to);
+                }
+            }
+        }
+
+        NeatNetwork {
+            num_inputs: self.num_inputs,
+            num_nodes: self.nodes.len(),
+            activation,
+            neurons,
+            outputs: (self.num_inputs + 1..self.num_inputs + 1 + self.num_outputs).collect(),
+        }
+    }
+}
+
+/// A network built from a [NeatGenome].
+#[derive(Debug, Clone)]
+pub struct NeatNetwork {
+    num_inputs: usize,
+    num_nodes: usize,
+    activation: Activation,
+    // The nodes that are not inputs, ordered such that they can be computed in turn, with the
+    // nodes they receive input from and the weights
+    neurons: Vec<(usize, Vec<(usize, f32)>)>,
+    outputs: Vec<usize>,
+}
+
+impl NeatNetwork {
+    /// Computes the outputs of the network for the given inputs.
+    ///
+    /// Panics when the number of inputs does not match the genome.
+    pub fn activate(&self, inputs: &[f32]) -> Vec<f32> {
+        assert_eq!(inputs.len(), self.num_inputs, "Wrong number of inputs");
+
+        let mut values = vec![0.0; self.num_nodes];
+        values[..self.num_inputs].copy_from_slice(inputs);
+        values[self.num_inputs] = 1.0;
+
+        for (node, incoming) in self.neurons.iter() {
+            let sum: f32 = incoming.iter().map(|(from, weight)| values[*from] * weight).sum();
+            values[*node] = self.activation.apply(sum);
+        }
+
+        self.outputs.iter().map(|&output| values[output]).collect()
+    }
+}
+
+/// The parameters of [Neat]. The defaults follow the original NEAT paper.
+#[derive(Debug, Clone)]
+pub struct NeatConfig {
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+    /// The probability that a child is created by crossover. Otherwise, it is a mutated copy of
+    /// its parent.
+    pub crossover_prob: f32,
+    /// The probability that the weights of a child are mutated.
+    pub weight_mutation_prob: f32,
+    /// The standard deviation of the noise that is added to weights when they are mutated.
+    pub weight_sigma: f32,
+    /// The probability that a weight is replaced by a new random one when it is mutated.
+    pub weight_replace_prob: f32,
+    /// The probability that a connection is added to a child.
+    pub add_connection_prob: f32,
+    /// The probability that a node is added to a child.
+    pub add_node_prob: f32,
+    /// Genomes whose compatibility distance to the representative of a species is below this
+    /// threshold belong to the species. See [NeatGenome::distance].
+    pub compatibility_threshold: f32,
+    pub excess_coefficient: f32,
+    pub disjoint_coefficient: f32,
+    pub weight_coefficient: f32,
+    /// Species whose best fitness has not improved for this many generations no longer get
+    /// offspring, unless they are one of the two best species.
+    pub max_stagnation: usize,
+    /// The fraction of each species, fittest first, that may become a parent.
+    pub survival_threshold: f32,
+    /// The fittest genome of each species with at least this many members is copied unchanged
+    /// into the next generation.
+    pub elitism_species_size: usize,
+}
+
+impl NeatConfig {
+    /// Creates a configuration for networks with the given number of inputs and outputs.
+    pub fn new(num_inputs: usize, num_outputs: usize) -> Self {
+        NeatConfig {
+            num_inputs,
+            num_outputs,
+            crossover_prob: 0.75,
+            weight_mutation_prob: 0.8,
+            weight_sigma: 0.5,
+            weight_replace_prob: 0.1,
+            add_connection_prob: 0.05,
+            add_node_prob: 0.03,
+            compatibility_threshold: 3.0,
+            excess_coefficient: 1.0,
+            disjoint_coefficient: 1.0,
+            weight_coefficient: 0.4,
+            max_stagnation: 15,
+            survival_threshold: 0.2,
+            elitism_species_size: 5,
+        }
+    }
+
+    fn check(&self) -> Result<(), EvolutionError> {
+        if self.num_inputs == 0 {
+            return Err(EvolutionError::InvalidParameter("num_inputs", "at least 1"));
+        }
+        if self.num_outputs == 0 {
+            return Err(EvolutionError::InvalidParameter("num_outputs", "at least 1"));
+        }
+        let probs = [
+            ("crossover_prob", self.crossover_prob),
+            ("weight_mutation_prob", self.weight_mutation_prob),
+            ("weight_replace_prob", self.weight_replace_prob),
+            ("add_connection_prob", self.add_connection_prob),
+            ("add_node_prob", self.add_node_prob),
+        ];
+        for (name, prob) in probs {
+            if !(0.0..=1.0).contains(&prob) {
+                return Err(EvolutionError::InvalidParameter(name, "in range [0, 1]"));
+            }
+        }
+        if !(self.weight_sigma >= 0.0 && self.weight_sigma.is_finite()) {
+            return Err(EvolutionError::InvalidParameter("weight_sigma", "at least 0"));
+        }
+        if self.compatibility_threshold.is_nan() || self.compatibility_threshold <= 0.0 {
+            return Err(EvolutionError::InvalidParameter("compatibility_threshold", "positive"));
+        }
+        if !(self.survival_threshold > 0.0 && self.survival_threshold <= 1.0) {
+            return Err(EvolutionError::InvalidParameter("survival_threshold", "in range <0, 1]"));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Species {
+    representative: NeatGenome,
+    // Indices into the population
+    members: Vec<usize>,
+    best_score: f64,
+    last_improved: usize,
+}
+
+/// Turns a fitness into a score that is higher for fitter genomes. Invalid fitness gets the
+/// lowest score.
+fn score<F: Fitness>(direction: Direction, fitness: F) -> f64 {
+    match (is_comparable(&fitness), direction) {
+        (false, _) => f64::NEG_INFINITY,
+        (true, Direction::Maximise) => fitness.to_f64(),
+        (true, Direction::Minimise) => -fitness.to_f64(),
+    }
+}
+
+/// Splits `total` into parts that are proportional to the weights, using the largest remainder
+/// for rounding.
+fn apportion(weights: &[f64], total: usize) -> Vec<usize> {
+    let sum: f64 = weights.iter().sum();
+    let shares: Vec<f64> = if sum > 0.0 && sum.is_finite() {
+        weights.iter().map(|weight| weight / sum * total as f64).collect()
+    } else {
+        vec![total as f64 / weights.len() as f64; weights.len()]
+    };
+
+    let mut counts: Vec<usize> = shares.iter().map(|share| share.floor() as usize).collect();
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    let remainders: Vec<f64> = shares.iter().map(|share| share - share.floor()).collect();
+    order.sort_by(|&a, &b| remainders[b].total_cmp(&remainders[a]));
+    let remaining = total - counts.iter().sum::<usize>();
+    for &i in order.iter().cycle().take(remaining) {
+        counts[i] += 1;
+    }
+
+    counts
+}
+
+/// NEAT, which evolves the weights and structure of neural networks. See the
+/// [module documentation](self).
+///
+/// The fitness of genomes is shared within their species, so that the number of offspring of a
+/// species depends on its average fitness rather than its size. For this, fitness is shifted such
+/// that the least fit genome of the generation gets zero.
+pub struct Neat<P: Phenotype> {
+    config: NeatConfig,
+    pop_size: usize,
+    innovations: Innovations,
+    population: Vec<NeatGenome>,
+    species: Vec<Species>,
+    generation: usize,
+    best: Option<(NeatGenome, P::Fitness)>,
+    evaluation: Evaluation<P>,
+}
+
+impl<P: Phenotype> Neat<P>
+where
+    NeatGenome: Genotype<P>,
+{
+    /// Creates an algorithm with a population of `pop_size` genomes, which initially connect
+    /// every input directly to every output.
+    pub fn new(pop_size: usize, config: NeatConfig) -> Result<Self, EvolutionError> {
+        if pop_size == 0 {
+            return Err(EvolutionError::InvalidParameter("pop_size", "at least 1"));
+        }
+        config.check()?;
+
+        let mut innovations = Innovations {
+            next_node: config.num_inputs + 1 + config.num_outputs,
+            ..Innovations::default()
+        };
+        let population = (0..pop_size).map(
+            |_| NeatGenome::minimal(config.num_inputs, config.num_outputs, &mut innovations)
+        ).collect();
+
+        Ok(Neat {
+            config,
+            pop_size,
+            innovations,
+            population,
+            species: Vec::new(),
+            generation: 0,
+            best: None,
+            evaluation: Evaluation::new(),
+        })
+    }
+
+    /// Sets the evaluator that is used to determine the fitness of genomes. See [Evaluator].
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator<P>>) {
+        self.evaluation.set_evaluator(evaluator);
+    }
+
+    /// Adds a sink that the statistics of each generation are written to.
+    pub fn add_log_sink(&mut self, sink: Box<dyn LogSink>) {
+        self.evaluation.add_log_sink(sink);
+    }
+
+    /// Sets what to do with evaluations that give an invalid fitness, such as NaN. By default,
+    /// such genomes are considered the least fit. See [InvalidFitnessPolicy].
+    pub fn set_invalid_fitness_policy(&mut self, policy: InvalidFitnessPolicy) {
+        self.evaluation.set_invalid_fitness_policy(policy);
+    }
+
+    pub fn config(&self) -> &NeatConfig {
+        &self.config
+    }
+
+    /// The number of generations run.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The genomes of the next generation, which have not been evaluated yet.
+    pub fn population(&self) -> &[NeatGenome] {
+        &self.population
+    }
+
+    /// The number of species in the last generation.
+    pub fn num_species(&self) -> usize {
+        self.species.len()
+    }
+
+    /// The fittest genome evaluated so far, and its fitness.
+    pub fn best(&self) -> Option<(&NeatGenome, P::Fitness)> {
+        self.best.as_ref().map(|(genome, fitness)| (genome, *fitness))
+    }
+
+    /// Assigns each genome to the first species whose representative is close enough, or to a
+    /// new species. Species without members are removed.
+    fn speciate(&mut self) {
+        for species in self.species.iter_mut() {
+            species.members.clear();
+        }
+
+        for (i, genome) in self.population.iter().enumerate() {
+            let config = &self.config;
+            match self.species.iter_mut().find(|species| {
+                genome.distance(&species.representative, config) < config.compatibility_threshold
+            }) {
+                Some(species) => species.members.push(i),
+                None => self.species.push(Species {
+                    representative: genome.clone(),
+                    members: vec![i],
+                    best_score: f64::NEG_INFINITY,
+                    last_improved: self.generation,
+                }),
+            }
+        }
+
+        self.species.retain(|species| !species.members.is_empty());
+    }
+
+    /// Removes species that stopped improving, except for the two best ones.
+    fn remove_stagnant_species(&mut self, scores: &[f64]) {
+        for species in self.species.iter_mut() {
+            let best_score = species.members.iter().map(|&i| scores[i])
+                .fold(f64::NEG_INFINITY, f64::max);
+            if best_score > species.best_score {
+                species.best_score = best_score;
+                species.last_improved = self.generation;
+            }
+        }
+
+        self.species.sort_by(|a, b| b.best_score.total_cmp(&a.best_score));
+        let (generation, max_stagnation) = (self.generation, self.config.max_stagnation);
+        let mut rank = 0;
+        self.species.retain(|species| {
+            rank += 1;
+            rank <= 2 || generation - species.last_improved < max_stagnation
+        });
+    }
+
+    fn mutate(&mut self, genome: &mut NeatGenome) {
+        let mut rng = random::rng();
+
+        if rng.gen::<f32>() < self.config.weight_mutation_prob {
+            genome.mutate_weights(&self.config);
+        }
+        if rng.gen::<f32>() < self.config.add_connection_prob {
+            genome.add_connection(&mut self.innovations);
+        }
+        if rng.gen::<f32>() < self.config.add_node_prob {
+            genome.add_node(&mut self.innovations);
+        }
+    }
+
+    /// Breeds the next generation. Each species gets a number of offspring in proportion to its
+    /// shared fitness.
+    fn reproduce(&mut self, scores: &[f64]) -> Vec<NeatGenome> {
+        let min_score = scores.iter().copied().filter(|score| score.is_finite())
+            .fold(f64::INFINITY, f64::min);
+        let adjusted = |i: usize| match scores[i] {
+            score if score.is_finite() => score - min_score,
+            score if score > 0.0 => f64::MAX,
+            _ => 0.0,
+        };
+        let shared: Vec<f64> = self.species.iter().map(|species| {
+            species.members.iter().map(|&i| adjusted(i)).sum::<f64>()
+                / species.members.len() as f64
+        }).collect();
+        let num_offspring = apportion(&shared, self.pop_size);
+
+        let mut rng = random::rng();
+        let mut next = Vec::with_capacity(self.pop_size);
+        for (s, &num_offspring) in num_offspring.iter().enumerate() {
+            let mut members = self.species[s].members.clone();
+            members.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+            let mut num_children = num_offspring;
+
+            if num_children > 0 && members.len() >= self.config.elitism_species_size {
+                next.push(self.population[members[0]].clone());
+                num_children -= 1;
+            }
+
+            let num_parents = ((members.len() as f32 * self.config.survival_threshold).ceil()
+                as usize).max(1);
+            let parents = &members[..num_parents];
+            for _ in 0..num_children {
+                let parent1 = parents[rng.gen_range(0..parents.len())];
+                let mut child = if parents.len() > 1
+                    && rng.gen::<f32>() < self.config.crossover_prob
+                {
+                    let parent2 = parents[rng.gen_range(0..parents.len())];
+                    let (fitter, other) = if scores[parent2] > scores[parent1] {
+                        (parent2, parent1)
+                    } else {
+                        (parent1, parent2)
+                    };
+                    NeatGenome::crossover(&self.population[fitter], &self.population[other])
+                } else {
+                    self.population[parent1].clone()
+                };
+                self.mutate(&mut child);
+                next.push(child);
+            }
+
+            // The representative for the next generation is a random member of this one
+            let representative = members[rng.gen_range(0..members.len())];
+            self.species[s].representative = self.population[representative].clone();
+        }
+
+        next
+    }
+}
+
+impl<P: Phenotype> fmt::Debug for Neat<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Neat")
+            .field("pop_size", &self.pop_size)
+            .field("generation", &self.generation)
+            .field("num_species", &self.species.len())
+            .finish()
+    }
+}
+
+impl<P: Phenotype> Optimiser for Neat<P>
+where
+    NeatGenome: Genotype<P>,
+{
+    /// Evaluates the population, divides it into species and breeds the next generation.
+    fn step(&mut self) -> Result<Stats, EvolutionError> {
+        let phenotypes: Vec<P> = self.population.iter().map(|genome| genome.express()).collect();
+        // The scores are indexed by the species members, which are positions in the population.
+        // The evaluation fails unless it gives exactly one fitness per genome.
+        let fitnesses = self.evaluation.evaluate(&phenotypes)?;
+
+        let genotypes: Vec<&NeatGenome> = self.population.iter().collect();
+        let stats = self.evaluation.stats(&fitnesses, &genotypes, None)?;
+        self.evaluation.log_generation(self.generation, &stats, None)?;
+
+        for (genome, fitness) in self.population.iter().zip(fitnesses.iter()) {
+            if P::DIRECTION.compare_optional(
+                &Some(*fitness), &self.best.as_ref().map(|(_, best)| *best)
+            ) == cmp::Ordering::Greater {
+                self.best = Some((genome.clone(), *fitness));
+            }
+        }
+
+        let scores: Vec<f64> = fitnesses.iter().map(|fitness| score(P::DIRECTION, *fitness))
+            .collect();
+        self.speciate();
+        self.remove_stagnant_species(&scores);
+        self.population = self.reproduce(&scores);
+        self.generation += 1;
+
+        Ok(stats)
+    }
+
+    fn evaluations(&self) -> usize {
+        self.evaluation.evaluations()
+    }
+}
@@ -0,0 +1,179 @@
+//! Handling of noisy fitness, for objectives that give a different fitness each time the same
+//! phenotype is evaluated, such as stochastic simulations.
+//!
+//! A single evaluation of a noisy objective can make a poor solution look good. It is then
+//! selected too often, and kept as an elite for too long. With noise handling enabled, each
+//! phenotype is evaluated several times, and the samples are kept per individual, see
+//! [FitnessSamples]. Individuals that survive into the next generation are sampled again, so
+//! that the estimate of their fitness keeps improving. The fitness of an individual, which
+//! selection and elitism use, is estimated from its samples, see [Estimate].
+
+use super::EvolutionError;
+use super::fitness::{is_comparable, Direction, Fitness, MeanFitness};
+
+/// How the fitness of an individual is estimated from its samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Estimate {
+    /// The mean of the samples.
+    Mean,
+    /// The pessimistic bound of the confidence interval of the mean, which is the mean minus the
+    /// given number of standard errors when maximising, or plus when minimising. It favours
+    /// individuals whose fitness is known with more certainty. With fewer than two samples the
+    /// variance is unknown, so the bound is the worst fitness possible.
+    ConfidenceBound(f64),
+}
+
+/// Configures how noisy fitness is handled. See the [module](self) documentation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoiseHandling {
+    samples: usize,
+    survivor_samples: usize,
+    estimate: Estimate,
+}
+
+impl NoiseHandling {
+    /// Creates a noise handling that evaluates each new phenotype the given number of times,
+    /// which should be at least 1. By default, survivors are sampled once more each generation,
+    /// and the fitness is estimated by the mean.
+    pub fn new(samples: usize) -> Result<Self, EvolutionError> {
+        if samples == 0 {
+            return Err(EvolutionError::InvalidParameter("samples", "at least 1"));
+        }
+
+        Ok(NoiseHandling {
+            samples,
+            survivor_samples: 1,
+            estimate: Estimate::Mean,
+        })
+    }
+
+    /// Sets the number of additional samples that are taken of each individual that survives
+    /// into the next generation. At 0, survivors keep their estimate.
+    pub fn with_survivor_samples(mut self, survivor_samples: usize) -> Self {
+        self.survivor_samples = survivor_samples;
+        self
+    }
+
+    /// Sets how the fitness is estimated from the samples.
+    pub fn with_estimate(mut self, estimate: Estimate) -> Result<Self, EvolutionError> {
+        if let Estimate::ConfidenceBound(z) = estimate {
+            if !z.is_finite() || z < 0.0 {
+                return Err(EvolutionError::InvalidParameter("z", "finite and at least 0"));
+            }
+        }
+        self.estimate = estimate;
+        Ok(self)
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn survivor_samples(&self) -> usize {
+        self.survivor_samples
+    }
+
+    pub fn estimate(&self) -> Estimate {
+        self.estimate
+    }
+}
+
+/// The running mean and variance of the fitness samples of an individual. Samples with an
+/// invalid fitness are not included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FitnessSamples {
+    count: usize,
+    mean: f64,
+    // The sum of squared differences from the mean, see Welford's algorithm
+    m2: f64,
+    // The generation in which the last samples were taken
+    pub(crate) generation: usize,
+}
+
+impl FitnessSamples {
+    pub(crate) fn new(generation: usize) -> Self {
+        FitnessSamples {
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+            generation,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// The number of valid samples.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The mean of the samples. It is NaN when there are none.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 { f64::NAN } else { self.mean }
+    }
+
+    /// The sample variance. It is zero when there are fewer than two samples.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 { 0.0 } else { self.m2 / (self.count - 1) as f64 }
+    }
+
+    /// The standard error of the mean.
+    pub fn standard_error(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { (self.variance() / self.count as f64).sqrt() }
+    }
+
+    /// Estimates the fitness from the samples.
+    pub fn estimate(&self, estimate: Estimate, direction: Direction) -> f64 {
+        match (estimate, direction) {
+            (Estimate::Mean, _) => self.mean(),
+            (Estimate::ConfidenceBound(_), Direction::Maximise) if self.count < 2 => {
+                f64::NEG_INFINITY
+            },
+            (Estimate::ConfidenceBound(_), Direction::Minimise) if self.count < 2 => {
+                f64::INFINITY
+            },
+            (Estimate::ConfidenceBound(z), Direction::Maximise) => {
+                self.mean() - z * self.standard_error()
+            },
+            (Estimate::ConfidenceBound(z), Direction::Minimise) => {
+                self.mean() + z * self.standard_error()
+            },
+        }
+    }
+}
+
+/// Noise handling, together with the conversion of estimates to the type of fitness.
+#[derive(Debug)]
+pub(crate) struct NoiseConfig<F> {
+    pub(crate) handling: NoiseHandling,
+    from_f64: fn(f64) -> F,
+}
+
+impl<F: MeanFitness> NoiseConfig<F> {
+    pub(crate) fn new(handling: NoiseHandling) -> Self {
+        NoiseConfig {
+            handling,
+            from_f64: F::from_f64,
+        }
+    }
+}
+
+impl<F: Fitness> NoiseConfig<F> {
+    /// Adds the valid values to the samples, and returns the estimated fitness. Returns `None`
+    /// when there are no valid samples.
+    pub(crate) fn record(
+        &self, samples: &mut FitnessSamples, values: &[F], direction: Direction
+    ) -> Option<F> {
+        for value in values.iter().filter(|value| is_comparable(*value)) {
+            samples.add(value.to_f64());
+        }
+
+        let estimate = samples.estimate(self.handling.estimate, direction);
+        (samples.count > 0).then(|| (self.from_f64)(estimate))
+    }
+}
@@ -0,0 +1,255 @@
+//! Novelty search, which selects individuals for behaving differently rather than for being fit.
+//!
+//! On deceptive problems, the fitness leads the search away from the optimum. Novelty search
+//! ignores the objective, or gives it less weight, and rewards individuals whose behaviour
+//! differs from what was seen before. Each phenotype describes its behaviour as a vector of
+//! numbers, see [Behaviour]. The novelty of an individual is the mean distance from its
+//! behaviour to the behaviours of its nearest neighbours, in the population and in an archive of
+//! behaviours of earlier generations.
+
+use super::{
+    EvolutionError, Genotype, Individual, Phenotype, Population, SelectionFactory, Selector
+};
+use super::random;
+use rand::Rng;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+/// A phenotype whose behaviour can be described for novelty search.
+pub trait Behaviour: Phenotype {
+    /// Describes the behaviour of the phenotype, for example where a robot ended up or which
+    /// outputs a program gave. The descriptors of all phenotypes should have the same length.
+    fn behaviour(&self) -> Vec<f32>;
+}
+
+/// Which behaviours are added to the archive each generation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchivePolicy {
+    /// Each individual is added with the given probability.
+    Random(f32),
+    /// Individuals are added when their novelty is at least the given threshold.
+    Threshold(f32),
+}
+
+/// Selection by novelty, or by a weighted mix of novelty and fitness.
+///
+/// Each time the algorithm breeds, the novelty of the parent generation is computed and some of
+/// its behaviours are added to the archive. Parents are then chosen by tournament on the mixed
+/// score. As novelty and fitness have different scales, both are turned into ranks, normalised
+/// to [0, 1], before they are mixed.
+///
+/// The archive is updated as soon as selection starts, so it also changes when breeding fails
+/// afterwards, and breeding from the same generation again adds its behaviours again.
+///
+/// Clones share the archive, so a clone can be kept to inspect it during the run. Elitism is
+/// still based on fitness alone.
+#[derive(Clone)]
+pub struct NoveltySelection {
+    num_neighbours: usize,
+    group_size: usize,
+    fitness_weight: f32,
+    archive_policy: ArchivePolicy,
+    archive_capacity: usize,
+    archive: Rc<RefCell<Vec<Vec<f32>>>>,
+}
+
+impl NoveltySelection {
+    /// Creates a novelty selection that computes the novelty from the given number of nearest
+    /// neighbours, and picks the most novel of a group of randomly chosen individuals. Both
+    /// should be at least one.
+    ///
+    /// By default, fitness is ignored, and each individual is added to the archive with a
+    /// probability of 0.01. The archive keeps at most 1000 behaviours.
+    pub fn new(num_neighbours: usize, group_size: usize) -> Result<Self, EvolutionError> {
+        if num_neighbours == 0 {
+            return Err(EvolutionError::InvalidParameter("num_neighbours", "at least 1"));
+        }
+        if group_size == 0 {
+            return Err(EvolutionError::InvalidParameter("group_size", "at least 1"));
+        }
+
+        Ok(NoveltySelection {
+            num_neighbours,
+            group_size,
+            fitness_weight: 0.0,
+            archive_policy: ArchivePolicy::Random(0.01),
+            archive_capacity: 1000,
+            archive: Rc::new(RefCell::new(Vec::new())),
+        })
+    }
+
+    /// Sets the weight of the fitness in the score, in range [0, 1]. The novelty has the
+    /// remaining weight. At 0, selection is by novelty alone. At 1, it is by fitness alone.
+    pub fn with_fitness_weight(mut self, fitness_weight: f32) -> Result<Self, EvolutionError> {
+        if !(0.0..=1.0).contains(&fitness_weight) {
+            return Err(EvolutionError::InvalidParameter("fitness_weight", "in range [0, 1]"));
+        }
+        self.fitness_weight = fitness_weight;
+        Ok(self)
+    }
+
+    /// Sets which behaviours are added to the archive, and how many it keeps. When it is full,
+    /// the oldest behaviours are removed.
+    pub fn with_archive(
+        mut self, policy: ArchivePolicy, capacity: usize
+    ) -> Result<Self, EvolutionError> {
+        match policy {
+            ArchivePolicy::Random(prob) if !(0.0..=1.0).contains(&prob) => {
+                return Err(EvolutionError::InvalidParameter("archive_prob", "in range [0, 1]"));
+            },
+            ArchivePolicy::Threshold(threshold) if threshold.is_nan() || threshold < 0.0 => {
+                return Err(EvolutionError::InvalidParameter("archive_threshold", "at least 0"));
+            },
+            _ => {},
+        }
+        self.archive_policy = policy;
+        self.archive_capacity = capacity;
+        Ok(self)
+    }
+
+    /// The behaviours in the archive, oldest first.
+    pub fn archive(&self) -> Vec<Vec<f32>> {
+        self.archive.borrow().clone()
+    }
+
+    pub fn archive_size(&self) -> usize {
+        self.archive.borrow().len()
+    }
+
+    /// Computes the novelty of each of the given behaviours, compared to the others and to the
+    /// archive.
+    pub fn novelty(&self, behaviours: &[Vec<f32>]) -> Vec<f32> {
+        let archive = self.archive.borrow();
+        let mut distances = Vec::with_capacity(behaviours.len() + archive.len());
+
+        behaviours.iter().enumerate().map(|(i, behaviour)| {
+            distances.clear();
+            distances.extend(behaviours.iter().enumerate().filter(|(j, _)| *j != i).map(
+                |(_, other)| distance(behaviour, other)
+            ));
+            distances.extend(archive.iter().map(|other| distance(behaviour, other)));
+
+            let k = self.num_neighbours.min(distances.len());
+            if k == 0 {
+                return 0.0;
+            }
+            distances.select_nth_unstable_by(k - 1, |a, b| a.total_cmp(b));
+            distances[..k].iter().sum::<f32>() / k as f32
+        }).collect()
+    }
+
+    /// Adds behaviours of the generation to the archive, according to the policy.
+    fn update_archive(&self, behaviours: &[Vec<f32>], novelty: &[f32]) {
+        let mut archive = self.archive.borrow_mut();
+        let mut rng = random::rng();
+
+        for (behaviour, novelty) in behaviours.iter().zip(novelty) {
+            let add = match self.archive_policy {
+                ArchivePolicy::Random(prob) => rng.gen::<f32>() < prob,
+                ArchivePolicy::Threshold(threshold) => *novelty >= threshold,
+            };
+            if add {
+                archive.push(behaviour.clone());
+            }
+        }
+
+        let excess = archive.len().saturating_sub(self.archive_capacity);
+        archive.drain(..excess);
+    }
+}
+
+impl fmt::Debug for NoveltySelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NoveltySelection")
+            .field("num_neighbours", &self.num_neighbours)
+            .field("group_size", &self.group_size)
+            .field("fitness_weight", &self.fitness_weight)
+            .field("archive_policy", &self.archive_policy)
+            .field("archive_capacity", &self.archive_capacity)
+            .field("archive_size", &self.archive_size())
+            .finish()
+    }
+}
+
+/// The Euclidean distance between two behaviours.
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum::<f32>().sqrt()
+}
+
+/// Returns the rank of each value, normalised to [0, 1], where the greatest value has rank 1.
+/// Equal values get the same rank.
+fn normalised_ranks<T>(values: &[T], compare: impl Fn(&T, &T) -> Ordering) -> Vec<f32> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| compare(&values[a], &values[b]));
+
+    let mut ranks = vec![0.0; values.len()];
+    let scale = values.len().saturating_sub(1).max(1) as f32;
+    let mut rank = 0;
+    for (position, &index) in order.iter().enumerate() {
+        let previous = position.checked_sub(1).map(|previous| &values[order[previous]]);
+        if previous.is_some_and(|previous| compare(previous, &values[index]) != Ordering::Equal) {
+            rank = position;
+        }
+        ranks[index] = rank as f32 / scale;
+    }
+
+    ranks
+}
+
+struct NoveltySelector<'a, P: Phenotype, G: Genotype<P>> {
+    group_size: usize,
+    population: &'a Population<P, G>,
+    scores: Vec<f32>,
+}
+
+impl<P: Behaviour, G: Genotype<P>> SelectionFactory<P, G> for NoveltySelection {
+    fn select_from<'a>(
+        &self, population: &'a Population<P, G>
+    ) -> Box<dyn Selector<P, G> + 'a> {
+        // Survivors of elitism have no phenotype until they are grown again
+        let behaviours: Vec<Vec<f32>> = population.iter().map(
+            |indiv| match indiv.phenotype() {
+                Some(phenotype) => phenotype.behaviour(),
+                None => indiv.genotype().express().behaviour(),
+            }
+        ).collect();
+        let novelty = self.novelty(&behaviours);
+        // There is no hook for when breeding succeeds, so the archive is updated here
+        self.update_archive(&behaviours, &novelty);
+
+        let novelty_ranks = normalised_ranks(&novelty, |a, b| a.total_cmp(b));
+        let fitness: Vec<Option<P::Fitness>> = population.iter().map(|indiv| indiv.fitness())
+            .collect();
+        let fitness_ranks = normalised_ranks(&fitness, |a, b| P::DIRECTION.compare_optional(a, b));
+        let weight = self.fitness_weight;
+        let scores = novelty_ranks.iter().zip(fitness_ranks).map(
+            |(novelty, fitness)| (1.0 - weight) * novelty + weight * fitness
+        ).collect();
+
+        Box::new(
+            NoveltySelector {
+                group_size: self.group_size,
+                population,
+                scores,
+            }
+        )
+    }
+}
+
+impl<'a, P: Phenotype, G: Genotype<P>> Selector<P, G> for NoveltySelector<'a, P, G> {
+    fn select(&self) -> &Individual<P, G> {
+        let mut rng = random::rng();
+        let mut best = rng.gen_range(0..self.scores.len());
+
+        for _ in 1..self.group_size {
+            let other = rng.gen_range(0..self.scores.len());
+            if self.scores[other] > self.scores[best] {
+                best = other;
+            }
+        }
+
+        &self.population.individuals[best]
+    }
+}
@@ -0,0 +1,252 @@
+//! The interface shared by all optimisation algorithms, and the criteria to stop them.
+
+use super::{evaluate_all, EvolutionError, Evaluator, Genotype, Phenotype, Stats};
+use super::diversity::Diversity;
+use super::fitness::{is_comparable, InvalidFitnessPolicy};
+use super::logging::LogSink;
+use std::cmp;
+
+/// An optimisation algorithm that proceeds in generations. This makes it possible to run, stop
+/// and compare different algorithms in the same way.
+pub trait Optimiser {
+    /// Runs one generation, including the evaluation of its candidate solutions. Returns the
+    /// statistics of the generation.
+    fn step(&mut self) -> Result<Stats, EvolutionError>;
+
+    /// The number of fitness evaluations done so far.
+    fn evaluations(&self) -> usize;
+
+    /// Runs generations until the termination criteria are met. Returns the criterion that was
+    /// met first.
+    fn run(&mut self, termination: &Termination) -> Result<TerminationReason, EvolutionError> {
+        termination.check()?;
+        let mut progress = Progress::default();
+
+        loop {
+            let stats = self.step()?;
+            if let Some(reason) = progress.update(termination, &stats, self.evaluations()) {
+                return Ok(reason);
+            }
+        }
+    }
+}
+
+/// Criteria for when to stop running an optimiser. The run stops as soon as any of them is met,
+/// so at least one of them should be set.
+#[derive(Debug, Clone, Default)]
+pub struct Termination {
+    max_generations: Option<usize>,
+    max_evaluations: Option<usize>,
+    target_fitness: Option<f64>,
+    max_stalled_generations: Option<usize>,
+}
+
+impl Termination {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops after the given number of generations.
+    pub fn max_generations(mut self, max_generations: usize) -> Self {
+        self.max_generations = Some(max_generations);
+        self
+    }
+
+    /// Stops after the generation in which the number of evaluations reaches the given maximum.
+    pub fn max_evaluations(mut self, max_evaluations: usize) -> Self {
+        self.max_evaluations = Some(max_evaluations);
+        self
+    }
+
+    /// Stops when a solution is found whose fitness is at least as good as the given one. It is
+    /// compared with the best fitness in the statistics, see [Stats].
+    pub fn target_fitness(mut self, target_fitness: f64) -> Self {
+        self.target_fitness = Some(target_fitness);
+        self
+    }
+
+    /// Stops when the best fitness has not improved for the given number of generations.
+    pub fn max_stalled_generations(mut self, max_stalled_generations: usize) -> Self {
+        self.max_stalled_generations = Some(max_stalled_generations);
+        self
+    }
+
+    pub(crate) fn target(&self) -> Option<f64> {
+        self.target_fitness
+    }
+
+    pub(crate) fn check(&self) -> Result<(), EvolutionError> {
+        if self.max_generations.is_none() && self.max_evaluations.is_none()
+            && self.target_fitness.is_none() && self.max_stalled_generations.is_none()
+        {
+            return Err(EvolutionError::InvalidParameter("termination", "at least one criterion"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Tracks the progress of a run, to determine when it should be stopped.
+#[derive(Debug, Default)]
+pub(crate) struct Progress {
+    generations: usize,
+    best_fitness: Option<f64>,
+    stalled_generations: usize,
+}
+
+impl Progress {
+    /// Updates the progress with the statistics of the latest generation. Returns the criterion
+    /// that is met, if any.
+    pub(crate) fn update(
+        &mut self, termination: &Termination, stats: &Stats, evaluations: usize
+    ) -> Option<TerminationReason> {
+        self.generations += 1;
+
+        let direction = stats.direction;
+        let best_fitness = Some(stats.best_fitness);
+        if direction.compare_optional(&best_fitness, &self.best_fitness) == cmp::Ordering::Greater {
+            self.best_fitness = best_fitness;
+            self.stalled_generations = 0;
+        } else {
+            self.stalled_generations += 1;
+        }
+
+        if termination.target_fitness.is_some_and(
+            |target| direction.is_at_least_as_good(stats.best_fitness, target)
+        ) {
+            Some(TerminationReason::TargetReached)
+        } else if termination.max_generations.is_some_and(|max| self.generations >= max) {
+            Some(TerminationReason::MaxGenerations)
+        } else if termination.max_evaluations.is_some_and(|max| evaluations >= max) {
+            Some(TerminationReason::MaxEvaluations)
+        } else if termination.max_stalled_generations.is_some_and(
+            |max| self.stalled_generations >= max
+        ) {
+            Some(TerminationReason::Stalled)
+        } else {
+            None
+        }
+    }
+
+    /// The best fitness so far.
+    pub(crate) fn best_fitness(&self) -> Option<f64> {
+        self.best_fitness
+    }
+}
+
+/// The evaluation and logging that the optimisers without a population of individuals have in
+/// common: the evaluator, the log sinks, and the handling of invalid fitness.
+pub(crate) struct Evaluation<P: Phenotype> {
+    evaluator: Option<Box<dyn Evaluator<P>>>,
+    log_sinks: Vec<Box<dyn LogSink>>,
+    invalid_fitness_policy: InvalidFitnessPolicy,
+    evaluations: usize,
+    // The number of evaluations in the current generation that gave an invalid fitness
+    invalid_evaluations: usize,
+}
+
+impl<P: Phenotype> Evaluation<P> {
+    pub(crate) fn new() -> Self {
+        Evaluation {
+            evaluator: None,
+            log_sinks: Vec::new(),
+            invalid_fitness_policy: InvalidFitnessPolicy::Worst,
+            evaluations: 0,
+            invalid_evaluations: 0,
+        }
+    }
+
+    pub(crate) fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator<P>>) {
+        self.evaluator = Some(evaluator);
+    }
+
+    pub(crate) fn add_log_sink(&mut self, sink: Box<dyn LogSink>) {
+        self.log_sinks.push(sink);
+    }
+
+    pub(crate) fn set_invalid_fitness_policy(&mut self, policy: InvalidFitnessPolicy) {
+        self.invalid_fitness_policy = policy;
+    }
+
+    /// The number of fitness evaluations done so far, including re-evaluations.
+    pub(crate) fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    /// Evaluates the phenotypes of a generation, and handles invalid fitness according to the
+    /// policy. When the policy rejects it, it fails with the index of the first phenotype whose
+    /// fitness is invalid.
+    pub(crate) fn evaluate(&mut self, phenotypes: &[P]) -> Result<Vec<P::Fitness>, EvolutionError> {
+        let evaluator = self.evaluator.as_deref();
+        let mut fitnesses = evaluate_all(evaluator, &phenotypes.iter().collect::<Vec<_>>())?;
+        self.evaluations += phenotypes.len();
+
+        let mut invalid: Vec<usize> = (0..fitnesses.len()).filter(
+            |&i| !is_comparable(&fitnesses[i])
+        ).collect();
+        self.invalid_evaluations = invalid.len();
+
+        match self.invalid_fitness_policy {
+            InvalidFitnessPolicy::Reject => {
+                if let Some(&i) = invalid.first() {
+                    return Err(EvolutionError::InvalidFitness(i as u64));
+                }
+            },
+            InvalidFitnessPolicy::Reevaluate(max) => {
+                for _ in 0..max {
+                    if invalid.is_empty() {
+                        break;
+                    }
+                    let again: Vec<&P> = invalid.iter().map(|&i| &phenotypes[i]).collect();
+                    let values = evaluate_all(evaluator, &again)?;
+                    self.evaluations += again.len();
+                    for (&i, fitness) in invalid.iter().zip(values) {
+                        fitnesses[i] = fitness;
+                    }
+                    invalid.retain(|&i| !is_comparable(&fitnesses[i]));
+                    self.invalid_evaluations += invalid.len();
+                }
+            },
+            InvalidFitnessPolicy::Worst => {},
+        }
+
+        Ok(fitnesses)
+    }
+
+    /// Computes the statistics of the solutions of a generation. Unlike the fitness of the
+    /// solutions, the number of invalid evaluations includes those that were evaluated again.
+    pub(crate) fn stats<G: Genotype<P>>(
+        &self, fitnesses: &[P::Fitness], genotypes: &[&G], diversity: Option<fn(&[&G]) -> Diversity>
+    ) -> Result<Stats, EvolutionError> {
+        let stats = Stats::from_generation::<P, G>(
+            fitnesses.iter().map(|fitness| Some(*fitness)), genotypes, diversity
+        ).ok_or(EvolutionError::NoPopulation)?;
+
+        Ok(Stats { invalid_evaluations: self.invalid_evaluations, ..stats })
+    }
+
+    /// Writes the statistics of a generation to all log sinks, together with the probability
+    /// vector it was sampled from, if any.
+    pub(crate) fn log_generation(
+        &mut self, generation: usize, stats: &Stats, probabilities: Option<&[f32]>
+    ) -> Result<(), EvolutionError> {
+        for sink in self.log_sinks.iter_mut() {
+            sink.log_generation(generation, stats)?;
+            if let Some(probabilities) = probabilities {
+                sink.log_probabilities(generation, probabilities)?;
+            }
+            sink.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The reason that a run was stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationReason {
+    MaxGenerations,
+    MaxEvaluations,
+    TargetReached,
+    Stalled,
+}
@@ -0,0 +1,210 @@
+//! Chromosomes that encode an ordering, such as the order in which to visit a set of cities.
+
+use super::{EvolutionError, Genotype, Mutation, Phenotype, Recombination};
+use super::diversity::GenotypeDistance;
+use super::hall_of_fame::TextEncoding;
+use super::memetic::{HillClimber, Improved, Improvement, LocalSearch};
+use super::random;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use std::clone;
+
+/// A permutation of the numbers `0` up to its length.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PermutationChromosome {
+    pub order: Vec<usize>,
+}
+
+impl PermutationChromosome {
+    /// Creates a random permutation.
+    pub fn new(len: usize) -> Self {
+        let mut chromosome = Self::identity(len);
+        chromosome.order.shuffle(&mut random::rng());
+        chromosome
+    }
+
+    /// Creates the permutation that keeps all numbers in order.
+    pub fn identity(len: usize) -> Self {
+        PermutationChromosome {
+            order: (0..len).collect()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl clone::Clone for PermutationChromosome {
+    fn clone(&self) -> Self {
+        PermutationChromosome {
+            order: self.order.clone()
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.order.clone_from(&source.order);
+    }
+}
+
+/// Encodes the numbers separated by spaces. Decoding fails when they are not a permutation.
+impl TextEncoding for PermutationChromosome {
+    fn encode(&self) -> String {
+        let order: Vec<String> = self.order.iter().map(usize::to_string).collect();
+        order.join(" ")
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let order = text.split_whitespace().map(|number| number.parse().ok())
+            .collect::<Option<Vec<usize>>>()?;
+
+        let mut seen = vec![false; order.len()];
+        for &number in order.iter() {
+            if number >= seen.len() || seen[number] {
+                return None;
+            }
+            seen[number] = true;
+        }
+
+        Some(PermutationChromosome {
+            order
+        })
+    }
+}
+
+impl GenotypeDistance for PermutationChromosome {
+    /// Returns the number of positions that hold a different number. When the lengths differ,
+    /// each position beyond the end of the shorter permutation counts as a difference.
+    fn distance(&self, other: &Self) -> f32 {
+        let differences = self.order.iter().zip(other.order.iter()).filter(|(a, b)| a != b).count();
+
+        (differences + self.len().abs_diff(other.len())) as f32
+    }
+}
+
+/// Swaps two randomly chosen numbers.
+#[derive(Debug)]
+pub struct PermutationSwapMutation;
+
+impl Mutation for PermutationSwapMutation {
+    type Genotype = PermutationChromosome;
+
+    fn mutate(&self, target: &mut Self::Genotype) {
+        let len = target.len();
+        if len < 2 {
+            return;
+        }
+
+        let mut rng = random::rng();
+        target.order.swap(rng.gen_range(0..len), rng.gen_range(0..len));
+    }
+}
+
+/// Order crossover. The child takes a randomly chosen segment from the first parent, at the same
+/// positions. The remaining positions are filled with the other numbers, in the order in which
+/// they occur in the second parent, starting after the segment.
+#[derive(Debug)]
+pub struct PermutationOrderCrossover;
+
+impl Recombination for PermutationOrderCrossover {
+    type Genotype = PermutationChromosome;
+
+    fn recombine(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype
+    ) -> Result<Self::Genotype, EvolutionError> {
+        let mut child = parent1.clone();
+        self.recombine_into(parent1, parent2, &mut child)?;
+
+        Ok(child)
+    }
+
+    fn recombine_into(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype, child: &mut Self::Genotype
+    ) -> Result<(), EvolutionError> {
+        let len = parent1.len();
+        if len != parent2.len() {
+            return Err(EvolutionError::LengthMismatch(len, parent2.len()));
+        }
+        if parent1.order.iter().chain(parent2.order.iter()).any(|number| *number >= len) {
+            return Err(EvolutionError::InvalidParameter("parents", "permutations"));
+        }
+
+        child.clone_from(parent1);
+        if len < 2 {
+            return Ok(());
+        }
+
+        let mut rng = random::rng();
+        let start = rng.gen_range(0..len);
+        let end = rng.gen_range(start + 1..=len);
+
+        let mut taken = vec![false; len];
+        for number in parent1.order[start..end].iter() {
+            taken[*number] = true;
+        }
+
+        // Fill the positions after the segment, wrapping around to the start
+        let mut pos = end % len;
+        for i in 0..len {
+            let number = parent2.order[(end + i) % len];
+            if !taken[number] {
+                child.order[pos] = number;
+                pos = (pos + 1) % len;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 2-opt local search. A move reverses a segment of the permutation. For a round trip, this
+/// replaces two edges by two others. It stops when no move improves the fitness, or when its
+/// evaluation budget is used up.
+#[derive(Debug)]
+pub struct PermutationTwoOpt {
+    improvement: Improvement,
+    max_evaluations: usize,
+}
+
+impl PermutationTwoOpt {
+    /// Creates a new 2-opt local search. The maximum number of evaluations per improved genotype
+    /// includes the evaluation of the original genotype, so it should be at least 1.
+    pub fn new(improvement: Improvement, max_evaluations: usize) -> Result<Self, EvolutionError> {
+        if max_evaluations == 0 {
+            return Err(EvolutionError::InvalidParameter("max_evaluations", "at least 1"));
+        }
+
+        Ok(PermutationTwoOpt {
+            improvement,
+            max_evaluations
+        })
+    }
+}
+
+impl<P: Phenotype> LocalSearch<P, PermutationChromosome> for PermutationTwoOpt
+where
+    PermutationChromosome: Genotype<P>,
+{
+    fn improve(&self, genotype: &mut PermutationChromosome) -> Improved<P::Fitness> {
+        let len = genotype.len();
+        let segments: Vec<(usize, usize)> = (0..len).flat_map(
+            |start| (start + 2..=len).map(move |end| (start, end))
+        ).collect();
+        let reverse = |genotype: &mut PermutationChromosome, i: usize| {
+            let (start, end) = segments[i];
+            genotype.order[start..end].reverse();
+        };
+
+        let mut climber = HillClimber::new(self.improvement, self.max_evaluations);
+        let fitness = climber.evaluate(genotype);
+
+        let fitness = climber.climb(
+            genotype, fitness, segments.len(), reverse, |genotype, i, _| reverse(genotype, i)
+        );
+        climber.improved(fitness)
+    }
+}
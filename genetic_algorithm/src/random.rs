@@ -0,0 +1,50 @@
+//! The source of randomness for all algorithms and operators.
+//!
+//! Each thread has its own random number generator. By default it is seeded from the operating
+//! system, but it can be seeded explicitly with [seed], which makes runs on that thread
+//! reproducible.
+
+use rand::{Error, RngCore, SeedableRng};
+use rand::rngs::StdRng;
+use std::cell::RefCell;
+
+thread_local! {
+    static THREAD_RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Seeds the random number generator of the current thread.
+pub fn seed(seed: u64) {
+    THREAD_RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Returns a handle to the random number generator of the current thread.
+pub fn rng() -> LocalRng {
+    LocalRng {
+        _not_send: std::marker::PhantomData,
+    }
+}
+
+/// A handle to the random number generator of the current thread. See [rng].
+#[derive(Debug, Clone, Copy)]
+pub struct LocalRng {
+    // The handle refers to the generator of the thread that created it
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        THREAD_RNG.with(|rng| rng.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        THREAD_RNG.with(|rng| rng.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        THREAD_RNG.with(|rng| rng.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        THREAD_RNG.with(|rng| rng.borrow_mut().try_fill_bytes(dest))
+    }
+}
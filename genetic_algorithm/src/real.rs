@@ -0,0 +1,226 @@
+//! Chromosomes that encode a vector of real numbers, for continuous optimisation problems.
+
+use super::{EvolutionError, Genotype, Mutation, Phenotype, Recombination};
+use super::diversity::GenotypeDistance;
+use super::hall_of_fame::TextEncoding;
+use super::memetic::{HillClimber, Improved, Improvement, LocalSearch};
+use super::random;
+use rand::Rng;
+use std::{clone, f32};
+
+#[derive(Debug, PartialEq)]
+pub struct RealChromosome {
+    pub values: Vec<f32>,
+}
+
+impl RealChromosome {
+    /// Creates a chromosome of `len` values, drawn uniformly from range [min, max>.
+    pub fn new(len: usize, min: f32, max: f32) -> Self {
+        let mut rng = random::rng();
+
+        RealChromosome {
+            values: (0..len).map(|_| min + (max - min) * rng.gen::<f32>()).collect()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl clone::Clone for RealChromosome {
+    fn clone(&self) -> Self {
+        RealChromosome {
+            values: self.values.clone()
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.values.clone_from(&source.values);
+    }
+}
+
+/// Encodes the values separated by spaces.
+impl TextEncoding for RealChromosome {
+    fn encode(&self) -> String {
+        let values: Vec<String> = self.values.iter().map(f32::to_string).collect();
+        values.join(" ")
+    }
+
+    fn decode(text: &str) -> Option<Self> {
+        let values = text.split_whitespace().map(|value| value.parse().ok())
+            .collect::<Option<Vec<f32>>>()?;
+
+        Some(RealChromosome {
+            values
+        })
+    }
+}
+
+impl GenotypeDistance for RealChromosome {
+    /// Returns the Euclidean distance. When the lengths differ, only the common values are
+    /// compared.
+    fn distance(&self, other: &Self) -> f32 {
+        self.values.iter().zip(other.values.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f32>()
+            .sqrt()
+    }
+}
+
+/// Returns a random number from the standard normal distribution, using the Box-Muller
+/// transform.
+pub(crate) fn standard_normal<R: Rng>(rng: &mut R) -> f32 {
+    // The first uniform number is in range <0, 1], to avoid the logarithm of zero
+    let u1 = 1.0 - rng.gen::<f32>();
+    let u2 = rng.gen::<f32>();
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * f32::consts::PI * u2).cos()
+}
+
+/// Adds normally distributed noise to the values.
+#[derive(Debug)]
+pub struct RealGaussianMutation {
+    mutate_prob: f32,
+    sigma: f32,
+}
+
+impl RealGaussianMutation {
+    /// Creates a new Real Gaussian Mutation operator.
+    ///
+    /// The mutation probability is the probability that each value is changed. It should be in
+    /// range [0, 1]. The noise has a standard deviation of `sigma`, which should not be negative.
+    pub fn new(mutate_prob: f32, sigma: f32) -> Result<Self, EvolutionError> {
+        if !(0.0..=1.0).contains(&mutate_prob) {
+            return Err(EvolutionError::InvalidParameter("mutate_prob", "in range [0, 1]"));
+        }
+        if !(sigma >= 0.0 && sigma.is_finite()) {
+            return Err(EvolutionError::InvalidParameter("sigma", "at least 0"));
+        }
+
+        Ok(RealGaussianMutation {
+            mutate_prob,
+            sigma
+        })
+    }
+}
+
+impl Mutation for RealGaussianMutation {
+    type Genotype = RealChromosome;
+
+    fn mutate(&self, target: &mut Self::Genotype) {
+        let mut rng = random::rng();
+
+        for value in target.values.iter_mut() {
+            if rng.gen::<f32>() < self.mutate_prob {
+                *value += self.sigma * standard_normal(&mut rng);
+            }
+        }
+    }
+}
+
+/// Arithmetic crossover. Each value of the child is a random weighted average of the values of
+/// the parents, so it lies between them.
+#[derive(Debug)]
+pub struct RealArithmeticCrossover;
+
+impl Recombination for RealArithmeticCrossover {
+    type Genotype = RealChromosome;
+
+    fn recombine(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype
+    ) -> Result<Self::Genotype, EvolutionError> {
+        let mut child = parent1.clone();
+        self.recombine_into(parent1, parent2, &mut child)?;
+
+        Ok(child)
+    }
+
+    fn recombine_into(
+        &self, parent1: &Self::Genotype, parent2: &Self::Genotype, child: &mut Self::Genotype
+    ) -> Result<(), EvolutionError> {
+        if parent1.len() != parent2.len() {
+            return Err(EvolutionError::LengthMismatch(parent1.len(), parent2.len()));
+        }
+
+        let mut rng = random::rng();
+        child.values.clear();
+        child.values.extend(parent1.values.iter().zip(parent2.values.iter()).map(|(a, b)| {
+            let weight = rng.gen::<f32>();
+            weight * a + (1.0 - weight) * b
+        }));
+
+        Ok(())
+    }
+}
+
+/// Coordinate search, also known as compass search. A move increases or decreases one value by
+/// the step size. When no move improves the fitness, the step size is halved. It stops when the
+/// step size drops below the minimum, or when its evaluation budget is used up.
+#[derive(Debug)]
+pub struct RealCoordinateSearch {
+    improvement: Improvement,
+    initial_step: f32,
+    min_step: f32,
+    max_evaluations: usize,
+}
+
+impl RealCoordinateSearch {
+    /// Creates a new Real Coordinate Search local search. The step sizes should be positive, and
+    /// the minimum step should not exceed the initial step. The maximum number of evaluations
+    /// per improved genotype includes the evaluation of the original genotype, so it should be
+    /// at least 1.
+    pub fn new(
+        improvement: Improvement, initial_step: f32, min_step: f32, max_evaluations: usize
+    ) -> Result<Self, EvolutionError> {
+        if !(min_step > 0.0 && min_step.is_finite()) {
+            return Err(EvolutionError::InvalidParameter("min_step", "positive"));
+        }
+        if !(initial_step >= min_step && initial_step.is_finite()) {
+            return Err(EvolutionError::InvalidParameter("initial_step", "at least min_step"));
+        }
+        if max_evaluations == 0 {
+            return Err(EvolutionError::InvalidParameter("max_evaluations", "at least 1"));
+        }
+
+        Ok(RealCoordinateSearch {
+            improvement,
+            initial_step,
+            min_step,
+            max_evaluations
+        })
+    }
+}
+
+impl<P: Phenotype> LocalSearch<P, RealChromosome> for RealCoordinateSearch
+where
+    RealChromosome: Genotype<P>,
+{
+    fn improve(&self, genotype: &mut RealChromosome) -> Improved<P::Fitness> {
+        let mut climber = HillClimber::new(self.improvement, self.max_evaluations);
+        let mut fitness = climber.evaluate(genotype);
+        let mut step = self.initial_step;
+        let num_moves = 2 * genotype.len();
+
+        while step >= self.min_step && climber.has_budget() {
+            // Even moves increase a value, odd moves decrease it. Undoing restores the original
+            // value exactly, which subtracting the step again might not.
+            let shift = |genotype: &mut RealChromosome, i: usize| {
+                let value = &mut genotype.values[i / 2];
+                let original = *value;
+                *value += if i.is_multiple_of(2) { step } else { -step };
+                original
+            };
+            let restore = |genotype: &mut RealChromosome, i: usize, original: f32| {
+                genotype.values[i / 2] = original;
+            };
+
+            fitness = climber.climb(genotype, fitness, num_moves, shift, restore);
+            step /= 2.0;
+        }
+
+        climber.improved(fitness)
+    }
+}
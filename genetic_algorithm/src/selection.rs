@@ -1,51 +1,63 @@
-use super::{Genotype, Phenotype, Individual, Population, SelectionFactory, Selector};
-use rand::{self, Rng};
+use super::{
+    EvolutionError, Genotype, Phenotype, Individual, Population, SelectionFactory, Selector
+};
+use super::random;
+use rand::Rng;
+use std::cmp::Ordering;
 
 #[derive(Clone, Copy, Debug)]
 pub struct RankBasedSelection {
     group_size: usize
 }
 
-struct RankBasedSelector<P: Phenotype, G: Genotype<P>> {
+struct RankBasedSelector<'a, P: Phenotype, G: Genotype<P>> {
     selection: RankBasedSelection,
-    population: Population<P, G>
+    population: &'a Population<P, G>
 }
 
 impl RankBasedSelection {
-    pub fn new(group_size: usize) -> Self {
-        RankBasedSelection {
-            group_size
+    /// Creates a new Rank Based Selection. Each selection picks the fittest of a group of
+    /// randomly chosen individuals. The group size should be at least one.
+    pub fn new(group_size: usize) -> Result<Self, EvolutionError> {
+        if group_size == 0 {
+            return Err(EvolutionError::InvalidParameter("group_size", "at least 1"));
         }
+
+        Ok(RankBasedSelection {
+            group_size
+        })
     }
 }
 
 impl<P: Phenotype, G: Genotype<P>> SelectionFactory<P, G> for RankBasedSelection {
-    fn select_from(&self, population: Population<P, G>) -> Box<dyn Selector<P, G>> {
+    fn select_from<'a>(
+        &self, population: &'a Population<P, G>
+    ) -> Box<dyn Selector<P, G> + 'a> {
         Box::new(
             RankBasedSelector {
-                selection: self.clone(),
+                selection: *self,
                 population
             }
         )
     }
 }
 
-impl<P: Phenotype, G: Genotype<P>> RankBasedSelector<P, G> {
+impl<'a, P: Phenotype, G: Genotype<P>> RankBasedSelector<'a, P, G> {
     fn select_one(&self) -> &Individual<P, G> {
         self.population.individuals.get(
-            rand::thread_rng().gen_range(0..self.population.individuals.len())
+            random::rng().gen_range(0..self.population.individuals.len())
         ).unwrap()
     }
 }
 
-impl<P: Phenotype, G: Genotype<P>> Selector<P, G> for RankBasedSelector<P, G> {
+impl<'a, P: Phenotype, G: Genotype<P>> Selector<P, G> for RankBasedSelector<'a, P, G> {
     fn select(&self) -> &Individual<P, G> {
         let mut best = self.select_one();
 
         for _ in 1..self.selection.group_size {
             let other = self.select_one();
 
-            if other.fitness > best.fitness {
+            if P::DIRECTION.compare_optional(&other.fitness, &best.fitness) == Ordering::Greater {
                 best = other;
             }
         }
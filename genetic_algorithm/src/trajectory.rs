@@ -0,0 +1,388 @@
+//! Single-solution optimisers, to serve as baselines for the evolutionary algorithm.
+//!
+//! These optimisers follow a trajectory through the search space. Each generation, they create
+//! neighbours of the current solution by mutating it, and then decide which neighbour to move to,
+//! if any. How they decide depends on the algorithm, see [MoveStrategy].
+
+use super::{EvolutionError, Evaluator, Genotype, Mutation, Phenotype, Stats};
+use super::diversity::{Diversity, GenotypeDistance};
+use super::fitness::{Fitness, InvalidFitnessPolicy};
+use super::logging::LogSink;
+use super::optimiser::{Evaluation, Optimiser};
+use super::random;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::{cmp, fmt};
+
+/// Decides where a trajectory moves to. An invalid fitness is less fit than any valid fitness,
+/// see [InvalidFitnessPolicy].
+pub trait MoveStrategy<P: Phenotype, G>: fmt::Debug {
+    /// The number of neighbours to create each generation.
+    fn num_neighbours(&self) -> usize;
+
+    /// Returns the index of the neighbour to move to, or `None` to stay at the current solution.
+    /// It is given the generation, the current solution, the best fitness found so far and the
+    /// neighbours with their fitness.
+    fn choose(
+        &mut self, generation: usize, current: &(G, P::Fitness), best_fitness: P::Fitness,
+        neighbours: &[(G, P::Fitness)]
+    ) -> Option<usize>;
+}
+
+/// Returns the index of the fittest solution that satisfies the condition.
+fn fittest<P: Phenotype, G>(
+    solutions: &[(G, P::Fitness)], condition: impl Fn(&(G, P::Fitness)) -> bool
+) -> Option<usize> {
+    solutions.iter().enumerate().filter(|(_, solution)| condition(solution)).fold(
+        None, |best: Option<(usize, P::Fitness)>, (i, (_, fitness))| match best {
+            Some((_, best_fitness)) if P::DIRECTION.compare_optional(
+                &Some(best_fitness), &Some(*fitness)
+            ) != cmp::Ordering::Less => best,
+            _ => Some((i, *fitness)),
+        }
+    ).map(|(i, _)| i)
+}
+
+/// Hill climbing. It moves to the fittest neighbour, unless that is less fit than the current
+/// solution. Moving to equally fit neighbours lets it drift across plateaus.
+#[derive(Debug)]
+pub struct HillClimbing {
+    num_neighbours: usize,
+}
+
+impl HillClimbing {
+    /// Creates a hill climbing strategy that creates `num_neighbours` neighbours per generation.
+    /// It should be at least 1.
+    pub fn new(num_neighbours: usize) -> Result<Self, EvolutionError> {
+        if num_neighbours == 0 {
+            return Err(EvolutionError::InvalidParameter("num_neighbours", "at least 1"));
+        }
+
+        Ok(HillClimbing {
+            num_neighbours
+        })
+    }
+}
+
+impl<P: Phenotype, G> MoveStrategy<P, G> for HillClimbing {
+    fn num_neighbours(&self) -> usize {
+        self.num_neighbours
+    }
+
+    fn choose(
+        &mut self, _generation: usize, current: &(G, P::Fitness), _best_fitness: P::Fitness,
+        neighbours: &[(G, P::Fitness)]
+    ) -> Option<usize> {
+        fittest::<P, G>(neighbours, |(_, fitness)| {
+            P::DIRECTION.compare_optional(&Some(*fitness), &Some(current.1)) != cmp::Ordering::Less
+        })
+    }
+}
+
+/// Determines the temperature of simulated annealing in each generation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoolingSchedule {
+    /// The temperature is multiplied by `factor` each generation.
+    Geometric { initial: f32, factor: f32 },
+    /// The temperature is decreased by `decrement` each generation, until it reaches zero.
+    Linear { initial: f32, decrement: f32 },
+    /// The temperature in generation `k` is `initial / ln(k + e)`. It cools slowly enough to
+    /// find the global optimum in theory, but usually too slowly in practice.
+    Logarithmic { initial: f32 },
+}
+
+impl CoolingSchedule {
+    pub fn temperature(&self, generation: usize) -> f32 {
+        match *self {
+            CoolingSchedule::Geometric { initial, factor } => {
+                initial * factor.powi(generation.min(i32::MAX as usize) as i32)
+            },
+            CoolingSchedule::Linear { initial, decrement } => {
+                (initial - decrement * generation as f32).max(0.0)
+            },
+            CoolingSchedule::Logarithmic { initial } => {
+                initial / (generation as f32 + std::f32::consts::E).ln()
+            },
+        }
+    }
+
+    fn check(&self) -> Result<(), EvolutionError> {
+        let initial = match *self {
+            CoolingSchedule::Geometric { initial, factor } => {
+                if !(factor > 0.0 && factor <= 1.0) {
+                    return Err(EvolutionError::InvalidParameter("factor", "in range <0, 1]"));
+                }
+                initial
+            },
+            CoolingSchedule::Linear { initial, decrement } => {
+                if !(decrement >= 0.0 && decrement.is_finite()) {
+                    return Err(EvolutionError::InvalidParameter("decrement", "at least 0"));
+                }
+                initial
+            },
+            CoolingSchedule::Logarithmic { initial } => initial,
+        };
+
+        if initial >= 0.0 && initial.is_finite() {
+            Ok(())
+        } else {
+            Err(EvolutionError::InvalidParameter("initial", "at least 0"))
+        }
+    }
+}
+
+/// Simulated annealing. Each generation, it creates one neighbour. It always moves to the
+/// neighbour when it is at least as fit. Otherwise, it moves with probability
+/// `exp(-loss / temperature)`, where the loss is how much less fit the neighbour is, see
+/// [Fitness::to_f64](crate::fitness::Fitness::to_f64). The temperature
+/// decreases over time according to the cooling schedule, so that worse neighbours are accepted
+/// less and less often.
+#[derive(Debug)]
+pub struct SimulatedAnnealing {
+    schedule: CoolingSchedule,
+}
+
+impl SimulatedAnnealing {
+    pub fn new(schedule: CoolingSchedule) -> Result<Self, EvolutionError> {
+        schedule.check()?;
+
+        Ok(SimulatedAnnealing {
+            schedule
+        })
+    }
+}
+
+impl<P: Phenotype, G> MoveStrategy<P, G> for SimulatedAnnealing {
+    fn num_neighbours(&self) -> usize {
+        1
+    }
+
+    fn choose(
+        &mut self, generation: usize, current: &(G, P::Fitness), _best_fitness: P::Fitness,
+        neighbours: &[(G, P::Fitness)]
+    ) -> Option<usize> {
+        let neighbour = neighbours.first()?.1;
+        let ordering = P::DIRECTION.compare_optional(&Some(neighbour), &Some(current.1));
+        if ordering != cmp::Ordering::Less {
+            return Some(0);
+        }
+
+        let loss = P::DIRECTION.difference(current.1.to_f64(), neighbour.to_f64());
+        let temperature = self.schedule.temperature(generation) as f64;
+        if temperature > 0.0 && random::rng().gen::<f64>() < (-loss / temperature).exp() {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+/// Tabu search. It moves to the fittest neighbour that is not tabu, even when it is less fit
+/// than the current solution. Solutions that were visited during the last `tenure` moves are
+/// tabu, which keeps the search from returning to them. A tabu neighbour is still allowed when
+/// it is fitter than the best solution found so far.
+#[derive(Debug)]
+pub struct TabuSearch<G> {
+    num_neighbours: usize,
+    tenure: usize,
+    tabu: VecDeque<G>,
+}
+
+impl<G> TabuSearch<G> {
+    /// Creates a tabu search strategy that creates `num_neighbours` neighbours per generation,
+    /// which should be at least 1. The tenure is the number of recently visited solutions that
+    /// are tabu.
+    pub fn new(num_neighbours: usize, tenure: usize) -> Result<Self, EvolutionError> {
+        if num_neighbours == 0 {
+            return Err(EvolutionError::InvalidParameter("num_neighbours", "at least 1"));
+        }
+
+        Ok(TabuSearch {
+            num_neighbours,
+            tenure,
+            tabu: VecDeque::with_capacity(tenure),
+        })
+    }
+}
+
+impl<P: Phenotype, G: PartialEq + Clone + fmt::Debug> MoveStrategy<P, G> for TabuSearch<G> {
+    fn num_neighbours(&self) -> usize {
+        self.num_neighbours
+    }
+
+    fn choose(
+        &mut self, _generation: usize, current: &(G, P::Fitness), best_fitness: P::Fitness,
+        neighbours: &[(G, P::Fitness)]
+    ) -> Option<usize> {
+        // The solution that is moved away from becomes tabu
+        if self.tenure > 0 && self.tabu.back() != Some(&current.0) {
+            if self.tabu.len() == self.tenure {
+                self.tabu.pop_front();
+            }
+            self.tabu.push_back(current.0.clone());
+        }
+
+        fittest::<P, G>(neighbours, |(solution, fitness)| {
+            P::DIRECTION.compare_optional(&Some(*fitness), &Some(best_fitness))
+                == cmp::Ordering::Greater || !self.tabu.contains(solution)
+        })
+    }
+}
+
+/// A single-solution optimiser. The algorithm is determined by its [MoveStrategy].
+pub struct TrajectorySearch<P, G, M, S>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G>,
+    S: MoveStrategy<P, G>,
+{
+    factory: Box<dyn Fn() -> G>,
+    mutation: M,
+    strategy: S,
+    current: Option<(G, P::Fitness)>,
+    best: Option<(G, P::Fitness)>,
+    generation: usize,
+    diversity: Option<fn(&[&G]) -> Diversity>,
+    evaluation: Evaluation<P>,
+}
+
+impl<P, G, M, S> TrajectorySearch<P, G, M, S>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G>,
+    S: MoveStrategy<P, G>,
+{
+    /// Creates an optimiser that starts from a solution created by the factory. Neighbours are
+    /// created by applying the mutation to the current solution.
+    pub fn new(factory: impl Fn() -> G + 'static, mutation: M, strategy: S) -> Self {
+        TrajectorySearch {
+            factory: Box::new(factory),
+            mutation,
+            strategy,
+            current: None,
+            best: None,
+            generation: 0,
+            diversity: None,
+            evaluation: Evaluation::new(),
+        }
+    }
+
+    /// Sets the evaluator that is used to determine the fitness of solutions. See [Evaluator].
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator<P>>) {
+        self.evaluation.set_evaluator(evaluator);
+    }
+
+    /// Adds a sink that the run log is written to.
+    pub fn add_log_sink(&mut self, sink: Box<dyn LogSink>) {
+        self.evaluation.add_log_sink(sink);
+    }
+
+    /// Sets what to do with evaluations that give an invalid fitness, such as NaN. By default,
+    /// such solutions are considered the least fit. See [InvalidFitnessPolicy].
+    pub fn set_invalid_fitness_policy(&mut self, policy: InvalidFitnessPolicy) {
+        self.evaluation.set_invalid_fitness_policy(policy);
+    }
+
+    /// Includes the diversity of the neighbours in the statistics.
+    pub fn enable_diversity(&mut self) where G: GenotypeDistance {
+        self.diversity = Some(G::diversity);
+    }
+
+    /// The number of generations run.
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// The current solution, and its fitness.
+    pub fn current(&self) -> Option<(&G, P::Fitness)> {
+        self.current.as_ref().map(|(solution, fitness)| (solution, *fitness))
+    }
+
+    /// The fittest solution found so far, and its fitness.
+    pub fn best(&self) -> Option<(&G, P::Fitness)> {
+        self.best.as_ref().map(|(solution, fitness)| (solution, *fitness))
+    }
+
+    fn evaluate(&mut self, solutions: Vec<G>) -> Result<Vec<(G, P::Fitness)>, EvolutionError> {
+        let phenotypes: Vec<P> = solutions.iter().map(|solution| solution.express()).collect();
+        let fitnesses = self.evaluation.evaluate(&phenotypes)?;
+
+        Ok(solutions.into_iter().zip(fitnesses).collect())
+    }
+}
+
+impl<P, G, M, S> fmt::Debug for TrajectorySearch<P, G, M, S>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G> + fmt::Debug,
+    S: MoveStrategy<P, G>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TrajectorySearch")
+            .field("mutation", &self.mutation)
+            .field("strategy", &self.strategy)
+            .field("generation", &self.generation)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+impl<P, G, M, S> Optimiser for TrajectorySearch<P, G, M, S>
+where
+    P: Phenotype,
+    G: Genotype<P>,
+    M: Mutation<Genotype = G>,
+    S: MoveStrategy<P, G>,
+{
+    /// Creates the initial solution in the first generation. In later generations, it creates
+    /// neighbours and lets the strategy decide where to move. The statistics cover the
+    /// solutions that were evaluated in the generation.
+    fn step(&mut self) -> Result<Stats, EvolutionError> {
+        let mut evaluated = match &self.current {
+            None => self.evaluate(vec![(self.factory)()])?,
+            Some((current, _)) => {
+                let neighbours: Vec<G> = (0..self.strategy.num_neighbours()).map(|_| {
+                    let mut neighbour = current.clone();
+                    self.mutation.mutate(&mut neighbour);
+                    neighbour
+                }).collect();
+                self.evaluate(neighbours)?
+            },
+        };
+
+        let genotypes: Vec<&G> = evaluated.iter().map(|(solution, _)| solution).collect();
+        let fitnesses: Vec<P::Fitness> = evaluated.iter().map(|(_, fitness)| *fitness).collect();
+        let stats = self.evaluation.stats(&fitnesses, &genotypes, self.diversity)?;
+        self.evaluation.log_generation(self.generation, &stats, None)?;
+
+        let chosen = match &self.current {
+            None => Some(0),
+            Some(current) => {
+                let best_fitness = self.best.as_ref().map_or(current.1, |(_, fitness)| *fitness);
+                self.strategy.choose(self.generation, current, best_fitness, &evaluated)
+            },
+        };
+
+        if let Some(i) = fittest::<P, G>(&evaluated, |_| true) {
+            let (solution, fitness) = &evaluated[i];
+            if P::DIRECTION.compare_optional(
+                &Some(*fitness), &self.best.as_ref().map(|(_, best)| *best)
+            ) == cmp::Ordering::Greater {
+                self.best = Some((solution.clone(), *fitness));
+            }
+        }
+        if let Some(i) = chosen {
+            self.current = Some(evaluated.swap_remove(i));
+        }
+        self.generation += 1;
+
+        Ok(stats)
+    }
+
+    fn evaluations(&self) -> usize {
+        self.evaluation.evaluations()
+    }
+}